@@ -2,56 +2,39 @@
 #![no_main]
 #![feature(abi_avr_interrupt)]
 
-use arduino_hal::{
-    pins, Peripherals
-};
+use arduino_hal::{pins, Peripherals};
+use core::ptr::addr_of_mut;
 use panic_halt as _;
+use radio_uno::logic_capture::LogicCapture;
+use srxl2::logic::{Trigger, TICKS_PER_US};
 
-const BUFFER_SIZE: usize = 0x400;
-const PRESCALER: usize = 69;
-const PRINT_STEP: usize = 0x20;
+const BAUD: u32 = 115200;
 
+/// Start at the first edge after the line has been still for 1ms: the start of a packet
+const TRIGGER: Trigger = Trigger::IdleGap(1000 * TICKS_PER_US);
+/// End once the line has been still for 5ms, after the packet and any reply
+const END_IDLE_TICKS: u32 = 5000 * TICKS_PER_US;
+/// Most SRXL2 packets at 115200 baud; a bit's worth of run takes 2 bytes
+const CAPTURE_SIZE: usize = 0x400;
+
+static mut CAPTURE: [u8; CAPTURE_SIZE] = [0; CAPTURE_SIZE];
+
+/// Watches a serial line on D8, timing each edge with Timer1, and sends each capture as
+/// `srxl2::logic` blocks, for working out what a receiver is sending. `monitor_samples`
+/// takes a rougher look at D2 without the timer.
 #[arduino_hal::entry]
 fn main() -> ! {
     let dp = Peripherals::take().unwrap();
     let pins = pins!(dp);
-    let mut serial = arduino_hal::default_serial!(dp, pins, 9600);
+    let mut serial = arduino_hal::default_serial!(dp, pins, BAUD);
 
-    let mut throttle = 0usize;
-    let mut samples = [0u8; BUFFER_SIZE];
-    let mut head = (0usize, 0usize);
-    let mut offset = 0u32;
+    let buffer = unsafe { &mut *addr_of_mut!(CAPTURE) };
+    let mut capture = LogicCapture::new(dp.TC1, pins.d8, buffer);
+    unsafe { avr_device::interrupt::enable(); }
 
-    let pin = pins.d2.into_pull_up_input();
-
-    // Wait for a character and print current time once it is received
     loop {
-        if let (BUFFER_SIZE, 0) = head {
-            for line_index in (0..BUFFER_SIZE).step_by(PRINT_STEP) {
-                ufmt::uwrite!(&mut serial, "{:04x} | ", offset + line_index as u32).unwrap();
-
-                for x in &samples[line_index .. line_index + PRINT_STEP] {
-                    ufmt::uwrite!(&mut serial, "{:02x} ", *x).unwrap();
-                }
-
-                ufmt::uwriteln!(&mut serial, "").unwrap();
-            }
-            ufmt::uwriteln!(&mut serial, "").unwrap();
-
-            offset += BUFFER_SIZE as u32;
-            head = (0, 0);
-        }
-
-        if throttle == 0 {
-            let mask = 1u8 << head.1;
-            let value = if pin.is_high() { 1u8 } else { 0u8 } << head.1;
-            samples[head.0] = samples[head.0] & !mask | value;
-            head = match head {
-                (byte, bit) if bit < 7 => (byte, bit + 1),
-                (byte, _) => (byte + 1, 0),
-            };
-        }
-
-        throttle = (throttle + 1) % PRESCALER;
+        capture.arm(TRIGGER, END_IDLE_TICKS);
+        while !capture.is_done() {}
+        capture.stream(|byte| serial.write_byte(byte));
     }
 }
@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+use arduino_hal::{
+    hal::port::PD2,
+    pins,
+    port::{
+        mode::{Input, PullUp},
+        Pin,
+    },
+    Peripherals,
+};
+use core::{convert::Infallible, ptr::addr_of_mut};
+use panic_halt as _;
+
+const BAUD: u32 = 115200;
+
+const BUFFER_SIZE: usize = 0x400;
+const PRESCALER: usize = 69;
+const PRINT_STEP: usize = 0x20;
+
+static mut SAMPLES: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
+
+/// Samples D2 in a busy loop and prints the samples in hex, for a first look at a line
+/// without `monitor_raw`'s timing
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = Peripherals::take().unwrap();
+    let pins = pins!(dp);
+    let mut serial = arduino_hal::default_serial!(dp, pins, BAUD);
+
+    let samples = unsafe { &mut *addr_of_mut!(SAMPLES) };
+    print_samples(&mut serial, pins.d2.into_pull_up_input(), samples)
+}
+
+/// Samples every so often, about as fast as the loop goes, 8 to a byte, first sample in
+/// the lowest bit
+fn print_samples(serial: &mut impl ufmt::uWrite<Error = Infallible>, pin: Pin<Input<PullUp>, PD2>, samples: &mut [u8; BUFFER_SIZE]) -> ! {
+    let mut throttle = 0usize;
+    let mut head = (0usize, 0usize);
+    let mut offset = 0u32;
+
+    loop {
+        if let (BUFFER_SIZE, 0) = head {
+            for line_index in (0..BUFFER_SIZE).step_by(PRINT_STEP) {
+                ufmt::uwrite!(serial, "{:04x} | ", offset + line_index as u32).unwrap();
+
+                for x in &samples[line_index .. line_index + PRINT_STEP] {
+                    ufmt::uwrite!(serial, "{:02x} ", *x).unwrap();
+                }
+
+                ufmt::uwriteln!(serial, "").unwrap();
+            }
+            ufmt::uwriteln!(serial, "").unwrap();
+
+            offset += BUFFER_SIZE as u32;
+            head = (0, 0);
+        }
+
+        if throttle == 0 {
+            let mask = 1u8 << head.1;
+            let value = if pin.is_high() { 1u8 } else { 0u8 } << head.1;
+            samples[head.0] = samples[head.0] & !mask | value;
+            head = match head {
+                (byte, bit) if bit < 7 => (byte, bit + 1),
+                (byte, _) => (byte + 1, 0),
+            };
+        }
+
+        throttle = (throttle + 1) % PRESCALER;
+    }
+}
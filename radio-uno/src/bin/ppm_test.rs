@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+use arduino_hal::{
+    Peripherals,
+    pins,
+};
+use panic_halt as _;
+use radio_uno::ppm_output::PpmOutput;
+use srxl2::ppm::{Frame, Timing};
+
+const CHANNELS: usize = 8;
+/// About one frame; the pattern only has to move smoothly, not keep time
+const STEP_MS: u16 = 20;
+
+/// Sends a sweeping test pattern out of D9, for checking a trainer port or PPM input
+#[arduino_hal::entry]
+fn main() -> ! {
+    let dp = Peripherals::take().unwrap();
+    let pins = pins!(dp);
+
+    let mut ppm = PpmOutput::new(dp.TC1, pins.d9.into_output(), Timing::new(), &Frame::test_pattern(CHANNELS, 0));
+    unsafe { avr_device::interrupt::enable(); }
+
+    let mut time_ms = 0u32;
+    loop {
+        arduino_hal::delay_ms(STEP_MS);
+        time_ms = time_ms.wrapping_add(STEP_MS as u32);
+        ppm.set_frame(&Frame::test_pattern(CHANNELS, time_ms));
+    }
+}
@@ -5,16 +5,17 @@ use arduino_hal::{
     Peripherals,
     pins,
 };
+use embedded_io::Write;
 use panic_halt as _;
-use radio_uno::software_serial::SerialWriter;
+use radio_uno::{software_serial::SerialWriter, usart::BufferedSerial};
 
 #[arduino_hal::entry]
 fn main() -> ! {
     let dp = Peripherals::take().unwrap();
     let pins = pins!(dp);
-    let mut serial_hw = arduino_hal::default_serial!(dp, pins, 57600);
+    let mut serial_hw = BufferedSerial::new(dp.USART0, pins.d0.forget_imode(), pins.d1.into_output(), 57600);
 
-    let result = SerialWriter::<1024>::new(
+    let result = SerialWriter::new(
         pins.d2.into_output().downgrade(),
         dp.TC2,
         9600,
@@ -41,13 +42,14 @@ fn main() -> ! {
                 }
                 dbg_counter = 0;
             }
-            serial_sw.process(&mut serial_hw).unwrap();
             test_counter = (test_counter + 1) % 50000;
 
 
             if dbg_counter < 250 {
                 if dbg_counter % 10 == 0 {
                     serial_sw.debug(&mut serial_hw).unwrap();
+                    // Faster than they can be sent: wait rather than drop them
+                    serial_hw.flush().unwrap();
                 }
                 dbg_counter += 1;
             }
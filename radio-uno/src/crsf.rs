@@ -0,0 +1,54 @@
+use embedded_io::{
+    Read,
+    ReadReady,
+    Write,
+};
+use srxl2::{
+    crsf::{Battery, Crsf, FrameBuf},
+    input::ChannelInput,
+};
+
+/// Feeds the bytes from a CRSF receiver into a `ChannelInput`, and sends telemetry back
+/// on the same port. The Uno's USART can't make 420000 baud closely enough, so the
+/// receiver has to be set to a lower rate, e.g. 250000 or 115200.
+pub struct CrsfReceiver<S> {
+    serial: S,
+    decoder: Crsf,
+}
+
+impl<S> CrsfReceiver<S> where S: Read + ReadReady {
+    pub fn new(serial: S) -> Self {
+        Self {
+            serial,
+            decoder: Crsf::new(),
+        }
+    }
+
+    /// Reads every byte waiting on the port, without blocking, and hands each complete
+    /// frame to `input`. Returns the number of frames received.
+    pub fn poll(&mut self, input: &mut ChannelInput, now_ms: u32) -> Result<usize, S::Error> {
+        let mut frames = 0;
+        let mut buf = [0u8; 16];
+        while self.serial.read_ready()? {
+            let len = self.serial.read(&mut buf)?;
+            for byte in &buf[..len] {
+                if let Some(frame) = self.decoder.push(*byte) {
+                    input.update_from_crsf(&frame, now_ms);
+                    frames += 1;
+                }
+            }
+        }
+        Ok(frames)
+    }
+
+    pub fn release(self) -> S {
+        self.serial
+    }
+}
+
+impl<S> CrsfReceiver<S> where S: Write {
+    /// Sends battery telemetry for the receiver to pass down to the transmitter
+    pub fn send_battery(&mut self, battery: &Battery) -> Result<(), S::Error> {
+        self.serial.write_all(FrameBuf::battery(battery).as_slice())
+    }
+}
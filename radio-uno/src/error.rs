@@ -8,13 +8,27 @@ use ufmt::uDisplay;
 pub enum SerialError {
     BufferFull,
     NotImplemented,
+    /// A byte's stop bit was low, so it was received wrong (or the line is in a break)
+    /// and has been dropped
+    Framing,
+    /// A byte's parity bit didn't match its data, and it has been dropped
+    Parity,
+    /// Bytes arrived with the receive buffer full, and have been dropped
+    Overrun,
+    /// A byte sent on a shared line didn't come back as it was sent, so another device
+    /// was sending too, and the rest of the packet has been dropped
+    Collision,
 }
 
 impl Error for SerialError {
     fn kind(&self) -> ErrorKind {
         match self {
             Self::BufferFull => ErrorKind::OutOfMemory,
-            Self::NotImplemented => ErrorKind::Unsupported
+            Self::NotImplemented => ErrorKind::Unsupported,
+            Self::Framing => ErrorKind::InvalidData,
+            Self::Parity => ErrorKind::InvalidData,
+            Self::Overrun => ErrorKind::Other,
+            Self::Collision => ErrorKind::Interrupted,
         }
     }
 }
@@ -24,6 +38,24 @@ impl uDisplay for SerialError {
         match self {
             SerialError::BufferFull => ufmt::uwrite!(fmt, "Buffer full"),
             SerialError::NotImplemented => ufmt::uwrite!(fmt, "Not implemented"),
+            SerialError::Framing => ufmt::uwrite!(fmt, "Framing error"),
+            SerialError::Parity => ufmt::uwrite!(fmt, "Parity error"),
+            SerialError::Overrun => ufmt::uwrite!(fmt, "Receive overrun"),
+            SerialError::Collision => ufmt::uwrite!(fmt, "Bus collision"),
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug)]
+pub enum BindError {
+    /// Remote receivers can only be bound to DSM2 or DSMX air protocols
+    UnsupportedSystem,
+}
+
+impl uDisplay for BindError {
+    fn fmt<W>(&self, fmt: &mut ufmt::Formatter<'_, W>) -> Result<(), W::Error> where W: ufmt::uWrite + ?Sized {
+        match self {
+            BindError::UnsupportedSystem => ufmt::uwrite!(fmt, "Unsupported bind system"),
+        }
+    }
+}
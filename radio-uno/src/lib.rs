@@ -1,5 +1,20 @@
 #![no_std]
 #![feature(abi_avr_interrupt)]
+#![feature(asm_experimental_arch)]
 
+#[cfg(feature = "software-serial")]
 pub mod software_serial;
-pub mod error;
\ No newline at end of file
+pub mod error;
+pub mod satellite_bind;
+pub mod sbus;
+pub mod crsf;
+#[cfg(feature = "rc-capture")]
+pub mod rc_capture;
+#[cfg(feature = "ppm-output")]
+pub mod ppm_output;
+#[cfg(feature = "usart")]
+pub mod usart;
+#[cfg(feature = "logic-capture")]
+pub mod logic_capture;
+#[cfg(any(feature = "software-serial", feature = "usart"))]
+mod ring;
@@ -0,0 +1,273 @@
+//! A one-line logic analyser on D8. Timer1 runs free at the full 16MHz, and its input
+//! capture (ICP1) latches the count in hardware at each edge, so edges are timed to the
+//! tick, 62.5ns, however late the interrupt runs. After each edge the capture is turned
+//! round to catch the next one the other way. The time between edges is run-length
+//! encoded as it comes in, in the `srxl2::logic` format, so most edges take a byte or two
+//! of the buffer.
+//!
+//! An edge that comes before the interrupt has turned the capture round is missed: about
+//! 4µs apart is as close as two edges can be, so 115200 baud is fine and 400000 isn't.
+//! The interrupts are shared with `rc_capture`, which hands them on here while a capture
+//! is running.
+
+use arduino_hal::{
+    hal::port::PB0,
+    pac::{tc1, PORTB, TC1},
+    port::{
+        mode::{Floating, Input},
+        Pin,
+    },
+};
+use avr_device::interrupt::Mutex;
+use core::cell::RefCell;
+use srxl2::logic::{encode_varint, Header, Status, Trigger, MAX_VARINT_BYTES};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Off,
+    /// Timing, with nothing armed yet
+    Ready,
+    /// Waiting for the trigger
+    Armed,
+    Capturing,
+    Done(Status),
+}
+
+struct LogicState {
+    phase: Phase,
+    trigger: Trigger,
+    /// The capture ends once the line has been still this long
+    end_idle_ticks: u32,
+    overflows: u16,
+    /// When the last edge came, or when the capture was armed
+    last_edge: u32,
+    /// The level at the start of the capture
+    start_level: bool,
+    /// The caller's buffer, which the encoded runs go in
+    runs: &'static mut [u8],
+    len: usize,
+}
+
+static LOGIC_STATE: Mutex<RefCell<LogicState>> = Mutex::new(RefCell::new(LogicState {
+    phase: Phase::Off,
+    trigger: Trigger::Immediate,
+    end_idle_ticks: 0,
+    overflows: 0,
+    last_edge: 0,
+    start_level: true,
+    runs: &mut [],
+    len: 0,
+}));
+
+/// Runs `f` on the capture state with interrupts off
+fn with_state<R>(f: impl FnOnce(&mut LogicState) -> R) -> R {
+    avr_device::interrupt::free(|cs| f(&mut LOGIC_STATE.borrow(cs).borrow_mut()))
+}
+
+impl LogicState {
+    /// Ticks since the timer started, wrapping after about 4.5 minutes, from a timer
+    /// value read while interrupts are off
+    fn time(&self, timer: &tc1::RegisterBlock, ticks: u16) -> u32 {
+        let mut overflows = self.overflows;
+        // An overflow the interrupt hasn't counted yet, from before the timer was read
+        if timer.tifr1.read().tov1().bit_is_set() && ticks < 0x8000 {
+            overflows = overflows.wrapping_add(1);
+        }
+        (overflows as u32) << 16 | ticks as u32
+    }
+
+    /// An edge to `level` at `time`
+    fn edge(&mut self, time: u32, level: bool) {
+        let run = time.wrapping_sub(self.last_edge);
+        self.last_edge = time;
+        match self.phase {
+            Phase::Armed => {
+                let triggered = match self.trigger {
+                    Trigger::Immediate => true,
+                    Trigger::FallingEdge => !level,
+                    Trigger::IdleGap(ticks) => run >= ticks,
+                };
+                if triggered {
+                    self.start_level = level;
+                    self.phase = Phase::Capturing;
+                }
+            },
+            Phase::Capturing => {
+                // Runs of 0 end the block; edges a tick apart can't happen anyway
+                match encode_varint(run.max(1), &mut self.runs[self.len..]) {
+                    Some(len) => self.len += len,
+                    None => self.finish(Status::Full),
+                }
+                // Stop while the next run is sure to fit, rather than lose it
+                if self.runs.len() - self.len < MAX_VARINT_BYTES {
+                    self.finish(Status::Full);
+                }
+            },
+            Phase::Off | Phase::Ready | Phase::Done(_) => (),
+        }
+    }
+
+    fn finish(&mut self, status: Status) {
+        self.phase = Phase::Done(status);
+        unsafe { &*TC1::ptr() }.timsk1.modify(|_, w| w.icie1().clear_bit());
+    }
+}
+
+/// The level on D8
+fn pin_level() -> bool {
+    unsafe { (*PORTB::ptr()).pinb.read().bits() & 1 != 0 }
+}
+
+/// Timer1's input capture interrupt, while capturing. `rc_capture` has the vector when
+/// it's built, and hands it on here first; returns false if there's no capture.
+pub(crate) fn timer1_capt() -> bool {
+    with_state(|state| {
+        if state.phase == Phase::Off {
+            return false;
+        }
+
+        let timer = unsafe { &*TC1::ptr() };
+        let time = state.time(timer, timer.icr1.read().bits());
+        // A rising edge leaves the line high; catch the next one the other way
+        let level = timer.tccr1b.read().ices1().bit_is_set();
+        timer.tccr1b.modify(|_, w| w.ices1().bit(!level));
+        // Turning the capture round can set the flag, so clear it after
+        timer.tifr1.write(|w| w.icf1().set_bit());
+        state.edge(time, level);
+
+        // The next edge already came, before the capture was turned round to catch it
+        if pin_level() != level && timer.tifr1.read().icf1().bit_is_clear() {
+            match state.phase {
+                Phase::Capturing => state.finish(Status::EdgesLost),
+                // Nothing recorded yet: catch up with the line and carry on waiting
+                _ => timer.tccr1b.modify(|_, w| w.ices1().bit(level)),
+            }
+        }
+        true
+    })
+}
+
+/// Timer1's overflow interrupt, while capturing. `rc_capture` has the vector when it's
+/// built, and hands it on here first; returns false if there's no capture.
+pub(crate) fn timer1_ovf() -> bool {
+    with_state(|state| {
+        if state.phase == Phase::Off {
+            return false;
+        }
+
+        state.overflows = state.overflows.wrapping_add(1);
+        if state.phase == Phase::Capturing {
+            let timer = unsafe { &*TC1::ptr() };
+            let now = state.time(timer, timer.tcnt1.read().bits());
+            if now.wrapping_sub(state.last_edge) >= state.end_idle_ticks {
+                state.finish(Status::Idle);
+            }
+        }
+        true
+    })
+}
+
+#[cfg(not(feature = "rc-capture"))]
+#[avr_device::interrupt(atmega328p)]
+fn TIMER1_CAPT() {
+    timer1_capt();
+}
+
+#[cfg(not(feature = "rc-capture"))]
+#[avr_device::interrupt(atmega328p)]
+fn TIMER1_OVF() {
+    timer1_ovf();
+}
+
+/// Records the edges on D8, one capture at a time
+pub struct LogicCapture {
+    timer: TC1,
+    pin: Pin<Input<Floating>, PB0>,
+}
+
+impl LogicCapture {
+    /// Takes over Timer1, and starts it running free at 62.5ns per tick. Captures go in
+    /// `buffer`, which takes most edges at a byte or two each; it's the caller's so only
+    /// programs that capture pay for it out of the Uno's 2KB.
+    pub fn new(timer: TC1, pin: Pin<Input<Floating>, PB0>, buffer: &'static mut [u8]) -> Self {
+        timer.timsk1.reset();
+        timer.tccr1a.reset();
+        // No noise canceller: it would hold every edge back 4 ticks, and take glitches
+        // shorter than that out of the capture
+        timer.tccr1b.write(|w| w.cs1().direct());
+        timer.tcnt1.write(|w| w.bits(0));
+        with_state(|state| {
+            state.phase = Phase::Ready;
+            state.overflows = 0;
+            state.runs = buffer;
+            state.len = 0;
+        });
+        timer.tifr1.write(|w| w.tov1().set_bit());
+        timer.timsk1.write(|w| w.toie1().set_bit());
+        Self {
+            timer,
+            pin,
+        }
+    }
+
+    /// Throws away the last capture and waits for `trigger`. From then on, edges are
+    /// recorded until the line has been still for `end_idle_ticks`, give or take the
+    /// 4ms the timer takes to overflow, or the buffer is full.
+    pub fn arm(&mut self, trigger: Trigger, end_idle_ticks: u32) {
+        let timer = &self.timer;
+        with_state(|state| {
+            let level = pin_level();
+            state.trigger = trigger;
+            state.end_idle_ticks = end_idle_ticks;
+            state.last_edge = state.time(timer, timer.tcnt1.read().bits());
+            state.start_level = level;
+            state.len = 0;
+            state.phase = match trigger {
+                Trigger::Immediate => Phase::Capturing,
+                _ => Phase::Armed,
+            };
+
+            timer.tccr1b.modify(|_, w| w.ices1().bit(!level));
+            timer.tifr1.write(|w| w.icf1().set_bit());
+            timer.timsk1.modify(|_, w| w.icie1().set_bit());
+        });
+    }
+
+    /// True once the capture has ended
+    pub fn is_done(&self) -> bool {
+        with_state(|state| matches!(state.phase, Phase::Done(_)))
+    }
+
+    /// Once the capture has ended, hands it to `write` a byte at a time, as a block in
+    /// the `srxl2::logic` format. Returns false, having sent nothing, if it hasn't.
+    pub fn stream(&mut self, mut write: impl FnMut(u8)) -> bool {
+        // The buffer is borrowed out while it's written, so interrupts stay on; nothing
+        // touches it again until the next `arm`
+        let taken = with_state(|state| match state.phase {
+            Phase::Done(status) => {
+                let header = Header { level: state.start_level, trigger: state.trigger };
+                Some((header, status, core::mem::take(&mut state.runs), state.len))
+            },
+            _ => None,
+        });
+        let Some((header, status, runs, runs_len)) = taken else {
+            return false;
+        };
+
+        let (header, len) = header.encode();
+        header[..len].iter().chain(&runs[..runs_len]).chain(&status.encode()).for_each(|byte| write(*byte));
+        with_state(|state| state.runs = runs);
+        true
+    }
+
+    /// Stops the timer and gives back the hardware and the buffer
+    pub fn release(self) -> (TC1, Pin<Input<Floating>, PB0>, &'static mut [u8]) {
+        self.timer.timsk1.reset();
+        self.timer.tccr1b.reset();
+        let buffer = with_state(|state| {
+            state.phase = Phase::Off;
+            core::mem::take(&mut state.runs)
+        });
+        (self.timer, self.pin, buffer)
+    }
+}
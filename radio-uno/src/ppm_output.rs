@@ -0,0 +1,176 @@
+//! Sends a PPM sum signal on D9, e.g. into a transmitter's trainer port or a flight
+//! controller's PPM input. It times the signal the way `SerialWriter` times bits, with a
+//! timer in CTC mode interrupting at each change of level, but on Timer1: its 16-bit
+//! counter covers a whole channel at 0.5µs per tick, and its compare output (OC1A) flips
+//! the pin in hardware, so interrupt latency doesn't move the edges.
+
+use arduino_hal::{
+    hal::port::PB1,
+    pac::{PORTB, TC1},
+    port::{
+        mode::Output,
+        Pin,
+    },
+};
+use avr_device::interrupt::Mutex;
+use core::{
+    cell::RefCell,
+    mem::ManuallyDrop,
+};
+use srxl2::ppm::{Frame, Polarity, Timing, MAX_CHANNELS};
+
+/// Pulse and rest of channel for each channel, then pulse and gap
+const MAX_STEPS: usize = 2 * MAX_CHANNELS + 2;
+
+/// Step durations in compare register values: timer ticks, less one
+struct Steps {
+    ticks: [u16; MAX_STEPS],
+    len: usize,
+}
+
+impl Steps {
+    const fn new() -> Self {
+        Self {
+            ticks: [0; MAX_STEPS],
+            len: 0,
+        }
+    }
+
+    /// Steps longer than the timer can count, about 32ms, are cut short
+    fn from_frame(timing: &Timing, frame: &Frame) -> Self {
+        let mut steps = Self::new();
+        for step in timing.steps(frame) {
+            let ticks = step.duration_us.saturating_mul(2).clamp(1, u16::MAX as u32 + 1);
+            steps.ticks[steps.len] = (ticks - 1) as u16;
+            steps.len += 1;
+        }
+        steps
+    }
+}
+
+struct OutputState {
+    /// The frame being sent
+    steps: Steps,
+    /// The step the pin is on
+    index: usize,
+    /// The frame to send once this one is done, if it has changed
+    next: Option<Steps>,
+}
+
+static OUTPUT_STATE: Mutex<RefCell<OutputState>> = Mutex::new(RefCell::new(OutputState {
+    steps: Steps::new(),
+    index: 0,
+    next: None,
+}));
+
+#[avr_device::interrupt(atmega328p)]
+fn TIMER1_COMPA() {
+    // A `SerialWriter` on TC1 times its bits with this interrupt too; whichever has the
+    // timer turned it on
+    #[cfg(feature = "software-serial")]
+    if crate::software_serial::timer1_compa() {
+        return;
+    }
+
+    avr_device::interrupt::free(|cs| {
+        let mut state = OUTPUT_STATE.borrow(cs).borrow_mut();
+        // The pin has just flipped: time the step it has started
+        state.index += 1;
+        if state.index == state.steps.len {
+            state.index = 0;
+            if let Some(next) = state.next.take() {
+                state.steps = next;
+            }
+        }
+
+        let timer = unsafe { &*TC1::ptr() };
+        timer.ocr1a.write(|w| w.bits(state.steps.ticks[state.index]));
+    })
+}
+
+/// The level on D9, whether the compare output or the port is driving it
+fn pin_level() -> bool {
+    unsafe { (*PORTB::ptr()).pinb.read().bits() & 1 << 1 != 0 }
+}
+
+pub struct PpmOutput {
+    timer: TC1,
+    pin: Pin<Output, PB1>,
+    timing: Timing,
+}
+
+impl PpmOutput {
+    /// Takes over Timer1 and starts sending `frame` over and over
+    pub fn new(timer: TC1, pin: Pin<Output, PB1>, timing: Timing, frame: &Frame) -> Self {
+        timer.timsk1.reset();
+        timer.tccr1b.reset();
+
+        let steps = Steps::from_frame(&timing, frame);
+        let first_ticks = steps.ticks[0];
+        avr_device::interrupt::free(|cs| {
+            *OUTPUT_STATE.borrow(cs).borrow_mut() = OutputState {
+                steps,
+                index: 0,
+                next: None,
+            };
+        });
+
+        // Hand the pin to the compare output, and start it on the first pulse: every
+        // frame has an even number of steps, so each one starts on a pulse too
+        timer.tccr1a.write(|w| w.com1a().match_toggle());
+        if pin_level() != (timing.polarity == Polarity::Positive) {
+            timer.tccr1c.write(|w| w.foc1a().set_bit());
+        }
+
+        timer.ocr1a.write(|w| w.bits(first_ticks));
+        timer.tcnt1.write(|w| w.bits(0));
+        timer.tifr1.write(|w| w.ocf1a().set_bit());
+        timer.timsk1.write(|w| w.ocie1a().set_bit());
+        // CTC mode, counting up to OCR1A, at 0.5µs per tick
+        timer.tccr1b.write(|w| w.wgm1().bits(0b01).cs1().prescale_8());
+
+        Self {
+            timer,
+            pin,
+            timing,
+        }
+    }
+
+    /// Sends `frame` from the start of the next frame on, until it's replaced. A frame
+    /// that doesn't get sent before the next call is dropped.
+    pub fn set_frame(&mut self, frame: &Frame) {
+        let steps = Steps::from_frame(&self.timing, frame);
+        avr_device::interrupt::free(|cs| {
+            OUTPUT_STATE.borrow(cs).borrow_mut().next = Some(steps);
+        });
+    }
+
+    /// Stops the signal, leaving the pin at whatever level it was on, and gives back the
+    /// hardware
+    pub fn release(self) -> (TC1, Pin<Output, PB1>) {
+        let mut output = ManuallyDrop::new(self);
+        output.stop();
+        // `output` is never dropped, so these are the only moves out of it
+        unsafe { (core::ptr::read(&output.timer), core::ptr::read(&output.pin)) }
+    }
+
+    /// Stops the timer, and hands the pin back to the port at the level it was on
+    fn stop(&mut self) {
+        self.timer.timsk1.reset();
+        self.timer.tccr1b.reset();
+        if pin_level() {
+            self.pin.set_high();
+        }
+        else {
+            self.pin.set_low();
+        }
+        self.timer.tccr1a.reset();
+    }
+}
+
+impl Drop for PpmOutput {
+    /// Stops the signal, as `release` does
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,285 @@
+//! Timestamps PPM and PWM edges with Timer1, running free at 0.5µs per tick. PPM comes
+//! in on the input capture pin (ICP1, D8), which latches the timer in hardware on each
+//! leading edge. PWM channels come in on pin-change interrupts from any of D0-D7, and
+//! are timestamped when the interrupt runs, a few microseconds late.
+
+use arduino_hal::{
+    hal::port::PB0,
+    pac::{tc1, EXINT, PORTD, TC1},
+    port::{
+        mode::{Floating, Input},
+        Pin,
+    },
+};
+use avr_device::interrupt::Mutex;
+use core::cell::RefCell;
+use srxl2::{
+    input::ChannelInput,
+    ppm::{Polarity, PpmDecoder, PwmDecoder},
+};
+
+/// Timer1 overflows every 65536 ticks
+const OVERFLOW_US: u32 = 32_768;
+/// Edges held between polls; a PPM frame has up to 13
+const EDGE_BUFFER: usize = 32;
+
+#[derive(Clone, Copy)]
+struct Edge {
+    time_us: u32,
+    /// Port D's pins at the time of the edge
+    levels: u8,
+}
+
+struct CaptureState {
+    overflows: u32,
+    edges: [Edge; EDGE_BUFFER],
+    head: usize,
+    len: usize,
+    overrun: bool,
+}
+
+static CAPTURE_STATE: Mutex<RefCell<CaptureState>> = Mutex::new(RefCell::new(CaptureState::new()));
+
+impl CaptureState {
+    const fn new() -> Self {
+        Self {
+            overflows: 0,
+            edges: [Edge { time_us: 0, levels: 0 }; EDGE_BUFFER],
+            head: 0,
+            len: 0,
+            overrun: false,
+        }
+    }
+
+    /// Microseconds since the timer started, wrapping, from a timer value read while
+    /// interrupts are off
+    fn time_us(&self, timer: &tc1::RegisterBlock, ticks: u16) -> u32 {
+        let mut overflows = self.overflows;
+        // An overflow the interrupt hasn't counted yet, from before the timer was read
+        if timer.tifr1.read().tov1().bit_is_set() && ticks < 0x8000 {
+            overflows = overflows.wrapping_add(1);
+        }
+        overflows.wrapping_mul(OVERFLOW_US).wrapping_add(ticks as u32 >> 1)
+    }
+
+    fn push(&mut self, edge: Edge) {
+        if self.len == EDGE_BUFFER {
+            self.overrun = true;
+            return;
+        }
+        self.edges[(self.head + self.len) % EDGE_BUFFER] = edge;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<Edge> {
+        if self.len == 0 {
+            return None;
+        }
+        let edge = self.edges[self.head];
+        self.head = (self.head + 1) % EDGE_BUFFER;
+        self.len -= 1;
+        Some(edge)
+    }
+}
+
+/// Runs `f` on the capture state with interrupts off
+fn with_state<R>(f: impl FnOnce(&mut CaptureState) -> R) -> R {
+    avr_device::interrupt::free(|cs| f(&mut CAPTURE_STATE.borrow(cs).borrow_mut()))
+}
+
+#[avr_device::interrupt(atmega328p)]
+fn TIMER1_OVF() {
+    // A `LogicCapture` times its edges with Timer1 too; whichever has the timer turned
+    // it on
+    #[cfg(feature = "logic-capture")]
+    if crate::logic_capture::timer1_ovf() {
+        return;
+    }
+
+    with_state(|state| state.overflows = state.overflows.wrapping_add(1));
+}
+
+#[avr_device::interrupt(atmega328p)]
+fn TIMER1_CAPT() {
+    #[cfg(feature = "logic-capture")]
+    if crate::logic_capture::timer1_capt() {
+        return;
+    }
+
+    let timer = unsafe { &*TC1::ptr() };
+    with_state(|state| {
+        let time_us = state.time_us(timer, timer.icr1.read().bits());
+        state.push(Edge { time_us, levels: 0 });
+    });
+}
+
+#[avr_device::interrupt(atmega328p)]
+fn PCINT2() {
+    let timer = unsafe { &*TC1::ptr() };
+    let ticks = timer.tcnt1.read().bits();
+    let levels = unsafe { (*PORTD::ptr()).pind.read().bits() };
+    with_state(|state| {
+        let time_us = state.time_us(timer, ticks);
+        state.push(Edge { time_us, levels });
+    });
+}
+
+/// Starts Timer1 from zero in normal mode at 0.5µs per tick, with the overflow interrupt
+/// on and everything captured so far thrown away
+fn start_timer(timer: &TC1, rising: bool) {
+    timer.timsk1.reset();
+    timer.tccr1a.reset();
+    // The noise canceller holds off a capture until the pin has been steady for 4
+    // cycles, 0.25µs
+    timer.tccr1b.write(|w| w.icnc1().set_bit().ices1().bit(rising).cs1().prescale_8());
+    timer.tcnt1.write(|w| w.bits(0));
+    timer.tifr1.write(|w| w.tov1().set_bit().icf1().set_bit());
+
+    with_state(|state| {
+        state.overflows = 0;
+        state.len = 0;
+        state.overrun = false;
+    });
+    timer.timsk1.write(|w| w.toie1().set_bit());
+}
+
+/// The time Timer1 has counted since `start_timer`, in microseconds
+fn now_us(timer: &TC1) -> u32 {
+    with_state(|state| state.time_us(timer, timer.tcnt1.read().bits()))
+}
+
+/// Takes the next captured edge. If edges were lost since the last call, they are all
+/// thrown away, and this gives port D's pins as they are now instead.
+fn next_edge() -> Result<Option<Edge>, u8> {
+    with_state(|state| {
+        if core::mem::take(&mut state.overrun) {
+            state.len = 0;
+            return Err(unsafe { (*PORTD::ptr()).pind.read().bits() });
+        }
+        Ok(state.pop())
+    })
+}
+
+/// Decodes a PPM sum signal on D8
+pub struct PpmCapture {
+    timer: TC1,
+    pin: Pin<Input<Floating>, PB0>,
+    decoder: PpmDecoder,
+}
+
+impl PpmCapture {
+    /// Takes over Timer1 and starts timing the leading edge of each pulse
+    pub fn new(timer: TC1, pin: Pin<Input<Floating>, PB0>, polarity: Polarity) -> Self {
+        start_timer(&timer, polarity == Polarity::Positive);
+        timer.timsk1.modify(|_, w| w.icie1().set_bit());
+        Self {
+            timer,
+            pin,
+            decoder: PpmDecoder::new(),
+        }
+    }
+
+    /// Decodes the edges captured since the last call, and hands each complete frame to
+    /// `input`. Returns the number of frames received. Has to be called at least every
+    /// couple of frames, or edges are lost and the frame they fell in with them.
+    pub fn poll(&mut self, input: &mut ChannelInput, now_ms: u32) -> usize {
+        let mut frames = 0;
+        loop {
+            match next_edge() {
+                Err(_) => self.decoder.reset(),
+                Ok(None) => return frames,
+                Ok(Some(edge)) => if let Some(frame) = self.decoder.push(edge.time_us) {
+                    input.update_from_ppm(&frame, now_ms);
+                    frames += 1;
+                },
+            }
+        }
+    }
+
+    /// True if no frame has been decoded in the last `ppm::HOLD_US`
+    pub fn is_signal_lost(&self) -> bool {
+        self.decoder.is_signal_lost(now_us(&self.timer))
+    }
+
+    /// Stops the timer and gives back the hardware
+    pub fn release(self) -> (TC1, Pin<Input<Floating>, PB0>) {
+        self.timer.timsk1.reset();
+        self.timer.tccr1b.reset();
+        (self.timer, self.pin)
+    }
+}
+
+/// Measures PWM pulses from a receiver's servo outputs on up to 8 pins of port D. Each
+/// channel is given as its pin number, D0-D7, and the pins have to be inputs. Leave out
+/// D0 and D1 if the USB serial port is in use.
+pub struct PwmCapture<const N: usize> {
+    timer: TC1,
+    exint: EXINT,
+    pins: [u8; N],
+    levels: u8,
+    decoder: PwmDecoder<N>,
+}
+
+impl<const N: usize> PwmCapture<N> {
+    /// Takes over Timer1 and the port D pin-change interrupt, and starts timing pulses on
+    /// `pins`
+    pub fn new(timer: TC1, exint: EXINT, pins: [u8; N]) -> Self {
+        let mask = pins.iter().fold(0u8, |mask, pin| mask | 1 << (pin & 7));
+        start_timer(&timer, true);
+        let levels = unsafe { (*PORTD::ptr()).pind.read().bits() };
+        exint.pcmsk2.write(|w| unsafe { w.bits(mask) });
+        exint.pcifr.write(|w| unsafe { w.bits(1 << 2) });
+        exint.pcicr.modify(|r, w| unsafe { w.bits(r.bits() | 1 << 2) });
+        Self {
+            timer,
+            exint,
+            pins,
+            levels,
+            decoder: PwmDecoder::new(),
+        }
+    }
+
+    /// Measures the pulses captured since the last call, and hands a frame to `input` each
+    /// time every channel has pulsed. Returns the number of frames received.
+    pub fn poll(&mut self, input: &mut ChannelInput, now_ms: u32) -> usize {
+        let mut frames = 0;
+        loop {
+            let edge = match next_edge() {
+                Err(levels) => {
+                    self.levels = levels;
+                    self.decoder.reset();
+                    continue;
+                },
+                Ok(None) => return frames,
+                Ok(Some(edge)) => edge,
+            };
+
+            let changed = edge.levels ^ self.levels;
+            self.levels = edge.levels;
+            for (channel, pin) in self.pins.iter().enumerate() {
+                let mask = 1 << (pin & 7);
+                if changed & mask == 0 {
+                    continue;
+                }
+                if let Some(frame) = self.decoder.edge(channel, edge.levels & mask != 0, edge.time_us) {
+                    input.update_from_ppm(&frame, now_ms);
+                    frames += 1;
+                }
+            }
+        }
+    }
+
+    /// True if some channel hasn't pulsed in the last `ppm::HOLD_US`
+    pub fn is_signal_lost(&self) -> bool {
+        self.decoder.is_signal_lost(now_us(&self.timer))
+    }
+
+    /// Stops the timer and the pin-change interrupt, and gives back the hardware
+    pub fn release(self) -> (TC1, EXINT) {
+        self.exint.pcicr.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << 2)) });
+        self.exint.pcmsk2.reset();
+        self.timer.timsk1.reset();
+        self.timer.tccr1b.reset();
+        (self.timer, self.exint)
+    }
+}
@@ -0,0 +1,42 @@
+use arduino_hal::port::{
+    mode::Output,
+    Pin,
+    PinOps,
+};
+use srxl2::{
+    bind::BindStatus,
+    satellite::{BindPulses, Remote},
+};
+use crate::error::BindError;
+
+/// Long enough for a remote receiver to fully shut down before it's powered up again
+pub const POWER_OFF_MS: u16 = 100;
+
+/// Puts a Spektrum remote receiver into bind mode for `system`. The remote only listens
+/// for bind pulses just after power-up, so `power` has to switch its supply (high is on).
+/// Blocks for about 175ms. Afterwards `data` should go back to being the serial input,
+/// since the remote drives it from then on.
+pub fn bind<D: PinOps, P: PinOps>(data: &mut Pin<Output, D>, power: &mut Pin<Output, P>, system: BindStatus, remote: Remote) -> Result<(), BindError> {
+    let pulses = match BindPulses::new(system, remote) {
+        None => return Err(BindError::UnsupportedSystem),
+        Some(pulses) => pulses,
+    };
+
+    // Hold data low too while the remote is off, so it isn't powered through that pin
+    data.set_low();
+    power.set_low();
+    arduino_hal::delay_ms(POWER_OFF_MS);
+
+    power.set_high();
+    for step in pulses {
+        if step.high {
+            data.set_high();
+        }
+        else {
+            data.set_low();
+        }
+        arduino_hal::delay_us(step.duration_us);
+    }
+
+    Ok(())
+}
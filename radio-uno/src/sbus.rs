@@ -0,0 +1,52 @@
+use embedded_io::{
+    Read,
+    ReadReady,
+    Write,
+};
+use srxl2::{
+    input::ChannelInput,
+    sbus::{Frame, Sbus},
+};
+
+/// Feeds the bytes from an SBUS receiver into a `ChannelInput`. The serial port has to
+/// run at `sbus::BAUD`, 8E2, with the line inverted in hardware (the Uno's pins can't
+/// invert on their own).
+pub struct SbusReceiver<R> {
+    serial: R,
+    decoder: Sbus,
+}
+
+impl<R> SbusReceiver<R> where R: Read + ReadReady {
+    pub fn new(serial: R) -> Self {
+        Self {
+            serial,
+            decoder: Sbus::new(),
+        }
+    }
+
+    /// Reads every byte waiting on the port, without blocking, and hands each complete
+    /// frame to `input`. Returns the number of frames received.
+    pub fn poll(&mut self, input: &mut ChannelInput, now_ms: u32) -> Result<usize, R::Error> {
+        let mut frames = 0;
+        let mut buf = [0u8; 16];
+        while self.serial.read_ready()? {
+            let len = self.serial.read(&mut buf)?;
+            for byte in &buf[..len] {
+                if let Some(frame) = self.decoder.push(*byte) {
+                    input.update_from_sbus(&frame, now_ms);
+                    frames += 1;
+                }
+            }
+        }
+        Ok(frames)
+    }
+
+    pub fn release(self) -> R {
+        self.serial
+    }
+}
+
+/// Sends a frame to an SBUS device, e.g. a servo or a flight controller under test
+pub fn send<W>(serial: &mut W, frame: &Frame) -> Result<(), W::Error> where W: Write {
+    serial.write_all(&frame.encode())
+}
@@ -1,233 +1,977 @@
 use arduino_hal::{
     clock::Clock,
-    pac::TC2,
+    hal::port::PD2,
+    pac::{portd, EXINT, PORTD, TC1, TC2},
     port::{
-        mode::Output,
+        mode::{Input, Output, PullUp},
         Pin,
     }, usart::{UsartOps, UsartWriter},
 };
 use embedded_hal::digital::OutputPin;
 use embedded_io::{
     ErrorType,
+    Read,
+    ReadReady,
     Write,
     WriteReady,
 };
+use avr_device::interrupt::Mutex;
 use core::{
-    mem::MaybeUninit,
-    ops::RangeInclusive,
+    cell::RefCell,
+    mem::ManuallyDrop,
 };
-use ufmt::{derive::uDebug, uWrite};
-use crate::error::SerialError;
+use srxl2::{
+    handshake::{Baud, BaudRates},
+    uart::{BaudDivider, Bits, FrameError, Framing, Sampler},
+    Transport,
+};
+use ufmt::uWrite;
+use crate::{error::SerialError, ring::Ring};
+use self::private::{WriterSlot, WriterState};
+
+/// Bytes waiting to go out of a `SerialWriter`
+const WRITE_BUFFER: usize = 64;
+
+/// What the timers a `SerialWriter` runs on have in common, out of reach of other crates
+mod private {
+    use super::*;
+
+    pub struct WriterState {
+        pub(super) pin: Pin<Output>,
+        pub(super) framing: Framing,
+        /// The rest of the word going out
+        pub(super) bits: Option<Bits>,
+        pub(super) queue: Ring<WRITE_BUFFER>,
+        /// Set when the last stop bit of the queue has gone out
+        pub(super) complete: bool,
+    }
 
-/// A software serial implementation that uses two GPIO pins for RX and TX.
-/// High level (1) on TX is idle.
-/// Start bit is 0, then 8 data bits, then stop bit (1)
+    /// A writer's state, there while the writer has the timer
+    pub type WriterSlot = Mutex<RefCell<Option<WriterState>>>;
 
-struct InterruptState {
-    pin: Pin<Output>,
-    next_bit: Option<bool>,
+    pub trait Sealed {
+        /// The state the timer's interrupt sends from
+        fn writer() -> &'static WriterSlot;
+        /// Runs the timer in CTC mode, with a compare match every bit time at `baud`, and
+        /// the interrupt off
+        fn start(&mut self, baud: u32) -> Result<BaudDivider, &'static str>;
+        fn stop(&mut self);
+        /// Turns the interrupt on, if it's off, with the first bit a bit time from now
+        fn wake(&self);
+    }
 }
 
-static mut INTERRUPT_STATE: MaybeUninit<InterruptState> = MaybeUninit::uninit();
+impl WriterState {
+    /// The start of the next bit time: sets the pin to the next bit, taking the next byte
+    /// from the queue once a word's last stop bit has gone out. Returns false once there's
+    /// nothing left to send.
+    fn next_bit(&mut self) -> bool {
+        let mut level = self.bits.as_mut().and_then(|bits| bits.next());
+        if level.is_none() {
+            self.bits = self.queue.pop().map(|byte| self.framing.bits(byte as u16));
+            level = self.bits.as_mut().and_then(|bits| bits.next());
+        }
+
+        match level {
+            Some(level) => {
+                self.pin.set_state(level.into()).unwrap();
+                true
+            },
+            None => {
+                self.complete = true;
+                false
+            },
+        }
+    }
+}
+
+static TC1_WRITER: WriterSlot = Mutex::new(RefCell::new(None));
+static TC2_WRITER: WriterSlot = Mutex::new(RefCell::new(None));
 
 #[avr_device::interrupt(atmega328p)]
-unsafe fn TIMER2_COMPA() {
-    avr_device::interrupt::CriticalSection::new();
+fn TIMER2_COMPA() {
+    avr_device::interrupt::free(|cs| {
+        let sending = TC2_WRITER.borrow(cs).borrow_mut().as_mut().is_some_and(WriterState::next_bit);
+        if !sending {
+            // Nothing left to send: sleep until something is queued
+            unsafe { &*TC2::ptr() }.timsk2.write(|w| w.ocie2a().clear_bit());
+        }
+    })
+}
 
-    #[allow(static_mut_refs)]
-    let state = &mut *INTERRUPT_STATE.as_mut_ptr();
+/// Timer1's compare A interrupt, for a writer on TC1. `ppm_output` has the vector when
+/// it's built, and hands it on here first; returns false if there's no writer on TC1.
+pub(crate) fn timer1_compa() -> bool {
+    avr_device::interrupt::free(|cs| {
+        let mut writer = TC1_WRITER.borrow(cs).borrow_mut();
+        let Some(state) = writer.as_mut() else {
+            return false;
+        };
+        if !state.next_bit() {
+            unsafe { &*TC1::ptr() }.timsk1.modify(|_, w| w.ocie1a().clear_bit());
+        }
+        true
+    })
+}
 
-    if let Some(next_bit) = state.next_bit.take() {
-        // write next bit to pin
-        state.pin.set_state(next_bit.into()).unwrap();
-    }
+#[cfg(not(feature = "ppm-output"))]
+#[avr_device::interrupt(atmega328p)]
+fn TIMER1_COMPA() {
+    timer1_compa();
 }
 
-#[derive(uDebug)]
-pub enum WriterState {
-    Idle,
-    Start,
-    Data(u8),
-    Stop,
+/// A timer a `SerialWriter` can time its bits with. TC1 and TC2 each have their own
+/// interrupt and state, so there can be a writer on each at once, and since the timer is
+/// moved into its writer, no more than one on either. A `PpmOutput` takes TC1 the same
+/// way, so it can't share the timer with a writer either.
+pub trait WriterTimer: private::Sealed {}
+
+impl WriterTimer for TC1 {}
+impl WriterTimer for TC2 {}
+
+/// The timer setting nearest to `baud`, if it's within `MAX_BAUD_ERROR_PERMILLE`
+fn divider(baud: u32, prescales: &[u32], max_count: u32) -> Result<BaudDivider, &'static str> {
+    let divider = BaudDivider::closest(arduino_hal::DefaultClock::FREQ, baud, prescales, max_count)
+        .ok_or("Baud rate out of range for software serial")?;
+    within_tolerance(divider)
 }
 
-pub struct SerialWriter<const B: usize> {
-    clock: TC2,
-    head: usize,
-    tail: usize,
-    size: usize,
-    buf: [u8; B],
-    next_send: WriterState,
+fn within_tolerance(divider: BaudDivider) -> Result<BaudDivider, &'static str> {
+    if !divider.is_within_tolerance() {
+        return Err("Baud rate too far off for software serial");
+    }
+    Ok(divider)
 }
 
-impl<const B: usize> SerialWriter<B> {
-    pub fn new(pin: Pin<Output>, mut clock: TC2, baud: u32) -> Result<Self, &'static str> {
+impl private::Sealed for TC1 {
+    fn writer() -> &'static WriterSlot {
+        &TC1_WRITER
+    }
+
+    fn start(&mut self, baud: u32) -> Result<BaudDivider, &'static str> {
+        self.timsk1.modify(|_, w| w.ocie1a().clear_bit());
+        let divider = divider(baud, &[1, 8, 64, 256, 1024], 0x1_0000)?;
+        self.tccr1a.reset();
+        // CTC mode, counting up to OCR1A
+        self.tccr1b.write(|w| {
+            let w = w.wgm1().bits(0b01);
+            match divider.prescale {
+                1 => w.cs1().direct(),
+                8 => w.cs1().prescale_8(),
+                64 => w.cs1().prescale_64(),
+                256 => w.cs1().prescale_256(),
+                _ => w.cs1().prescale_1024(),
+            }
+        });
+        self.ocr1a.write(|w| w.bits((divider.count - 1) as u16));
+        Ok(divider)
+    }
+
+    fn stop(&mut self) {
+        self.timsk1.modify(|_, w| w.ocie1a().clear_bit());
+        self.tccr1b.reset();
+    }
+
+    fn wake(&self) {
+        if self.timsk1.read().ocie1a().bit_is_clear() {
+            self.tcnt1.write(|w| w.bits(0));
+            self.tifr1.write(|w| w.ocf1a().set_bit());
+            self.timsk1.modify(|_, w| w.ocie1a().set_bit());
+        }
+    }
+}
+
+impl private::Sealed for TC2 {
+    fn writer() -> &'static WriterSlot {
+        &TC2_WRITER
+    }
+
+    fn start(&mut self, baud: u32) -> Result<BaudDivider, &'static str> {
+        self.timsk2.write(|w| w.ocie2a().clear_bit());
+        let divider = divider(baud, &[1, 8, 32, 64, 128, 256, 1024], 0x100)?;
         // set timer to "clear timer on compare match" (CTC) mode
-        clock.tccr2a.write(|w| w.wgm2().ctc());
+        self.tccr2a.write(|w| w.wgm2().ctc());
+        self.tccr2b.write(|w| match divider.prescale {
+            1 => w.cs2().direct(),
+            8 => w.cs2().prescale_8(),
+            32 => w.cs2().prescale_32(),
+            64 => w.cs2().prescale_64(),
+            128 => w.cs2().prescale_128(),
+            256 => w.cs2().prescale_256(),
+            _ => w.cs2().prescale_1024(),
+        });
+        self.ocr2a.write(|w| w.bits((divider.count - 1) as u8));
+        Ok(divider)
+    }
 
-        let prescale = Self::calc_prescale(baud, &mut clock)?;
-        Self::calc_compare(baud, prescale, &mut clock);
+    fn stop(&mut self) {
+        self.timsk2.write(|w| w.ocie2a().clear_bit());
+        self.tccr2b.reset();
+    }
 
-        unsafe {
-            INTERRUPT_STATE = MaybeUninit::new(InterruptState {
-                pin: pin,
-                next_bit: None,
-            });
-            core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    fn wake(&self) {
+        if self.timsk2.read().ocie2a().bit_is_clear() {
+            self.tcnt2.write(|w| w.bits(0));
+            self.tifr2.write(|w| w.ocf2a().set_bit());
+            self.timsk2.write(|w| w.ocie2a().set_bit());
         }
+    }
+}
 
-        Ok(Self {
-            clock,
-            head: 0,
-            tail: 0,
-            size: 0,
-            buf: [0; B],
-            next_send: WriterState::Idle,
-        })
+/// Sends serial on any pin, in the background: a timer interrupts once per bit time and
+/// shifts the bytes out of a ring buffer, so the main loop only has to queue them. The
+/// line idles at the stop bit's level, and each word is a start bit, the data bits, an
+/// optional parity bit, and one or two stop bits, as `Framing` lays them out.
+pub struct SerialWriter<T: WriterTimer> {
+    timer: T,
+    baud: BaudDivider,
+}
+
+impl<T: WriterTimer> SerialWriter<T> {
+    /// 8N1
+    pub fn new(pin: Pin<Output>, timer: T, baud: u32) -> Result<Self, &'static str> {
+        Self::with_framing(pin, timer, baud, Framing::new())
     }
 
-    pub fn process<T>(&mut self, serial: &mut T) -> Result<(), T::Error> where T: ufmt::uWrite {
-        if self.size == 0 {
-            // disable overflow clock interrupt
-            self.clock.timsk2.write(|w| w.ocie2a().clear_bit());
-            return Ok(());
+    /// Bytes are sent as words of `framing.data_bits`, so with 9 data bits the ninth is
+    /// always 0, and with 7 the top bit of each byte is dropped. Fails if the timer can't
+    /// get within `MAX_BAUD_ERROR_PERMILLE` of `baud`, or if it already has a writer,
+    /// which takes a stolen peripheral.
+    pub fn with_framing(mut pin: Pin<Output>, mut timer: T, baud: u32, framing: Framing) -> Result<Self, &'static str> {
+        if !framing.is_valid() {
+            return Err("Unsupported serial framing");
         }
-        else {
-            self.clock.timsk2.write(|w| w.ocie2a().set_bit());
+        if avr_device::interrupt::free(|cs| T::writer().borrow(cs).borrow().is_some()) {
+            return Err("Timer already has a serial writer");
         }
 
-        let int_state = unsafe {
-            #[allow(static_mut_refs)]
-            &mut *INTERRUPT_STATE.as_mut_ptr()
-        };
+        let baud = timer.start(baud)?;
+        pin.set_state(framing.idle_level().into()).unwrap();
+        avr_device::interrupt::free(|cs| {
+            *T::writer().borrow(cs).borrow_mut() = Some(WriterState {
+                pin,
+                framing,
+                bits: None,
+                queue: Ring::new(),
+                complete: false,
+            });
+        });
 
-        if int_state.next_bit.is_none() {
-            //ufmt::uwriteln!(serial, "Sending next bit...")?;
-            int_state.next_bit = self.send_next_bit();
-        }
+        Ok(Self {
+            timer,
+            baud,
+        })
+    }
 
-        Ok(())
+    /// The baud rate the timer makes, and how far it is from the one asked for
+    pub fn baud(&self) -> BaudDivider {
+        self.baud
+    }
+
+    /// Runs `f` on the writer's state with interrupts off
+    fn with_state<R>(&self, f: impl FnOnce(&mut WriterState) -> R) -> R {
+        avr_device::interrupt::free(|cs| {
+            // Only `new` puts the state in, and only `release` or a drop takes it out
+            f(T::writer().borrow(cs).borrow_mut().as_mut().unwrap())
+        })
+    }
+
+    /// True while bytes are queued or going out
+    pub fn is_sending(&self) -> bool {
+        self.with_state(|state| state.bits.is_some() || !state.queue.is_empty())
     }
 
-    pub fn debug<T>(&self, serial: &mut T) -> Result<(), T::Error> where T: ufmt::uWrite {
-        ufmt::uwriteln!(serial,
-            "SerialWriter: head={}, tail={}, size={}, next_send={:?}",
-            self.head, self.tail, self.size, self.next_send)
+    /// True once after everything queued has gone out, down to the last stop bit; cleared
+    /// by reading it or by queuing more
+    pub fn take_transmit_complete(&mut self) -> bool {
+        self.with_state(|state| core::mem::take(&mut state.complete))
     }
 
-    fn calc_prescale(baud: u32, clock: &mut TC2) -> Result<u32, &'static str> {
-        const NATIVE_TICK_S: f32 = 1f32 / arduino_hal::DefaultClock::FREQ as f32;
-        const PRESCALE_1: RangeInclusive<f32>    = NATIVE_TICK_S * 1.0    ..= NATIVE_TICK_S * 1.0 * 256.0;
-        const PRESCALE_8: RangeInclusive<f32>    = NATIVE_TICK_S * 8.0    ..= NATIVE_TICK_S * 8.0 * 256.0;
-        const PRESCALE_32: RangeInclusive<f32>   = NATIVE_TICK_S * 32.0   ..= NATIVE_TICK_S * 32.0 * 256.0;
-        const PRESCALE_64: RangeInclusive<f32>   = NATIVE_TICK_S * 64.0   ..= NATIVE_TICK_S * 64.0 * 256.0;
-        const PRESCALE_128: RangeInclusive<f32>  = NATIVE_TICK_S * 128.0  ..= NATIVE_TICK_S * 128.0 * 256.0;
-        const PRESCALE_256: RangeInclusive<f32>  = NATIVE_TICK_S * 256.0  ..= NATIVE_TICK_S * 256.0 * 256.0;
-        const PRESCALE_1024: RangeInclusive<f32> = NATIVE_TICK_S * 1024.0 ..= NATIVE_TICK_S * 1024.0 * 256.0;
+    pub fn debug<W>(&self, serial: &mut W) -> Result<(), W::Error> where W: ufmt::uWrite {
+        let (queued, sending) = self.with_state(|state| (state.queue.len(), state.bits.is_some()));
+        ufmt::uwriteln!(serial, "SerialWriter: queued={}, sending={:?}", queued, sending)
+    }
+
+    /// Stops sending, dropping anything still queued, and gives back the hardware
+    pub fn release(self) -> (Pin<Output>, T) {
+        let mut writer = ManuallyDrop::new(self);
+        let state = writer.stop();
+        // `writer` is never dropped, so this is the only move out of it
+        let timer = unsafe { core::ptr::read(&writer.timer) };
+        (state.unwrap().pin, timer)
+    }
 
-        let freq_s = 1.0 / baud as f32;
-        if PRESCALE_1.contains(&freq_s) {
-            // set clock prescaler to 1:1
-            clock.tccr2b.write(|w| w.cs2().direct());
-            Ok(1)
+    /// Stops the timer and frees its slot for the next writer
+    fn stop(&mut self) -> Option<WriterState> {
+        self.timer.stop();
+        avr_device::interrupt::free(|cs| T::writer().borrow(cs).borrow_mut().take())
+    }
+}
+
+impl<T: WriterTimer> Drop for SerialWriter<T> {
+    /// Stops sending, as `release` does, dropping the pin and timer with it
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl<T: WriterTimer> ErrorType for SerialWriter<T> {
+    type Error = SerialError;
+}
+
+impl<T: WriterTimer> Write for SerialWriter<T> {
+    /// Queues as much of `buf` as fits, blocking until there's room for at least one byte
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
         }
-        else if PRESCALE_8.contains(&freq_s) {
-            clock.tccr2b.write(|w| w.cs2().prescale_8());
-            Ok(8)
+
+        loop {
+            let len = self.with_state(|state| {
+                let len = buf.iter().take_while(|byte| state.queue.push(**byte)).count();
+                if len > 0 {
+                    state.complete = false;
+                    self.timer.wake();
+                }
+                len
+            });
+            if len > 0 {
+                return Ok(len);
+            }
         }
-        else if PRESCALE_32.contains(&freq_s) {
-            clock.tccr2b.write(|w| w.cs2().prescale_32());
-            Ok(32)
+    }
+
+    /// Blocks until everything queued has gone out, down to the last stop bit
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        while self.is_sending() {}
+        Ok(())
+    }
+}
+
+impl<T: WriterTimer> WriteReady for SerialWriter<T> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.with_state(|state| !state.queue.is_full()))
+    }
+}
+
+impl<T: WriterTimer> uWrite for SerialWriter<T> {
+    type Error = SerialError;
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.write_all(s.as_bytes())
+    }
+}
+
+/// Bytes received and not yet read
+const RX_BUFFER: usize = 64;
+/// Bytes waiting to go out on a half-duplex line; room for the longest SRXL2 packet
+const TX_BUFFER: usize = srxl2::packet::SRXL_MAX_BUFFER_SIZE;
+/// Roughly the cycles from an edge or compare match to the interrupt reading the timer or
+/// the pin, taken off the time to the first sample for each
+const INTERRUPT_LATENCY_CYCLES: u32 = 40;
+/// Bit times the line has to be idle, after the last byte on it, before a half-duplex
+/// line starts sending: one character, so the device that sent it has let go of the line
+pub const GUARD_BITS: u32 = 10;
+
+/// Bit timing in Timer1 ticks
+#[derive(Clone, Copy)]
+struct LineTiming {
+    bit_ticks: u16,
+    /// From the start bit's falling edge being seen to the middle of the first data bit
+    first_sample_ticks: u16,
+    /// From the middle of a stop bit to the end of the guard time after it
+    guard_ticks: u16,
+    /// The `bit_bang` delay count, when words go out in one go rather than a bit per
+    /// interrupt
+    fast_delay: Option<u8>,
+}
+
+impl LineTiming {
+    const fn new() -> Self {
+        Self {
+            bit_ticks: 0,
+            first_sample_ticks: 0,
+            guard_ticks: 0,
+            fast_delay: None,
         }
-        else if PRESCALE_64.contains(&freq_s) {
-            clock.tccr2b.write(|w| w.cs2().prescale_64());
-            Ok(64)
+    }
+
+    /// The timing for `baud` with Timer1 at `prescale`, and the rate it makes, or None if
+    /// the guard time doesn't fit in the 16-bit timer
+    fn for_baud(baud: u32, prescale: u32) -> Option<(Self, BaudDivider)> {
+        let divider = BaudDivider::closest(arduino_hal::DefaultClock::FREQ, baud, &[prescale], u16::MAX as u32)?;
+        let cycles_per_bit = divider.count * prescale;
+        let first_sample_cycles = (cycles_per_bit * 3 / 2).saturating_sub(2 * INTERRUPT_LATENCY_CYCLES);
+        let guard_cycles = cycles_per_bit * GUARD_BITS + cycles_per_bit / 2;
+        if guard_cycles / prescale > u16::MAX as u32 {
+            return None;
         }
-        else if PRESCALE_128.contains(&freq_s) {
-            clock.tccr2b.write(|w| w.cs2().prescale_128());
-            Ok(128)
+
+        let timing = Self {
+            bit_ticks: divider.count as u16,
+            first_sample_ticks: (first_sample_cycles / prescale) as u16,
+            guard_ticks: (guard_cycles / prescale) as u16,
+            fast_delay: None,
+        };
+        Some((timing, divider))
+    }
+}
+
+/// Cycles `bit_bang` takes for each bit, besides its delay loop
+const FAST_BIT_CYCLES: u32 = 10;
+/// Cycles each pass of `bit_bang`'s delay loop takes
+const FAST_DELAY_CYCLES: u32 = 3;
+/// Timer1 ticks from the end of a word sent in one go to the interrupt that sends the
+/// next: long enough for the interrupt to have returned, so others get a look in
+const FAST_GAP_TICKS: u16 = 64;
+
+/// The `bit_bang` delay count nearest to `baud`, and the rate it makes
+fn fast_timing(baud: u32) -> Result<(u8, BaudDivider), &'static str> {
+    let freq = arduino_hal::DefaultClock::FREQ;
+    // Rounded: FREQ / baud = FAST_BIT_CYCLES + FAST_DELAY_CYCLES * delay
+    let delay = (2 * freq).saturating_sub((2 * FAST_BIT_CYCLES - FAST_DELAY_CYCLES) * baud) / (2 * FAST_DELAY_CYCLES * baud);
+    let delay = delay.clamp(1, u8::MAX as u32);
+    let divider = within_tolerance(BaudDivider::new(freq, baud, 1, FAST_BIT_CYCLES + FAST_DELAY_CYCLES * delay))?;
+    Ok((delay as u8, divider))
+}
+
+/// Writes `high` or `low` to `port` for each of the `count` levels in `levels`, lowest
+/// bit first, `FAST_BIT_CYCLES + FAST_DELAY_CYCLES * delay` cycles apart. Both branches
+/// of each bit take the same time, so only the clock sets the baud rate; an interrupt
+/// in the middle would stretch a bit, so they have to be off.
+unsafe fn bit_bang(port: *mut u8, high: u8, low: u8, levels: u16, count: u8, delay: u8) {
+    core::arch::asm!(
+        "1:",
+        "mov {level}, {low}",
+        "sbrc {levels_lo}, 0",
+        "mov {level}, {high}",
+        "st Z, {level}",
+        "lsr {levels_hi}",
+        "ror {levels_lo}",
+        "mov {wait}, {delay}",
+        "2:",
+        "dec {wait}",
+        "brne 2b",
+        "dec {count}",
+        "brne 1b",
+        in("Z") port,
+        high = in(reg) high,
+        low = in(reg) low,
+        delay = in(reg) delay,
+        levels_lo = inout(reg) levels as u8 => _,
+        levels_hi = inout(reg) (levels >> 8) as u8 => _,
+        count = inout(reg) count => _,
+        level = out(reg) _,
+        wait = out(reg) _,
+        options(nostack),
+    );
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Line {
+    /// Waiting for a start bit, or for bytes to send
+    Idle,
+    /// Sampling a word coming in
+    Receiving,
+    /// Waiting out the guard time after the last byte on the line, before sending
+    Guard,
+    /// Sending a word
+    Sending,
+}
+
+struct LineState {
+    timing: LineTiming,
+    /// Timing to switch to once the bytes queued have been sent
+    next_timing: Option<LineTiming>,
+    /// False for a receive-only line, which never waits out a guard time
+    half_duplex: bool,
+    line: Line,
+    sampler: Sampler,
+    /// The rest of the word going out
+    bits: Option<Bits>,
+    /// Level the pin is being driven to while sending
+    high: bool,
+    rx: Ring<RX_BUFFER>,
+    tx: Ring<TX_BUFFER>,
+    /// The first error since the last read
+    error: Option<SerialError>,
+}
+
+static LINE_STATE: Mutex<RefCell<LineState>> = Mutex::new(RefCell::new(LineState {
+    timing: LineTiming::new(),
+    next_timing: None,
+    half_duplex: false,
+    line: Line::Idle,
+    sampler: Sampler::new(Framing::new()),
+    bits: None,
+    high: true,
+    rx: Ring::new(),
+    tx: Ring::new(),
+    error: None,
+}));
+
+/// Runs `f` on the line's state with interrupts off
+fn with_line<R>(f: impl FnOnce(&mut LineState) -> R) -> R {
+    avr_device::interrupt::free(|cs| f(&mut LINE_STATE.borrow(cs).borrow_mut()))
+}
+
+const LINE_PIN: u8 = 1 << 2;
+
+/// Sets the level of the line while sending
+unsafe fn drive(state: &mut LineState, port: &portd::RegisterBlock, high: bool) {
+    if high != state.high {
+        // Writing a 1 to PIND flips the output without touching the other pins
+        port.pind.write(|w| w.bits(LINE_PIN));
+        state.high = high;
+    }
+}
+
+/// Sends the start bit of `byte`, or with fast transmit on, the whole word. Returns true
+/// in the second case, when the next bit time is timed from now rather than from the
+/// last one.
+unsafe fn start_byte(state: &mut LineState, port: &portd::RegisterBlock, byte: u8) -> bool {
+    let framing = state.sampler.framing();
+    let mut bits = framing.bits(byte as u16);
+    state.line = Line::Sending;
+
+    let Some(delay) = state.timing.fast_delay else {
+        let start = bits.next().unwrap_or_default();
+        drive(state, port, start);
+        state.bits = Some(bits);
+        return false;
+    };
+
+    let levels = bits.enumerate().fold(0u16, |levels, (index, level)| levels | (level as u16) << index);
+    let out = port.portd.read().bits();
+    bit_bang(port.portd.as_ptr(), out | LINE_PIN, out & !LINE_PIN, levels, framing.frame_bits(), delay);
+    state.high = framing.idle_level();
+    state.bits = None;
+    true
+}
+
+/// Start bit: time the samples from its falling edge, and ignore the edges of the data
+/// bits until the stop bit
+#[avr_device::interrupt(atmega328p)]
+unsafe fn INT0() {
+    let clock = &*TC1::ptr();
+    let now = clock.tcnt1.read().bits();
+    with_line(|state| {
+        clock.ocr1b.write(|w| w.bits(now.wrapping_add(state.timing.first_sample_ticks)));
+        clock.tifr1.write(|w| w.ocf1b().set_bit());
+        clock.timsk1.modify(|_, w| w.ocie1b().set_bit());
+        (*EXINT::ptr()).eimsk.modify(|_, w| w.int0().clear_bit());
+        state.line = Line::Receiving;
+        state.sampler.reset();
+    })
+}
+
+/// The next bit time: the middle of a bit coming in, the edge of a bit going out, or the
+/// end of the guard time
+#[avr_device::interrupt(atmega328p)]
+unsafe fn TIMER1_COMPB() {
+    let clock = &*TC1::ptr();
+    let exint = &*EXINT::ptr();
+    let port = &*PORTD::ptr();
+    with_line(|state| {
+        let mut wait_ticks = state.timing.bit_ticks;
+        let mut from_now = false;
+
+        match state.line {
+            Line::Idle => (),
+            Line::Receiving => {
+                let level = port.pind.read().bits() & LINE_PIN != 0;
+                if let Some(word) = state.sampler.push(level) {
+                    let error = match word {
+                        Ok(word) => (!state.rx.push(word as u8)).then_some(SerialError::Overrun),
+                        Err(FrameError::Framing) => Some(SerialError::Framing),
+                        Err(FrameError::Parity) => Some(SerialError::Parity),
+                    };
+                    if let Some(error) = error {
+                        state.error.get_or_insert(error);
+                    }
+
+                    // Wait for the next start bit
+                    exint.eifr.write(|w| w.intf0().set_bit());
+                    exint.eimsk.modify(|_, w| w.int0().set_bit());
+                    state.line = if state.half_duplex { Line::Guard } else { Line::Idle };
+                    wait_ticks = state.timing.guard_ticks;
+                }
+            },
+            Line::Guard => match state.tx.pop() {
+                None => state.line = Line::Idle,
+                Some(byte) => {
+                    // Take the line: it's still pulled up, so it starts out driven high
+                    exint.eimsk.modify(|_, w| w.int0().clear_bit());
+                    port.ddrd.modify(|r, w| w.bits(r.bits() | LINE_PIN));
+                    state.high = true;
+                    from_now = start_byte(state, port, byte);
+                },
+            },
+            Line::Sending => match state.bits.as_mut().and_then(|bits| bits.next()) {
+                Some(level) => drive(state, port, level),
+                // The end of the last stop bit
+                None => match state.tx.pop() {
+                    Some(byte) => from_now = start_byte(state, port, byte),
+                    None => {
+                        // Done: let go of the line, which the pull-up keeps high, and listen
+                        // again
+                        port.ddrd.modify(|r, w| w.bits(r.bits() & !LINE_PIN));
+                        state.bits = None;
+                        if let Some(timing) = state.next_timing.take() {
+                            state.timing = timing;
+                        }
+                        exint.eifr.write(|w| w.intf0().set_bit());
+                        exint.eimsk.modify(|_, w| w.int0().set_bit());
+                        state.line = Line::Guard;
+                        wait_ticks = state.timing.guard_ticks - state.timing.bit_ticks / 2;
+                    },
+                },
+            },
         }
-        else if PRESCALE_256.contains(&freq_s) {
-            clock.tccr2b.write(|w| w.cs2().prescale_256());
-            Ok(256)
+
+        if state.line == Line::Idle {
+            clock.timsk1.modify(|_, w| w.ocie1b().clear_bit());
         }
-        else if PRESCALE_1024.contains(&freq_s) {
-            clock.tccr2b.write(|w| w.cs2().prescale_1024());
-            Ok(1024)
+        else if from_now {
+            // The compare match went by while the word was going out
+            clock.ocr1b.write(|w| w.bits(clock.tcnt1.read().bits().wrapping_add(FAST_GAP_TICKS)));
         }
         else {
-            Err("Frequency too low/high for software serial")
+            clock.ocr1b.modify(|r, w| w.bits(r.bits().wrapping_add(wait_ticks)));
         }
+    })
+}
+
+/// Sets up Timer1 and INT0 for a line at `baud`, returning the rate it runs at
+fn start_line(exint: &EXINT, clock: &TC1, baud: u32, framing: Framing, half_duplex: bool) -> Result<BaudDivider, &'static str> {
+    if !framing.is_valid() {
+        return Err("Unsupported serial framing");
     }
 
-    fn calc_compare(baud: u32, prescale: u32, clock: &mut TC2) -> u8 {
-        let compare = (arduino_hal::DefaultClock::FREQ / baud / prescale - 1) as u8;
-        clock.ocr2a.write(|w| w.bits(compare));
-        compare
+    let (timing, divider) = LineTiming::for_baud(baud, 1)
+        .or_else(|| LineTiming::for_baud(baud, 8))
+        .ok_or("Baud rate out of range for software serial")?;
+    let divider = within_tolerance(divider)?;
+    if divider.prescale == 1 {
+        clock.tccr1b.write(|w| w.cs1().direct());
     }
+    else {
+        clock.tccr1b.write(|w| w.cs1().prescale_8());
+    }
+    clock.tccr1a.reset();
+    clock.timsk1.write(|w| w.ocie1b().clear_bit());
 
-    fn send_next_bit(&mut self) -> Option<bool> {
-        match self.next_send {
-            WriterState::Idle => {
-                if self.size > 0 {
-                    self.next_send = WriterState::Start;
-                }
-                None
-            },
-            WriterState::Start => {
-                self.next_send = WriterState::Data(0);
-                Some(false)
-            },
-            WriterState::Data(bit) => {
-                self.next_send = if bit < 7 { WriterState::Data(bit + 1) } else { WriterState::Stop };
-                Some((self.buf[self.head] & (1 << (7 - bit))) != 0)
-            },
-            WriterState::Stop => {
-                self.head = (self.head + 1) % B;
-                self.size -= 1;
-                self.next_send = if self.size > 0 { WriterState::Start } else { WriterState::Idle };
-                Some(true)
+    with_line(|state| {
+        state.timing = timing;
+        state.next_timing = None;
+        state.half_duplex = half_duplex;
+        state.line = Line::Idle;
+        state.sampler = Sampler::new(framing);
+        state.bits = None;
+        state.high = framing.idle_level();
+        state.rx = Ring::new();
+        state.tx = Ring::new();
+        state.error = None;
+    });
+
+    // The edge into the start bit: falling, or rising on an inverted line
+    exint.eicra.modify(|_, w| w.isc0().bits(if framing.inverted { 0x03 } else { 0x02 }));
+    exint.eifr.write(|w| w.intf0().set_bit());
+    exint.eimsk.modify(|_, w| w.int0().set_bit());
+    Ok(divider)
+}
+
+fn stop_line(exint: &EXINT, clock: &TC1) {
+    exint.eimsk.modify(|_, w| w.int0().clear_bit());
+    clock.timsk1.modify(|_, w| w.ocie1b().clear_bit());
+    // In case it was in the middle of sending
+    unsafe { (*PORTD::ptr()).ddrd.modify(|r, w| w.bits(r.bits() & !LINE_PIN)) };
+}
+
+/// Blocks until a byte has arrived. A framing or overrun error is returned once, ahead of
+/// the bytes received after it.
+fn read_line(buf: &mut [u8]) -> Result<usize, SerialError> {
+    if buf.is_empty() {
+        return Ok(0);
+    }
+
+    loop {
+        let result = with_line(|state| {
+            if let Some(error) = state.error.take() {
+                return Err(error);
             }
+
+            let mut len = 0;
+            while len < buf.len() {
+                let Some(byte) = state.rx.pop() else { break };
+                buf[len] = byte;
+                len += 1;
+            }
+            Ok(len)
+        });
+
+        match result {
+            Ok(0) => continue,
+            result => return result,
         }
     }
 }
 
-impl<const B: usize> ErrorType for SerialWriter<B> {
+fn line_read_ready() -> bool {
+    with_line(|state| {
+        !state.rx.is_empty() || state.error.is_some()
+    })
+}
+
+/// Receives serial on D2 in the background: INT0 catches the edge into each start bit,
+/// and Timer1's compare B interrupt samples the middle of each bit after it, with the
+/// timer running free. Bytes wait in a ring buffer until read.
+pub struct SerialReader {
+    pin: Pin<Input<PullUp>, PD2>,
+    exint: EXINT,
+    clock: TC1,
+    baud: BaudDivider,
+}
+
+impl SerialReader {
+    /// 8N1
+    pub fn new(pin: Pin<Input<PullUp>, PD2>, exint: EXINT, clock: TC1, baud: u32) -> Result<Self, &'static str> {
+        Self::with_framing(pin, exint, clock, baud, Framing::new())
+    }
+
+    /// Words of 9 data bits lose the ninth, since they're read as bytes. Fails if Timer1
+    /// can't get within `MAX_BAUD_ERROR_PERMILLE` of `baud`.
+    pub fn with_framing(pin: Pin<Input<PullUp>, PD2>, exint: EXINT, clock: TC1, baud: u32, framing: Framing) -> Result<Self, &'static str> {
+        let baud = start_line(&exint, &clock, baud, framing, false)?;
+        Ok(Self {
+            pin,
+            exint,
+            clock,
+            baud,
+        })
+    }
+
+    /// The rate the bits are sampled at, and how far it is from the one asked for
+    pub fn baud(&self) -> BaudDivider {
+        self.baud
+    }
+
+    /// Stops receiving and gives back the hardware
+    pub fn release(self) -> (Pin<Input<PullUp>, PD2>, EXINT, TC1) {
+        stop_line(&self.exint, &self.clock);
+        (self.pin, self.exint, self.clock)
+    }
+}
+
+impl ErrorType for SerialReader {
+    type Error = SerialError;
+}
+
+impl Read for SerialReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        read_line(buf)
+    }
+}
+
+impl ReadReady for SerialReader {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(line_read_ready())
+    }
+}
+
+/// A single-wire, half-duplex 8N1 line on D2, as SRXL2 uses. It receives like
+/// `SerialReader`, and sends from the same interrupts: for each transmission the pin
+/// switches from its pulled-up input to an output, and back once the last stop bit is
+/// out. Nothing is received while sending, so its own bytes never come back as echo,
+/// and sending waits until the line has been idle for `GUARD_BITS` after the last byte
+/// on it, whichever end sent it.
+///
+/// At 115200 baud a bit is only 139 cycles, too few to be sure of getting each edge out
+/// of an interrupt on time. `enable_fast_transmit` sends each word in one go instead,
+/// with interrupts off for the length of the word: about 87µs at 115200. Receiving is
+/// still done a bit at a time.
+pub struct HalfDuplexSerial {
+    pin: Pin<Input<PullUp>, PD2>,
+    exint: EXINT,
+    clock: TC1,
+    baud: BaudDivider,
+    /// The rate words are sent at in one go, if they are
+    fast: Option<BaudDivider>,
+    /// Rate changes the interpreter asked for that the line couldn't make
+    baud_errors: u16,
+}
+
+impl HalfDuplexSerial {
+    /// Fails if Timer1 can't get within `MAX_BAUD_ERROR_PERMILLE` of `baud`
+    pub fn new(pin: Pin<Input<PullUp>, PD2>, exint: EXINT, clock: TC1, baud: u32) -> Result<Self, &'static str> {
+        let baud = start_line(&exint, &clock, baud, Framing::new(), true)?;
+        Ok(Self {
+            pin,
+            exint,
+            clock,
+            baud,
+            fast: None,
+            baud_errors: 0,
+        })
+    }
+
+    /// The SRXL2 rates the line can offer the other devices on the bus, for `init_bus`:
+    /// only 115200, since 400000 can't be received a bit per interrupt
+    pub fn baud_rates(&self) -> BaudRates {
+        BaudRates::from(Baud::Baud115200)
+    }
+
+    /// Rate changes the interpreter asked for, as `Transport`, that failed since the last
+    /// call. The line stays at its old rate, out of step with the bus.
+    pub fn take_baud_errors(&mut self) -> u16 {
+        core::mem::take(&mut self.baud_errors)
+    }
+
+    /// The rate the line runs at, or will once `set_baud` takes effect, and how far it
+    /// is from the one asked for
+    pub fn baud(&self) -> BaudDivider {
+        self.baud
+    }
+
+    /// The rate words are sent at with fast transmit on
+    pub fn fast_transmit_baud(&self) -> Option<BaudDivider> {
+        self.fast
+    }
+
+    /// Sends each word in one go, with interrupts off, rather than a bit per interrupt,
+    /// from the next transmission on. Returns the rate it sends at, or fails if that's
+    /// too far off.
+    pub fn enable_fast_transmit(&mut self) -> Result<BaudDivider, &'static str> {
+        let (delay, divider) = fast_timing(self.baud.requested)?;
+        self.update_timing(|timing| timing.fast_delay = Some(delay));
+        self.fast = Some(divider);
+        Ok(divider)
+    }
+
+    /// Goes back to sending a bit per interrupt, from the next transmission on
+    pub fn disable_fast_transmit(&mut self) {
+        self.update_timing(|timing| timing.fast_delay = None);
+        self.fast = None;
+    }
+
+    /// Changes the line's timing once everything queued has been sent
+    fn update_timing(&self, f: impl FnOnce(&mut LineTiming)) {
+        with_line(|state| {
+            let mut timing = state.next_timing.unwrap_or(state.timing);
+            f(&mut timing);
+            if state.tx.is_empty() && state.line != Line::Sending {
+                state.timing = timing;
+            }
+            else {
+                state.next_timing = Some(timing);
+            }
+        });
+    }
+
+    /// True while bytes are queued or going out
+    pub fn is_sending(&self) -> bool {
+        with_line(|state| {
+            !state.tx.is_empty() || state.line == Line::Sending
+        })
+    }
+
+    /// Switches to `baud` once everything queued has been sent, returning the rate the
+    /// line will run at. The prescale is kept, so rates under about a tenth of the first
+    /// one may be out of reach. Fails, leaving the rate as it was, if it's too far off,
+    /// or with fast transmit on, if the words sent would be.
+    pub fn set_baud(&mut self, baud: u32) -> Result<BaudDivider, &'static str> {
+        let (mut timing, divider) = LineTiming::for_baud(baud, self.baud.prescale)
+            .ok_or("Baud rate out of range for software serial")?;
+        let divider = within_tolerance(divider)?;
+        let fast = match self.fast {
+            Some(_) => {
+                let (delay, fast) = fast_timing(baud)?;
+                timing.fast_delay = Some(delay);
+                Some(fast)
+            },
+            None => None,
+        };
+
+        self.update_timing(|next| *next = timing);
+        self.baud = divider;
+        self.fast = fast;
+        Ok(divider)
+    }
+
+    /// Stops the line, dropping anything not yet sent, and gives back the hardware
+    pub fn release(self) -> (Pin<Input<PullUp>, PD2>, EXINT, TC1) {
+        stop_line(&self.exint, &self.clock);
+        (self.pin, self.exint, self.clock)
+    }
+}
+
+impl ErrorType for HalfDuplexSerial {
     type Error = SerialError;
 }
 
-impl<const B: usize> Write for SerialWriter<B> {
+impl Read for HalfDuplexSerial {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        read_line(buf)
+    }
+}
+
+impl ReadReady for HalfDuplexSerial {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(line_read_ready())
+    }
+}
+
+impl Write for HalfDuplexSerial {
+    /// Queues as much of `buf` as fits, blocking until there's room for at least one byte
     fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
-        if self.size + buf.len() > B {
-            return Err(SerialError::BufferFull);
+        if buf.is_empty() {
+            return Ok(0);
         }
 
-        for byte in buf {
-            self.buf[self.tail] = *byte;
-            self.tail = (self.tail + 1) % B;
-            self.size += 1;
+        loop {
+            let len = with_line(|state| {
+                let len = buf.iter().take_while(|byte| state.tx.push(**byte)).count();
+                if len > 0 && state.line == Line::Idle {
+                    // The guard time is long over: start a bit time from now, far enough
+                    // off that the timer can't pass it before the interrupt is on
+                    let clock = &self.clock;
+                    clock.ocr1b.write(|w| w.bits(clock.tcnt1.read().bits().wrapping_add(state.timing.bit_ticks)));
+                    clock.tifr1.write(|w| w.ocf1b().set_bit());
+                    clock.timsk1.modify(|_, w| w.ocie1b().set_bit());
+                    state.line = Line::Guard;
+                }
+                len
+            });
+            if len > 0 {
+                return Ok(len);
+            }
         }
-
-        Ok(buf.len())
     }
 
+    /// Blocks until everything queued has been sent
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Err(SerialError::NotImplemented)
+        while self.is_sending() {}
+        Ok(())
     }
 }
 
-impl<const B: usize> WriteReady for SerialWriter<B> {
+impl WriteReady for HalfDuplexSerial {
     fn write_ready(&mut self) -> Result<bool, Self::Error> {
-        Ok(self.size < B)
+        Ok(with_line(|state| !state.tx.is_full()))
     }
 }
 
-impl<const B: usize> uWrite for SerialWriter<B> {
-    type Error = SerialError;
-    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
-        match self.write(s.as_bytes()) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
+/// An SRXL2 bus on the line. The line is the only bus, so the UART number is ignored.
+/// With fast transmit on, 115200 is sent reliably, but 400000 can't be received in
+/// software on the Uno, so devices on the line must only be offered `baud_rates`.
+impl Transport for HalfDuplexSerial {
+    fn send(&mut self, _uart: u8, packet: &[u8]) {
+        // Can't fail: `write` only returns once it has queued something
+        let _ = self.write_all(packet);
+    }
+
+    fn change_baud(&mut self, _uart: u8, baud: Baud) {
+        if self.set_baud(baud.bits_per_second()).is_err() {
+            self.baud_errors = self.baud_errors.saturating_add(1);
         }
     }
-}
\ No newline at end of file
+}
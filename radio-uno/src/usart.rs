@@ -0,0 +1,478 @@
+//! The hardware serial port (USART0, on D0 and D1, and the USB bridge) driven from its
+//! interrupts, so printing doesn't hold up the caller for the time the bytes take to go
+//! out. Bytes are queued in ring buffers both ways. Writes never block: whatever doesn't
+//! fit in the queue is dropped and counted, as are bytes received with nowhere to go.
+//!
+//! `HalfDuplexUsart` runs the same port as a single-wire SRXL2 bus instead, with TX tied
+//! to RX through a resistor, so everything sent is heard back and has to be filtered out.
+
+use arduino_hal::{
+    clock::Clock,
+    hal::port::{PD0, PD1},
+    pac::{usart0, PORTD, USART0},
+    port::{
+        mode::{Input, Output},
+        Pin,
+    },
+};
+use avr_device::interrupt::Mutex;
+use core::{cell::RefCell, convert::Infallible};
+use embedded_io::{
+    ErrorType,
+    Read,
+    ReadReady,
+    Write,
+    WriteReady,
+};
+use srxl2::{
+    handshake::Baud,
+    uart::BaudDivider,
+    Transport,
+};
+use ufmt::uWrite;
+use crate::{error::SerialError, ring::Ring};
+
+/// Bytes received and not yet read
+const RX_BUFFER: usize = 64;
+/// Bytes waiting to go out
+const TX_BUFFER: usize = 128;
+/// Bytes sent and not yet heard back on a half-duplex line: the one being received, the
+/// one being sent, and the one waiting in UDR0
+const ECHO_BUFFER: usize = 4;
+
+/// Bytes lost since the counts were last taken
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Dropped {
+    /// Written with the transmit queue full
+    pub sent: u16,
+    /// Received with the receive queue full, or with a framing error
+    pub received: u16,
+}
+
+struct UsartState {
+    rx: Ring<RX_BUFFER>,
+    tx: Ring<TX_BUFFER>,
+    dropped: Dropped,
+    /// The first error since the last read
+    error: Option<SerialError>,
+    /// True when TX and RX share a line, so each byte sent is received too
+    half_duplex: bool,
+    echo: Ring<ECHO_BUFFER>,
+    collisions: u16,
+    /// Rate to switch to once the bytes queued have been sent
+    next_baud: Option<BaudDivider>,
+}
+
+static USART_STATE: Mutex<RefCell<UsartState>> = Mutex::new(RefCell::new(UsartState {
+    rx: Ring::new(),
+    tx: Ring::new(),
+    dropped: Dropped { sent: 0, received: 0 },
+    error: None,
+    half_duplex: false,
+    echo: Ring::new(),
+    collisions: 0,
+    next_baud: None,
+}));
+
+fn with_usart<R>(f: impl FnOnce(&mut UsartState) -> R) -> R {
+    avr_device::interrupt::free(|cs| f(&mut USART_STATE.borrow(cs).borrow_mut()))
+}
+
+#[avr_device::interrupt(atmega328p)]
+fn USART_RX() {
+    let usart = unsafe { &*USART0::ptr() };
+    // The status flags are for the byte in UDR0, so they have to be read first
+    let status = usart.ucsr0a.read();
+    let byte = usart.udr0.read().bits();
+    with_usart(|state| {
+        if state.half_duplex && usart.ucsr0b.read().txen0().bit_is_set() {
+            // While the line is ours, all that's heard is our own bytes coming back, or
+            // whatever they clashed with
+            let sent = state.echo.pop();
+            if sent.is_some() && (sent != Some(byte) || status.fe0().bit_is_set()) {
+                collision(state, usart);
+            }
+            return;
+        }
+
+        let error = if status.fe0().bit_is_set() {
+            Some(SerialError::Framing)
+        }
+        else if !state.rx.push(byte) {
+            Some(SerialError::Overrun)
+        }
+        else if status.dor0().bit_is_set() {
+            // The byte before this one was lost in the hardware
+            Some(SerialError::Overrun)
+        }
+        else {
+            None
+        };
+
+        if let Some(error) = error {
+            state.dropped.received = state.dropped.received.saturating_add(1);
+            state.error.get_or_insert(error);
+        }
+    })
+}
+
+#[avr_device::interrupt(atmega328p)]
+fn USART_UDRE() {
+    let usart = unsafe { &*USART0::ptr() };
+    with_usart(|state| match state.tx.pop() {
+        Some(byte) => {
+            usart.udr0.write(|w| w.bits(byte));
+            if state.half_duplex {
+                // Can't fill up: `USART_TX` clears out any that never come back
+                state.echo.push(byte);
+            }
+        },
+        // Sleep until something is queued
+        None => usart.ucsr0b.modify(|_, w| w.udrie0().clear_bit()),
+    })
+}
+
+/// The last stop bit is out, and there's nothing more to send. Only on for a half-duplex
+/// line, where this lets go of it.
+#[avr_device::interrupt(atmega328p)]
+fn USART_TX() {
+    let usart = unsafe { &*USART0::ptr() };
+    with_usart(|state| {
+        if !state.echo.is_empty() {
+            // Sent, but never heard back: something is holding the line
+            collision(state, usart);
+        }
+        // The pin goes back to its pulled-up input
+        usart.ucsr0b.modify(|_, w| w.txen0().clear_bit().txcie0().clear_bit());
+        if let Some(baud) = state.next_baud.take() {
+            apply_baud(usart, baud);
+        }
+    })
+}
+
+/// Another device sent while we did: drops the rest of what was being sent, which the
+/// receiving end couldn't make sense of, and ignores what comes back of what's already
+/// gone out
+fn collision(state: &mut UsartState, usart: &usart0::RegisterBlock) {
+    state.collisions = state.collisions.saturating_add(1);
+    state.error.get_or_insert(SerialError::Collision);
+    state.echo = Ring::new();
+    state.tx = Ring::new();
+    usart.ucsr0b.modify(|_, w| w.udrie0().clear_bit());
+}
+
+/// The UBRR0 setting nearest to `baud`, with or without double speed
+fn baud_divider(baud: u32) -> BaudDivider {
+    let freq = arduino_hal::DefaultClock::FREQ;
+    // The USART divides by 8 at double speed and 16 otherwise, then by UBRR0 + 1
+    BaudDivider::closest(freq, baud, &[8, 16], 0x1000).unwrap_or_else(|| {
+        if baud > freq / 16 {
+            BaudDivider::new(freq, baud, 8, 1)
+        }
+        else {
+            BaudDivider::new(freq, baud, 16, 0x1000)
+        }
+    })
+}
+
+fn apply_baud(usart: &usart0::RegisterBlock, divider: BaudDivider) {
+    usart.ubrr0.write(|w| w.bits((divider.count - 1) as u16));
+    usart.ucsr0a.write(|w| w.u2x0().bit(divider.prescale == 8));
+}
+
+/// USART0 at 8N1, sending and receiving from its interrupts, which have to be enabled
+/// for anything to go out
+pub struct BufferedSerial {
+    usart: USART0,
+    rx: Pin<Input, PD0>,
+    tx: Pin<Output, PD1>,
+    baud: BaudDivider,
+    /// Something has been queued since the start, so the transmit complete flag counts
+    sent: bool,
+}
+
+impl BufferedSerial {
+    /// Takes the pins as `arduino_hal::default_serial!` does: `pins.d0.forget_imode()`
+    /// and `pins.d1.into_output()`. The rate isn't held to `MAX_BAUD_ERROR_PERMILLE`,
+    /// since the USART's receiver copes with more than a software one: 115200 comes out
+    /// 2.1% fast on the Uno, as it does for every other Uno sketch.
+    pub fn new(usart: USART0, rx: Pin<Input, PD0>, tx: Pin<Output, PD1>, baud: u32) -> Self {
+        usart.ucsr0b.reset();
+        with_usart(|state| {
+            state.rx = Ring::new();
+            state.tx = Ring::new();
+            state.dropped = Dropped::default();
+            state.error = None;
+            state.half_duplex = false;
+            state.echo = Ring::new();
+            state.collisions = 0;
+            state.next_baud = None;
+        });
+
+        let baud = baud_divider(baud);
+        apply_baud(&usart, baud);
+        usart.ucsr0c.write(|w| w.umsel0().usart_async().ucsz0().chr8().usbs0().stop1().upm0().disabled());
+        usart.ucsr0b.write(|w| w.txen0().set_bit().rxen0().set_bit().rxcie0().set_bit());
+        Self {
+            usart,
+            rx,
+            tx,
+            baud,
+            sent: false,
+        }
+    }
+
+    /// The rate the USART runs at, and how far it is from the one asked for
+    pub fn baud(&self) -> BaudDivider {
+        self.baud
+    }
+
+    /// Bytes dropped since the last call, both ways
+    pub fn take_dropped(&mut self) -> Dropped {
+        with_usart(|state| core::mem::take(&mut state.dropped))
+    }
+
+    /// Bytes queued and not yet sent
+    pub fn queued(&self) -> usize {
+        with_usart(|state| state.tx.len())
+    }
+
+    /// Stops the USART, dropping anything not yet sent or read, and gives back the
+    /// hardware, e.g. to make a blocking `arduino_hal::Usart` of again
+    pub fn release(self) -> (USART0, Pin<Input, PD0>, Pin<Output, PD1>) {
+        self.usart.ucsr0b.reset();
+        with_usart(|state| state.half_duplex = false);
+        // Back to an output, in case a half-duplex line let go of it
+        unsafe { (*PORTD::ptr()).ddrd.modify(|r, w| w.bits(r.bits() | TX_PIN)) };
+        (self.usart, self.rx, self.tx)
+    }
+}
+
+impl ErrorType for BufferedSerial {
+    type Error = SerialError;
+}
+
+impl Read for BufferedSerial {
+    /// Blocks until a byte has arrived. A framing or overrun error is returned once,
+    /// ahead of the bytes received after it.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let result = with_usart(|state| {
+                if let Some(error) = state.error.take() {
+                    return Err(error);
+                }
+
+                let mut len = 0;
+                while len < buf.len() {
+                    let Some(byte) = state.rx.pop() else { break };
+                    buf[len] = byte;
+                    len += 1;
+                }
+                Ok(len)
+            });
+
+            match result {
+                Ok(0) => continue,
+                result => return result,
+            }
+        }
+    }
+}
+
+impl ReadReady for BufferedSerial {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(with_usart(|state| !state.rx.is_empty() || state.error.is_some()))
+    }
+}
+
+impl Write for BufferedSerial {
+    /// Queues as much of `buf` as fits and drops the rest, so it always takes all of
+    /// `buf`. Check `write_ready` or `queued` first to avoid losing any.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let usart = &self.usart;
+        let double_speed = self.baud.prescale == 8;
+        with_usart(|state| {
+            let queued = buf.iter().take_while(|byte| state.tx.push(**byte)).count();
+            let dropped = (buf.len() - queued).min(u16::MAX as usize) as u16;
+            state.dropped.sent = state.dropped.sent.saturating_add(dropped);
+            if queued > 0 {
+                // Writing a 1 clears the flag, so it's only set again once this is out
+                usart.ucsr0a.write(|w| w.u2x0().bit(double_speed).txc0().set_bit());
+                // A half-duplex line takes the pin, and lets go once it's all out
+                let half_duplex = state.half_duplex;
+                usart.ucsr0b.modify(|_, w| w.txen0().set_bit().txcie0().bit(half_duplex).udrie0().set_bit());
+            }
+        });
+        self.sent = true;
+        Ok(buf.len())
+    }
+
+    /// Blocks until everything queued has been sent, down to the last stop bit
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if !self.sent {
+            return Ok(());
+        }
+
+        while self.queued() > 0 || self.usart.ucsr0b.read().udrie0().bit_is_set() {}
+        while self.usart.ucsr0a.read().txc0().bit_is_clear() {}
+        Ok(())
+    }
+}
+
+impl WriteReady for BufferedSerial {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(with_usart(|state| !state.tx.is_full()))
+    }
+}
+
+/// Never fails, and never blocks: what doesn't fit is dropped, as with `Write`
+impl uWrite for BufferedSerial {
+    type Error = Infallible;
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        let _ = self.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+const TX_PIN: u8 = 1 << 1;
+
+/// An SRXL2 bus on USART0, with D1 (TX) tied to D0 (RX) through a resistor of about
+/// 1kΩ, and D0 to the bus. The USART only drives D1 while sending, and leaves it as a
+/// pulled-up input otherwise, so other devices can pull the line low through the
+/// resistor. Each byte sent is heard back on RX and dropped there; one that comes back
+/// different, or not at all, is a collision with another device sending, and the rest
+/// of the packet is dropped rather than sent into the clash.
+///
+/// Writes block while the queue is full rather than drop bytes, since a packet with bytes
+/// missing is no use to anyone.
+pub struct HalfDuplexUsart {
+    serial: BufferedSerial,
+}
+
+impl HalfDuplexUsart {
+    pub fn new(usart: USART0, rx: Pin<Input, PD0>, tx: Pin<Output, PD1>, baud: u32) -> Self {
+        let serial = BufferedSerial::new(usart, rx, tx, baud);
+        with_usart(|state| state.half_duplex = true);
+        // Let go of the line until there's something to send
+        serial.usart.ucsr0b.modify(|_, w| w.txen0().clear_bit());
+        unsafe {
+            let port = &*PORTD::ptr();
+            port.portd.modify(|r, w| w.bits(r.bits() | TX_PIN));
+            port.ddrd.modify(|r, w| w.bits(r.bits() & !TX_PIN));
+        }
+        Self {
+            serial,
+        }
+    }
+
+    /// The rate the line runs at, or will once `set_baud` takes effect. At 115200 the
+    /// USART runs 2.1% fast, which SRXL2 devices take; 400000 is exact.
+    pub fn baud(&self) -> BaudDivider {
+        self.serial.baud
+    }
+
+    /// True from the first byte queued until the last stop bit is out, or a collision
+    /// cuts it short
+    pub fn is_sending(&self) -> bool {
+        self.serial.usart.ucsr0b.read().txen0().bit_is_set()
+    }
+
+    /// Switches to `baud` once everything queued has been sent
+    pub fn set_baud(&mut self, baud: u32) -> BaudDivider {
+        let divider = baud_divider(baud);
+        let usart = &self.serial.usart;
+        with_usart(|state| {
+            if usart.ucsr0b.read().txen0().bit_is_set() {
+                state.next_baud = Some(divider);
+            }
+            else {
+                apply_baud(usart, divider);
+            }
+        });
+        self.serial.baud = divider;
+        divider
+    }
+
+    /// Collisions since the last call
+    pub fn take_collisions(&mut self) -> u16 {
+        with_usart(|state| core::mem::take(&mut state.collisions))
+    }
+
+    /// Bytes dropped since the last call: ones received with the queue full
+    pub fn take_dropped(&mut self) -> Dropped {
+        self.serial.take_dropped()
+    }
+
+    /// Stops the USART, dropping anything not yet sent or read, and gives back the
+    /// hardware
+    pub fn release(self) -> (USART0, Pin<Input, PD0>, Pin<Output, PD1>) {
+        self.serial.release()
+    }
+}
+
+impl ErrorType for HalfDuplexUsart {
+    type Error = SerialError;
+}
+
+impl Read for HalfDuplexUsart {
+    /// Blocks until a byte has arrived. A collision, framing, or overrun error is returned
+    /// once, ahead of the bytes received after it.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.serial.read(buf)
+    }
+}
+
+impl ReadReady for HalfDuplexUsart {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.serial.read_ready()
+    }
+}
+
+impl Write for HalfDuplexUsart {
+    /// Queues as much of `buf` as fits, blocking until there's room for at least one byte
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let room = TX_BUFFER - self.serial.queued();
+            if room > 0 {
+                return self.serial.write(&buf[..room.min(buf.len())]);
+            }
+        }
+    }
+
+    /// Blocks until everything queued has been sent, or dropped after a collision
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        while self.is_sending() {}
+        Ok(())
+    }
+}
+
+impl WriteReady for HalfDuplexUsart {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        self.serial.write_ready()
+    }
+}
+
+/// An SRXL2 bus on the line. The USART is the only bus, so the UART number is ignored.
+/// Unlike the software UART, it keeps up with 400000 baud both ways.
+impl Transport for HalfDuplexUsart {
+    fn send(&mut self, _uart: u8, packet: &[u8]) {
+        // Can't fail: `write` only returns once it has queued something
+        let _ = self.write_all(packet);
+    }
+
+    fn change_baud(&mut self, _uart: u8, baud: Baud) {
+        self.set_baud(baud.bits_per_second());
+    }
+}
@@ -30,9 +30,13 @@ const PRESCALER: u32 = 1024;
 const TIMER_COUNTS: u32 = 125;
 
 const MILLIS_INCREMENT: usize = (PRESCALER * TIMER_COUNTS / 16000) as usize;
+const MICROS_PER_COUNT: u32 = PRESCALER / 16;
 
 static MILLIS_COUNTER: avr_device::interrupt::Mutex<cell::Cell<usize>> =
     avr_device::interrupt::Mutex::new(cell::Cell::new(0));
+/// Kept apart from the millisecond counter so it wraps cleanly at 32 bits
+static MICROS_COUNTER: avr_device::interrupt::Mutex<cell::Cell<u32>> =
+    avr_device::interrupt::Mutex::new(cell::Cell::new(0));
 
 pub fn millis_init(tc0: &arduino_hal::pac::TC0) {
     // Configure the timer for the above interval (in CTC mode)
@@ -51,6 +55,7 @@ pub fn millis_init(tc0: &arduino_hal::pac::TC0) {
     // Reset the global millisecond counter
     avr_device::interrupt::free(|cs| {
         MILLIS_COUNTER.borrow(cs).set(0);
+        MICROS_COUNTER.borrow(cs).set(0);
     });
 }
 
@@ -60,6 +65,9 @@ fn TIMER0_COMPA() {
         let counter_cell = MILLIS_COUNTER.borrow(cs);
         let counter = counter_cell.get();
         counter_cell.set(counter + MILLIS_INCREMENT);
+
+        let micros_cell = MICROS_COUNTER.borrow(cs);
+        micros_cell.set(micros_cell.get().wrapping_add(MILLIS_INCREMENT as u32 * 1000));
     })
 }
 
@@ -67,6 +75,23 @@ pub fn millis() -> usize {
     avr_device::interrupt::free(|cs| MILLIS_COUNTER.borrow(cs).get())
 }
 
+/// Time since `millis_init` in microseconds, to the nearest timer count (64µs). Wraps
+/// after about 71 minutes.
+pub fn micros() -> u32 {
+    // only read here, and written only by millis_init
+    let tc0 = unsafe { &*arduino_hal::pac::TC0::ptr() };
+    avr_device::interrupt::free(|cs| {
+        let mut micros = MICROS_COUNTER.borrow(cs).get();
+        let mut count = tc0.tcnt0.read().bits();
+        // the timer may have wrapped since interrupts went off, before its interrupt ran
+        if tc0.tifr0.read().ocf0a().bit_is_set() {
+            micros = micros.wrapping_add(MILLIS_INCREMENT as u32 * 1000);
+            count = tc0.tcnt0.read().bits();
+        }
+        micros.wrapping_add(count as u32 * MICROS_PER_COUNT)
+    })
+}
+
 // ----------------------------------------------------------------------------
 
 fn _main() -> ! {
@@ -1,24 +1,62 @@
 use arduino_hal::{
-    hal::port::{PD0, PD1},
-    pac::{TC0, USART0},
+    hal::port::{PD2, PD3},
+    pac::TC0,
     port::{
-        mode::{Input, Output},
+        mode::{Input, Output, PullUp},
         Pin,
     },
     prelude::*
 };
+use embedded_io::{Read, ReadReady};
+use radio_uno::{satellite_bind, usart::BufferedSerial};
+use srxl2::{
+    bind::BindStatus,
+    input::{ChannelInput, LinkState, RcInput},
+    satellite::{self, Remote, Satellite},
+};
 use crate::{
     led::Led,
-    millis::{millis, millis_init},
+    millis::{micros, millis, millis_init},
     command::{Command, CommandState, blink::BlinkCommand},
-    subsystem::{Subsystem, onboard_leds_buttons::OnboardLedsButtons},
+    subsystem::{Subsystem, drive::Drive, onboard_leds_buttons::OnboardLedsButtons},
 };
 
+/// Protocol the satellite receiver is bound to when button B is pressed.
+///
+/// Satellites run on 3.3V and must not see the Uno's 5V. D3 switches the satellite's
+/// power through a 3.3V regulator with an enable input (or a transistor feeding one),
+/// and both D0 and D2 reach its data line through a bidirectional level shifter: D0 to
+/// receive the serial stream, D2 to send the bind pulses. The USB bridge also drives D0,
+/// through a 1k resistor, so nothing should be typed into the serial monitor, and the
+/// data line has to be unplugged to upload.
+const SATELLITE_SYSTEM: BindStatus = BindStatus::Dsmx11Ms;
+/// Sticks are ignored after this long without a frame from the receiver
+const RC_TIMEOUT_MS: u32 = 100;
+
 pub struct SpiderBot {
-    serial: arduino_hal::Usart<USART0, Pin<Input, PD0>, Pin<Output, PD1>>,
+    /// Queues what's printed for its interrupt to send, so printing never holds up a tick.
+    /// Receives the satellite's serial output too, so the console runs at its rate,
+    /// `satellite::BAUD` (125000, not ravedude's 57600): the USART divides the clock down
+    /// to it exactly, and samples the bits in hardware, leaving only a byte every 80µs
+    /// for its interrupt to take in.
+    serial: BufferedSerial,
     clock_pin: TC0,
     led: Led,
     leds_buttons: OnboardLedsButtons,
+    /// Sticks from whichever receiver is connected; stays in failsafe until one is
+    rc_input: ChannelInput,
+    drive: Drive,
+    /// Input, pulled up to the idle level of the satellite's serial output, except while
+    /// binding. Only None during a bind.
+    satellite_data: Option<Pin<Input<PullUp>, PD2>>,
+    satellite: Satellite,
+    satellite_power: Pin<Output, PD3>,
+    /// Last link state printed
+    rc_link: LinkState,
+    /// Whether the last drive command printed was a move
+    driving: bool,
+    /// Button B stays pressed for its whole debounce time, so only a new press binds
+    bind_pressed: bool,
     state: CommandState<'static>,
 }
 
@@ -26,13 +64,21 @@ impl SpiderBot {
     pub fn new() -> Self {
         let dp = arduino_hal::Peripherals::take().unwrap();
         let pins = arduino_hal::pins!(dp);
-        let serial = arduino_hal::default_serial!(dp, pins, 57600);
+        let serial = BufferedSerial::new(dp.USART0, pins.d0.forget_imode(), pins.d1.into_output(), satellite::BAUD);
 
         Self {
             serial,
             clock_pin: dp.TC0,
             led: Led::new(pins.d13.into_output()),
             leds_buttons: OnboardLedsButtons::new(pins.d7.into_output(), pins.d8.into_output()),
+            rc_input: ChannelInput::new(RC_TIMEOUT_MS),
+            drive: Drive::new(),
+            satellite_data: Some(pins.d2.into_pull_up_input()),
+            satellite: Satellite::new(),
+            satellite_power: pins.d3.into_output_high(),
+            rc_link: LinkState::NoSignal,
+            driving: false,
+            bind_pressed: false,
             state: CommandState::Disabled,
         }
     }
@@ -59,9 +105,11 @@ impl SpiderBot {
 
             let runtime = millis() - time_start;
             if runtime < time_budget {
-                let delay = time_budget - runtime;
-                //ufmt::uwriteln!(self.serial, "Waiting {}ms", delay).unwrap_infallible();
-                arduino_hal::delay_ms(delay as u16);
+                // the serial port only buffers a few frames from the satellite, so keep
+                // taking them in until the next tick
+                while millis() - time_start < time_budget {
+                    self.poll_satellite();
+                }
             }
             else {
                 ufmt::uwriteln!(self.serial,"Overrun! Expected {}ms, took {}ms", runtime, time_budget)
@@ -73,9 +121,59 @@ impl SpiderBot {
     fn tick(&mut self, clock: usize) {
         self.led.tick(clock);
         self.leds_buttons.tick(clock);
+        self.poll_satellite();
+        self.drive.update(&self.rc_input, clock);
+
+        let rc_link = self.rc_input.link_state(clock as u32);
+        if rc_link != self.rc_link {
+            ufmt::uwriteln!(self.serial, "RC link: {:?}", rc_link).unwrap_infallible();
+            self.rc_link = rc_link;
+        }
+        let driving = !self.drive.command().is_stopped();
+        if driving != self.driving {
+            ufmt::uwriteln!(self.serial, "Drive: {}", if driving { "moving" } else { "stopped" }).unwrap_infallible();
+            self.driving = driving;
+        }
 
         if self.leds_buttons.button_a().is_pressed() {
             self.leds_buttons.led_red_mut().toggle();
         }
+
+        let bind_pressed = self.leds_buttons.button_b().is_pressed();
+        if bind_pressed && !self.bind_pressed {
+            ufmt::uwriteln!(self.serial, "Binding satellite for {:?}", SATELLITE_SYSTEM).unwrap_infallible();
+            // D2 only drives the line for the bind pulses, then leaves it to the satellite
+            let mut data = self.satellite_data.take().unwrap().into_output_high();
+            let result = satellite_bind::bind(&mut data, &mut self.satellite_power, SATELLITE_SYSTEM, Remote::Internal);
+            self.satellite_data = Some(data.into_pull_up_input());
+            // D0 heard the bind pulses as garbage, and the new binding may be at another
+            // resolution
+            while let Ok(true) = self.serial.read_ready() {
+                let _ = self.serial.read(&mut [0; satellite::FRAME_LENGTH]);
+            }
+            self.satellite = Satellite::new();
+            if let Err(err) = result {
+                ufmt::uwriteln!(self.serial, "Satellite bind failed: {}", err).unwrap_infallible();
+            }
+        }
+        self.bind_pressed = bind_pressed;
+    }
+
+    /// Takes in whatever the satellite has sent since the last call, passing each whole
+    /// frame on to `rc_input`
+    fn poll_satellite(&mut self) {
+        let mut buf = [0u8; satellite::FRAME_LENGTH];
+        while let Ok(true) = self.serial.read_ready() {
+            // a framing error means a lost byte, which the gap before the next frame
+            // resynchronizes
+            let Ok(count) = self.serial.read(&mut buf) else { continue };
+
+            let (now_us, now_ms) = (micros(), millis() as u32);
+            for &byte in &buf[..count] {
+                if let Some(frame) = self.satellite.push(byte, now_us) {
+                    self.rc_input.update_from_satellite(&mut self.satellite, &frame, now_ms);
+                }
+            }
+        }
     }
 }
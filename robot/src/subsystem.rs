@@ -1,3 +1,4 @@
+pub mod drive;
 pub mod onboard_leds_buttons;
 
 pub trait Subsystem {
@@ -0,0 +1,65 @@
+use srxl2::{
+    channel::Normalized,
+    input::RcInput,
+};
+
+/// Spektrum channel order: throttle, aileron, elevator, rudder
+const CHANNEL_STRAFE: usize = 1;
+const CHANNEL_FORWARD: usize = 2;
+const CHANNEL_TURN: usize = 3;
+
+/// Stick positions closer to center than this count as centered (about 3%)
+const DEADBAND: u16 = 1000;
+
+/// How the body should move, each axis from -1.0 (back/left) to +1.0 (forward/right)
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct DriveCommand {
+    pub forward: Normalized,
+    pub strafe: Normalized,
+    pub turn: Normalized,
+}
+
+impl DriveCommand {
+    pub fn is_stopped(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Turns stick positions into a drive command. Reads sticks only through `RcInput`, so
+/// it doesn't matter which receiver they came from.
+pub struct Drive {
+    command: DriveCommand,
+}
+
+impl Drive {
+    pub fn new() -> Self {
+        Self {
+            command: DriveCommand::default(),
+        }
+    }
+
+    pub fn command(&self) -> DriveCommand {
+        self.command
+    }
+
+    /// Stops whenever the input is in failsafe, or a stick channel is missing
+    pub fn update(&mut self, input: &dyn RcInput, clock: usize) {
+        if input.is_failsafe(clock as u32) {
+            self.command = DriveCommand::default();
+            return;
+        }
+
+        self.command = DriveCommand {
+            forward: Self::axis(input, CHANNEL_FORWARD),
+            strafe: Self::axis(input, CHANNEL_STRAFE),
+            turn: Self::axis(input, CHANNEL_TURN),
+        };
+    }
+
+    fn axis(input: &dyn RcInput, channel: usize) -> Normalized {
+        match input.channel(channel) {
+            Some(value) if value.0.unsigned_abs() > DEADBAND => value,
+            _ => Normalized::ZERO,
+        }
+    }
+}
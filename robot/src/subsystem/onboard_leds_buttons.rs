@@ -30,11 +30,15 @@ impl OnboardLedsButtons {
     pub fn button_a(&self) -> & dyn Button {
         &self.pin_red_a
     }
+    pub fn button_b(&self) -> & dyn Button {
+        &self.pin_green_b
+    }
 }
 
 impl Subsystem for OnboardLedsButtons {
     fn tick(&mut self, clock: usize) {
         self.pin_red_a.refresh(clock);
+        self.pin_green_b.refresh(clock);
     }
 }
 
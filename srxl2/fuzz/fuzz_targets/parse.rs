@@ -0,0 +1,103 @@
+//! Feeds arbitrary bus traffic through the framer and into a receiver (bus master) and a
+//! flight controller (slave), with time passing in between, to check nothing the bus can
+//! carry makes them panic.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use srxl2::{
+    device::{DeviceId, DeviceInfo, DeviceType},
+    flags::Flags,
+    framer::Framer,
+    handshake::{Baud, BaudRates},
+    packet::{Packet, PacketType, FRAMING_LENGTH, SRXL_MAX_BUFFER_SIZE},
+    Srxl2Interpreter,
+    Transport,
+};
+
+const PACKET_TYPES: [PacketType; 7] = [
+    PacketType::Handshake,
+    PacketType::BindInfo,
+    PacketType::ParamConfig,
+    PacketType::SignalQuality,
+    PacketType::TelemetryData,
+    PacketType::ControlData,
+    PacketType::Internal,
+];
+
+#[derive(Arbitrary, Debug)]
+enum Step {
+    /// Raw bytes arriving on the bus
+    Receive(Vec<u8>),
+    /// A packet with a good CRC around an arbitrary payload, which random bytes would
+    /// almost never produce
+    Packet { packet_type: u8, payload: Vec<u8> },
+    /// Time passing, in milliseconds
+    Run(i16),
+}
+
+struct Discard;
+
+impl Transport for Discard {
+    fn send(&mut self, _uart: u8, _packet: &[u8]) { }
+    fn change_baud(&mut self, _uart: u8, _baud: Baud) { }
+}
+
+fn device(device_id: DeviceId, priority: u8) -> Srxl2Interpreter {
+    let mut srxl = Srxl2Interpreter::new();
+    srxl.init_device(device_id, priority, Flags::from(DeviceInfo::TelemTxEnabled), 0x1234_5678);
+    srxl.init_bus(0, 0, BaudRates::from([Baud::Baud115200, Baud::Baud400000]));
+    srxl
+}
+
+fn deliver(devices: &mut [Srxl2Interpreter], bytes: &[u8]) {
+    if let Ok(packet) = Packet::try_from_slice_unverified(bytes) {
+        let _ = packet.as_handshake_ref();
+        let _ = packet.as_bind_ref();
+        let _ = packet.as_param_ref();
+        let _ = packet.as_rssi_ref();
+        let _ = packet.as_telemetry_ref();
+        let _ = packet.as_internal_ref();
+        if let Some(control) = packet.as_control_ref() {
+            let _ = control.as_channel_ref();
+            let _ = control.as_vtx_ref();
+            let _ = control.as_fwd_pgm_ref();
+        }
+    }
+    for srxl in devices {
+        srxl.parse_packet(0, bytes, &mut Discard);
+    }
+}
+
+fuzz_target!(|steps: Vec<Step>| {
+    let mut devices = [
+        device(DeviceId::new(DeviceType::Receiver, 1), 0),
+        device(DeviceId::new(DeviceType::FlightController, 0), 30),
+    ];
+    let mut framer = Framer::new();
+
+    for step in steps {
+        match step {
+            Step::Receive(bytes) => {
+                for byte in bytes {
+                    if let Some(packet) = framer.push(byte) {
+                        let packet = packet.to_vec();
+                        deliver(&mut devices, &packet);
+                    }
+                }
+            },
+            Step::Packet { packet_type, payload } => {
+                let packet_type = PACKET_TYPES[packet_type as usize % PACKET_TYPES.len()];
+                let len = payload.len().min(SRXL_MAX_BUFFER_SIZE - FRAMING_LENGTH);
+                let packet = Packet::new(packet_type, &payload[..len]);
+                deliver(&mut devices, packet.as_slice());
+            },
+            Step::Run(delta_ms) => {
+                for srxl in &mut devices {
+                    srxl.run(0, delta_ms, &mut Discard);
+                }
+            },
+        }
+    }
+});
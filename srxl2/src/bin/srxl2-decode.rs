@@ -0,0 +1,340 @@
+//! Decodes a capture of SRXL2 bus traffic (see `srxl2::capture`) into readable packets.
+//!
+//! Usage: `srxl2-decode [capture file]`, reading standard input without a file.
+
+use std::{
+    env,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    process::ExitCode,
+};
+
+use srxl2::{
+    bind::Request as BindRequest,
+    capture::Record,
+    channel::ChannelValue,
+    control::CmdCode,
+    framer::Framer,
+    handshake::Baud,
+    internal::InternalTest,
+    packet::{Packet, PacketType},
+    param::Request as ParamRequest,
+    rssi::Request as RssiRequest,
+};
+
+/// A gap this long in the middle of a packet means the rest of it was lost
+const IDLE_GAP_US: u32 = 1000;
+
+fn main() -> ExitCode {
+    let input: Box<dyn BufRead> = match env::args().nth(1) {
+        None => Box::new(io::stdin().lock()),
+        Some(path) => match File::open(&path) {
+            Err(err) => {
+                eprintln!("{path}: {err}");
+                return ExitCode::FAILURE;
+            },
+            Ok(file) => Box::new(BufReader::new(file)),
+        },
+    };
+
+    let mut decoder = Decoder::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = match line {
+            Err(err) => {
+                eprintln!("{err}");
+                return ExitCode::FAILURE;
+            },
+            Ok(line) => line,
+        };
+
+        let mut buf = vec![0u8; line.len() / 2];
+        match Record::parse(&line, &mut buf) {
+            Err(err) => eprintln!("line {}: {err}", index + 1),
+            Ok(None) => (),
+            Ok(Some(record)) => decoder.push(&record),
+        }
+    }
+    decoder.finish();
+    ExitCode::SUCCESS
+}
+
+struct Decoder {
+    framer: Framer,
+    /// Time of the last record, to notice a packet cut short
+    last_us: Option<u32>,
+    /// Time the packet being received started
+    packet_us: u32,
+    /// Bytes skipped while looking for the start of a packet
+    skipped: usize,
+}
+
+impl Decoder {
+    fn new() -> Self {
+        Self {
+            framer: Framer::new(),
+            last_us: None,
+            packet_us: 0,
+            skipped: 0,
+        }
+    }
+
+    fn push(&mut self, record: &Record) {
+        let gap = self.last_us.map(|last_us| record.timestamp_us.wrapping_sub(last_us));
+        if self.framer.is_receiving() && gap.is_some_and(|gap| gap > IDLE_GAP_US) {
+            println!("{}  incomplete packet", format_time(self.packet_us));
+            self.framer.reset();
+        }
+        self.last_us = Some(record.timestamp_us);
+
+        for byte in record.bytes {
+            if !self.framer.is_receiving() {
+                self.packet_us = record.timestamp_us;
+            }
+            match self.framer.push(*byte) {
+                Some(packet) => {
+                    if self.skipped > 0 {
+                        println!("{}  skipped {} bytes of noise", format_time(self.packet_us), self.skipped);
+                        self.skipped = 0;
+                    }
+                    print_packet(self.packet_us, packet);
+                },
+                None => {
+                    if !self.framer.is_receiving() {
+                        self.skipped += 1;
+                    }
+                },
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.framer.is_receiving() {
+            println!("{}  incomplete packet at end of capture", format_time(self.packet_us));
+        }
+        if self.skipped > 0 {
+            println!("skipped {} bytes of noise at end of capture", self.skipped);
+        }
+    }
+}
+
+fn format_time(timestamp_us: u32) -> String {
+    format!("{:>12.3} ms", timestamp_us as f64 / 1000.0)
+}
+
+fn print_packet(timestamp_us: u32, bytes: &[u8]) {
+    let packet = match Packet::try_from_slice_unverified(bytes) {
+        Err(err) => {
+            println!("{}  unreadable packet ({err}): {}", format_time(timestamp_us), hex(bytes));
+            return;
+        },
+        Ok(packet) => packet,
+    };
+
+    let crc = if packet.is_crc_valid() {
+        String::from("CRC ok")
+    }
+    else {
+        format!("CRC BAD (got {:04X}, expected {:04X})", packet.crc(), packet.expected_crc())
+    };
+    println!(
+        "{}  {:02X} {:02X} {:02X}  {} ({} bytes), {crc}",
+        format_time(timestamp_us),
+        bytes[0],
+        bytes[1],
+        bytes[2],
+        { packet.hdr.packet_type },
+        packet.len(),
+    );
+
+    let details = match packet.hdr.packet_type {
+        PacketType::Handshake => handshake(&packet),
+        PacketType::BindInfo => bind(&packet),
+        PacketType::ParamConfig => param(&packet),
+        PacketType::SignalQuality => rssi(&packet),
+        PacketType::TelemetryData => telemetry(&packet),
+        PacketType::ControlData => control(&packet),
+        PacketType::Internal => internal(&packet),
+    };
+    for line in details.unwrap_or_else(|| vec![format!("payload doesn't fit the packet type: {}", hex(packet.payload()))]) {
+        println!("{:18}{line}", "");
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect::<Vec<_>>().join(" ")
+}
+
+fn handshake(packet: &Packet) -> Option<Vec<String>> {
+    let handshake = packet.as_handshake_ref()?.handshake;
+    let baud = if handshake.baud_supported.supports(Baud::Baud400000) { "115200/400000" } else { "115200" };
+    Some(vec![
+        format!("{} -> {}", handshake.src_dev_id, handshake.dest_dev_id),
+        format!(
+            "priority {}, baud {baud}, info {:02X}, uid {:08X}",
+            handshake.priority,
+            handshake.info.bits(),
+            { handshake.uid },
+        ),
+    ])
+}
+
+fn bind(packet: &Packet) -> Option<Vec<String>> {
+    let bind = packet.as_bind_ref()?.bind;
+    let request = match bind.request {
+        BindRequest::Enter => "enter bind mode",
+        BindRequest::Status => "request bind info",
+        BindRequest::BoundData => "bound data report",
+        BindRequest::SetBind => "set bind info",
+    };
+    Some(vec![
+        format!("{request}, device {}", bind.device_id),
+        format!(
+            "bind type {:02X}, options {:02X}, guid {:016X}, uid {:08X}",
+            bind.data.bind_type,
+            bind.data.options.bits(),
+            { bind.data.guid },
+            { bind.data.uid },
+        ),
+    ])
+}
+
+fn param(packet: &Packet) -> Option<Vec<String>> {
+    let param = packet.as_param_ref()?.param;
+    let request = match param.request {
+        ParamRequest::Query => "query",
+        ParamRequest::Write => "write",
+    };
+    Some(vec![format!(
+        "{request} to {}, parameter {:08X} = {:08X}",
+        param.dest_dev_id,
+        { param.param_id },
+        { param.param_val },
+    )])
+}
+
+fn rssi(packet: &Packet) -> Option<Vec<String>> {
+    let rssi = packet.as_rssi_ref()?.rssi;
+    let request = match rssi.request {
+        RssiRequest::Request => "request",
+        RssiRequest::Send => "report",
+    };
+    Some(vec![format!(
+        "{request}, antennas A {} B {} C {} D {}",
+        rssi.antenna_a,
+        rssi.antenna_b,
+        rssi.antenna_c,
+        rssi.antenna_d,
+    )])
+}
+
+fn telemetry(packet: &Packet) -> Option<Vec<String>> {
+    let telemetry = packet.as_telemetry_ref()?.telemetry;
+    let payload = &telemetry.payload;
+    let data = &payload.data;
+    // X-Bus sensor data is big-endian, unlike the rest of SRXL2
+    let be16 = |index: usize| u16::from_be_bytes([data[index], data[index + 1]]);
+
+    let mut lines = vec![format!(
+        "to {}, sensor {:02X}{}, secondary id {:02X}",
+        telemetry.dest_dev,
+        payload.sensor_id,
+        match payload.sensor_id {
+            0x20 => " (ESC)",
+            0x7E => " (RPM)",
+            0x7F => " (flight log)",
+            _ => "",
+        },
+        payload.secondary_id,
+    )];
+    match payload.sensor_id {
+        0x20 => lines.push(format!(
+            "{} rpm, {:.2} V, FET {:.1} °C, motor {:.2} A, BEC {:.1} °C {:.1} A {:.2} V, throttle {:.1}%, power {:.1}%",
+            be16(0) as u32 * 10,
+            be16(2) as f64 / 100.0,
+            be16(4) as f64 / 10.0,
+            be16(6) as f64 / 100.0,
+            be16(8) as f64 / 10.0,
+            data[10] as f64 / 10.0,
+            data[11] as f64 / 20.0,
+            data[12] as f64 / 2.0,
+            data[13] as f64 / 2.0,
+        )),
+        0x7E => lines.push(format!(
+            "period {} µs, {:.2} V, {} °F, dBm A {} B {}",
+            be16(0),
+            be16(2) as f64 / 100.0,
+            be16(4) as i16,
+            data[6] as i8,
+            data[7] as i8,
+        )),
+        0x7F => lines.push(format!(
+            "fades A {} B {} L {} R {}, frame losses {}, holds {}, {:.2} V",
+            be16(0),
+            be16(2),
+            be16(4),
+            be16(6),
+            be16(8),
+            be16(10),
+            be16(12) as f64 / 100.0,
+        )),
+        _ => (),
+    }
+    lines.push(format!("data {}", hex(data)));
+    Some(lines)
+}
+
+fn control(packet: &Packet) -> Option<Vec<String>> {
+    let control = packet.as_control_ref()?;
+    let cmd = match control.control.cmd {
+        CmdCode::Channel => "channel data",
+        CmdCode::ChannelFailsafe => "failsafe channel data",
+        CmdCode::Vtx => "VTX settings",
+        CmdCode::FwdPgm => "forward programming",
+    };
+    let mut lines = vec![format!("{cmd}, reply from {}", control.control.reply_id)];
+
+    if let Some(channel) = control.as_channel_ref() {
+        let data = channel.control.data;
+        lines.push(format!("rssi {}, frame losses {}, mask {:08X}", data.rssi, { data.frame_losses }, { data.mask }));
+
+        let mask = data.mask;
+        let values = data.values;
+        let channels = (0..32).filter(|index| mask & (1 << index) != 0)
+            .zip(values)
+            .map(|(index, raw)| {
+                let value = ChannelValue::from_raw(raw);
+                format!("{index}: {} µs {:+}%", value.to_us(), value.to_percent())
+            })
+            .collect::<Vec<_>>();
+        for chunk in channels.chunks(4) {
+            lines.push(chunk.join("   "));
+        }
+    }
+    else if let Some(vtx) = control.as_vtx_ref() {
+        let vtx = vtx.control.data;
+        lines.push(format!(
+            "band {}, channel {}, pit mode {}, power {} ({} mW), region {}",
+            vtx.band as u8,
+            vtx.channel,
+            vtx.pit as u8,
+            vtx.power as u8,
+            { vtx.power_dec },
+            vtx.region as u8,
+        ));
+    }
+    Some(lines)
+}
+
+fn internal(packet: &Packet) -> Option<Vec<String>> {
+    let internal = packet.as_internal_ref()?.internal;
+    let test = match internal.test {
+        InternalTest::EchoRequest => "echo request",
+        InternalTest::EchoReply => "echo reply",
+    };
+    Some(vec![format!(
+        "{test}, {} -> {}, key {:08X}",
+        internal.src_dev_id,
+        internal.dest_dev_id,
+        { internal.key },
+    )])
+}
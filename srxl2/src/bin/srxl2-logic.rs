@@ -0,0 +1,509 @@
+//! Decodes what the Uno recorded of a serial line: `monitor_raw`'s edge captures (see
+//! `srxl2::logic`), or `monitor_samples`' hex dump of samples. Works out the baud
+//! rate, takes the UART bytes off the line, and lists the SRXL2 packets in them, with
+//! framing errors where they happened. With `--capture`, writes the bytes out as a capture
+//! (see `srxl2::capture`) instead, for `srxl2-decode` to show what's in the packets.
+//!
+//! Usage: `srxl2-logic [--baud N] [--sample-rate HZ] [--capture] [file]`, reading standard
+//! input without a file. `--baud` overrides the rate worked out from the line. The hex
+//! dump doesn't say how fast it was sampled, so unless given `--sample-rate`, its times
+//! are worked out from the baud rate, 115200 by default. Nothing records the time between
+//! captures, so each one is put 10ms after the one before.
+
+use std::{
+    env,
+    fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+};
+
+use srxl2::{
+    capture::{max_line_length, Record},
+    framer::Framer,
+    logic::{Decoder, Event, Status, Trigger, MAGIC, TICKS_PER_US},
+    packet::Packet,
+    uart::{estimate_baud, snap_baud, FrameError, Framing, LineReceiver},
+};
+
+const USAGE: &str = "usage: srxl2-logic [--baud N] [--sample-rate HZ] [--capture] [file]";
+/// The Uno's timer, which the edge captures are timed with
+const TIMER_HZ: u32 = 1_000_000 * TICKS_PER_US;
+/// SRXL2 starts at 115200, and most buses stay there
+const DEFAULT_BAUD: u32 = 115_200;
+/// A gap this long in the middle of a packet means the rest of it was lost
+const IDLE_GAP_US: u64 = 1000;
+/// Time put between one recording and the next
+const SEGMENT_GAP_US: u64 = 10_000;
+
+struct Options {
+    baud: Option<u32>,
+    sample_rate: Option<u32>,
+    capture: bool,
+    path: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            return ExitCode::FAILURE;
+        },
+        Ok(options) => options,
+    };
+
+    let mut bytes = Vec::new();
+    let read = match &options.path {
+        None => io::stdin().lock().read_to_end(&mut bytes).map(|_| ()),
+        Some(path) => fs::read(path).map(|file| bytes = file),
+    };
+    if let Err(err) = read {
+        eprintln!("{}: {err}", options.path.as_deref().unwrap_or("stdin"));
+        return ExitCode::FAILURE;
+    }
+
+    let segments = if bytes.windows(MAGIC.len()).any(|window| window == MAGIC) {
+        read_captures(&bytes)
+    }
+    else {
+        read_dump(&String::from_utf8_lossy(&bytes))
+    };
+    if segments.is_empty() {
+        eprintln!("no captures or hex dump found");
+        return ExitCode::FAILURE;
+    }
+
+    let mut output: Box<dyn Output> = if options.capture { Box::new(CaptureOutput::new()) } else { Box::new(PacketOutput::new()) };
+    let mut time_us = 0;
+    for segment in &segments {
+        time_us = match Timing::of(segment, &options) {
+            None => {
+                output.heading(&format!("{}: can't tell the baud rate", segment.name));
+                time_us
+            },
+            Some(timing) => decode(segment, &timing, time_us, output.as_mut()),
+        } + SEGMENT_GAP_US;
+    }
+    ExitCode::SUCCESS
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        baud: None,
+        sample_rate: None,
+        capture: false,
+        path: None,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--baud" => options.baud = Some(number(&arg, args.next())?),
+            "--sample-rate" => options.sample_rate = Some(number(&arg, args.next())?),
+            "--capture" => options.capture = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ => options.path = Some(arg),
+        }
+    }
+    Ok(options)
+}
+
+fn number(option: &str, value: Option<String>) -> Result<u32, String> {
+    value.and_then(|value| value.parse().ok()).filter(|value| *value > 0).ok_or_else(|| format!("{option} needs a number"))
+}
+
+/// A stretch of the line recorded without a break
+struct Segment {
+    name: String,
+    /// The level the line was at before the first run, if it's known
+    before: Option<bool>,
+    /// The level of the first run
+    level: bool,
+    /// How long the line held each level, in ticks, starting with `level`
+    runs: Vec<u32>,
+    /// Ticks in a second, if it's known
+    tick_hz: Option<u32>,
+    /// Why a capture ended
+    status: Option<Status>,
+}
+
+/// Picks the capture blocks out of the Uno's output, skipping anything else
+fn read_captures(bytes: &[u8]) -> Vec<Segment> {
+    let mut decoder = Decoder::new();
+    let mut segments = Vec::new();
+    let mut current: Option<Segment> = None;
+    for (offset, byte) in bytes.iter().enumerate() {
+        match decoder.push(*byte) {
+            Err(err) => {
+                eprintln!("byte {offset}: {err}");
+                current = None;
+            },
+            Ok(None) => (),
+            Ok(Some(Event::Start(header))) => {
+                let (name, before) = match header.trigger {
+                    Trigger::Immediate => (String::from("as soon as armed"), None),
+                    Trigger::FallingEdge => (String::from("at a falling edge"), Some(!header.level)),
+                    Trigger::IdleGap(ticks) => (format!("after {}µs idle", ticks / TICKS_PER_US), Some(!header.level)),
+                };
+                current = Some(Segment {
+                    name: format!("capture {}, {name}", segments.len() + 1),
+                    before,
+                    level: header.level,
+                    runs: Vec::new(),
+                    tick_hz: Some(TIMER_HZ),
+                    status: None,
+                });
+            },
+            Ok(Some(Event::Run(ticks))) => {
+                if let Some(segment) = &mut current {
+                    segment.runs.push(ticks);
+                }
+            },
+            Ok(Some(Event::End(status))) => {
+                if let Some(mut segment) = current.take() {
+                    segment.status = Some(status);
+                    segments.push(segment);
+                }
+            },
+        }
+    }
+    if let Some(segment) = current {
+        eprintln!("{} is cut short", segment.name);
+        segments.push(segment);
+    }
+    segments
+}
+
+/// Reads the `offset | bytes...` lines of the older sample mode: 8 samples to a byte, the
+/// first in the lowest bit. A new dump starts wherever the offsets don't follow on, since
+/// nothing was sampled while the last one was printed.
+fn read_dump(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut samples = Vec::new();
+    let mut start = 0;
+    let mut next_offset = None;
+    for (index, line) in text.lines().enumerate() {
+        let Some((offset, hex)) = line.split_once('|') else {
+            if !line.trim().is_empty() {
+                eprintln!("line {}: not a line of a dump", index + 1);
+            }
+            continue;
+        };
+        let offset = u32::from_str_radix(offset.trim(), 16);
+        let bytes: Result<Vec<u8>, _> = hex.split_whitespace().map(|byte| u8::from_str_radix(byte, 16)).collect();
+        let (Ok(offset), Ok(bytes)) = (offset, bytes) else {
+            eprintln!("line {}: bad hex", index + 1);
+            continue;
+        };
+
+        if next_offset != Some(offset) {
+            segments.extend(dump_segment(start, &samples));
+            samples.clear();
+            start = offset;
+        }
+        samples.extend(bytes.iter().flat_map(|byte| (0..8).map(move |bit| byte >> bit & 1 != 0)));
+        next_offset = Some(offset + bytes.len() as u32);
+    }
+    segments.extend(dump_segment(start, &samples));
+    segments
+}
+
+fn dump_segment(offset: u32, samples: &[bool]) -> Option<Segment> {
+    let level = *samples.first()?;
+    let mut runs = vec![0u32];
+    let mut last = level;
+    for sample in samples {
+        if *sample != last {
+            runs.push(0);
+            last = *sample;
+        }
+        *runs.last_mut()? += 1;
+    }
+    Some(Segment {
+        name: format!("dump from {offset:04x}"),
+        before: None,
+        level,
+        runs,
+        tick_hz: None,
+        status: None,
+    })
+}
+
+struct Timing {
+    tick_hz: u32,
+    baud: u32,
+    description: String,
+}
+
+impl Timing {
+    /// Works out the baud rate from the runs, or for samples taken at an unknown rate,
+    /// how fast they were taken
+    fn of(segment: &Segment, options: &Options) -> Option<Self> {
+        // The first and last runs are cut short by the ends of the recording
+        let mut runs = match segment.runs.len() {
+            0..=2 => segment.runs.clone(),
+            len => segment.runs[1..len - 1].to_vec(),
+        };
+
+        if let Some(tick_hz) = segment.tick_hz.or(options.sample_rate) {
+            let estimate = estimate_baud(&mut runs, tick_hz);
+            let baud = options.baud.or(estimate.and_then(snap_baud)).or(estimate)?;
+            let description = match estimate {
+                None => format!("{baud} baud"),
+                Some(estimate) => format!("{estimate} baud, decoded at {baud}"),
+            };
+            return Some(Self { tick_hz, baud, description });
+        }
+
+        // Bits to a million samples
+        let rate = estimate_baud(&mut runs, 1_000_000)?;
+        if rate > 500_000 {
+            eprintln!("{}: fewer than 2 samples a bit, too few to decode", segment.name);
+            return None;
+        }
+        let baud = options.baud.unwrap_or(DEFAULT_BAUD);
+        let tick_hz = (baud as u64 * 1_000_000 / rate as u64) as u32;
+        let description = format!("{:.1} samples a bit: at {baud} baud, {tick_hz} samples a second", 1e6 / rate as f64);
+        Some(Self { tick_hz, baud, description })
+    }
+}
+
+/// Takes the words off the line in `segment`, with times counted from `start_us`, and
+/// returns when it ended
+fn decode(segment: &Segment, timing: &Timing, start_us: u64, output: &mut dyn Output) -> u64 {
+    let framing = Framing::new();
+    let to_us = |ticks: u64| start_us + ticks * 1_000_000 / timing.tick_hz as u64;
+    let word_ticks = framing.frame_bits() as u64 * timing.tick_hz as u64 / timing.baud as u64;
+    output.heading(&format!("{}, {}", segment.name, timing.description));
+    output.start(word_ticks * 1_000_000 / timing.tick_hz as u64);
+
+    let mut receiver = LineReceiver::new(framing, timing.tick_hz, timing.baud);
+    if let Some(before) = segment.before {
+        receiver.set_level(before);
+    }
+    let mut level = segment.level;
+    for run in &segment.runs {
+        receiver.push(level, *run, |start, word| output.word(to_us(start), word));
+        level = !level;
+    }
+    // The line held its last level for at least the time it took to end the capture
+    if segment.status == Some(Status::Idle) {
+        receiver.push(level, word_ticks as u32 + 1, |start, word| output.word(to_us(start), word));
+    }
+
+    let end_us = to_us(receiver.now());
+    if receiver.is_receiving() {
+        output.note(end_us, "recording ends partway through a byte");
+    }
+    match segment.status {
+        Some(Status::Full) => output.note(end_us, "capture buffer filled"),
+        Some(Status::EdgesLost) => output.note(end_us, "edges came too close together to catch; the end of the capture is wrong"),
+        Some(Status::Idle) | None => (),
+    }
+    output.finish(end_us);
+    end_us
+}
+
+fn format_time(time_us: u64) -> String {
+    format!("{:>12.3} ms", time_us as f64 / 1000.0)
+}
+
+fn frame_error(err: FrameError) -> &'static str {
+    match err {
+        FrameError::Framing => "framing error: stop bit low",
+        FrameError::Parity => "parity error",
+    }
+}
+
+/// Where the words taken off the line go
+trait Output {
+    /// What the next recording is, and how it was timed
+    fn heading(&mut self, text: &str);
+    /// A recording starts, sent at `word_us` per word
+    fn start(&mut self, word_us: u64);
+    fn word(&mut self, time_us: u64, word: Result<u16, FrameError>);
+    fn note(&mut self, time_us: u64, text: &str);
+    /// The recording ends
+    fn finish(&mut self, time_us: u64);
+}
+
+/// Lists SRXL2 packets and errors
+struct PacketOutput {
+    framer: Framer,
+    /// Time of the last byte, to notice a packet cut short
+    last_us: Option<u64>,
+    /// Time the packet being received started
+    packet_us: u64,
+    /// Bytes skipped while looking for the start of a packet
+    skipped: usize,
+}
+
+impl PacketOutput {
+    fn new() -> Self {
+        Self {
+            framer: Framer::new(),
+            last_us: None,
+            packet_us: 0,
+            skipped: 0,
+        }
+    }
+
+    fn incomplete(&mut self) {
+        if self.framer.is_receiving() {
+            println!("{}  incomplete packet", format_time(self.packet_us));
+            self.framer.reset();
+        }
+    }
+
+    fn noise(&mut self, time_us: u64) {
+        if self.skipped > 0 {
+            println!("{}  skipped {} bytes of noise", format_time(time_us), self.skipped);
+            self.skipped = 0;
+        }
+    }
+}
+
+impl Output for PacketOutput {
+    fn heading(&mut self, text: &str) {
+        println!("{text}");
+    }
+
+    fn start(&mut self, _word_us: u64) {
+        self.last_us = None;
+    }
+
+    fn word(&mut self, time_us: u64, word: Result<u16, FrameError>) {
+        let gap = self.last_us.map(|last_us| time_us - last_us);
+        self.last_us = Some(time_us);
+        let byte = match word {
+            Err(err) => {
+                self.incomplete();
+                self.noise(time_us);
+                println!("{}  {}", format_time(time_us), frame_error(err));
+                return;
+            },
+            Ok(byte) => byte as u8,
+        };
+
+        if gap.is_some_and(|gap| gap > IDLE_GAP_US) {
+            self.incomplete();
+        }
+        if !self.framer.is_receiving() {
+            self.packet_us = time_us;
+        }
+        match self.framer.push(byte) {
+            Some(packet) => {
+                let packet = packet.to_vec();
+                self.noise(self.packet_us);
+                print_packet(self.packet_us, &packet);
+            },
+            None => {
+                if !self.framer.is_receiving() {
+                    self.skipped += 1;
+                }
+            },
+        }
+    }
+
+    fn note(&mut self, time_us: u64, text: &str) {
+        println!("{}  {text}", format_time(time_us));
+    }
+
+    fn finish(&mut self, time_us: u64) {
+        self.incomplete();
+        self.noise(time_us);
+    }
+}
+
+fn print_packet(time_us: u64, bytes: &[u8]) {
+    let packet = match Packet::try_from_slice_unverified(bytes) {
+        Err(err) => {
+            println!("{}  unreadable packet ({err}): {}", format_time(time_us), hex(bytes));
+            return;
+        },
+        Ok(packet) => packet,
+    };
+
+    let crc = if packet.is_crc_valid() { "CRC ok" } else { "CRC BAD" };
+    println!("{}  {} ({} bytes), {crc}: {}", format_time(time_us), { packet.hdr.packet_type }, packet.len(), hex(bytes));
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect::<Vec<_>>().join(" ")
+}
+
+/// Writes the bytes as capture records, a burst of bytes to a line, with everything else
+/// as comments
+struct CaptureOutput {
+    word_us: u64,
+    /// The bytes of the record being built, and when the first and last started
+    bytes: Vec<u8>,
+    first_us: u64,
+    last_us: u64,
+}
+
+impl CaptureOutput {
+    fn new() -> Self {
+        Self {
+            word_us: 0,
+            bytes: Vec::new(),
+            first_us: 0,
+            last_us: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.bytes.is_empty() {
+            return;
+        }
+
+        let record = Record {
+            timestamp_us: self.first_us as u32,
+            bytes: &self.bytes,
+        };
+        let mut line = vec![0; max_line_length(self.bytes.len())];
+        if let Some(len) = record.format(&mut line) {
+            // Nothing to be done if stdout has gone
+            let _ = io::stdout().write_all(&line[..len]);
+        }
+        self.bytes.clear();
+    }
+}
+
+impl Output for CaptureOutput {
+    fn heading(&mut self, text: &str) {
+        self.flush();
+        println!("# {text}");
+    }
+
+    fn start(&mut self, word_us: u64) {
+        self.word_us = word_us;
+    }
+
+    fn word(&mut self, time_us: u64, word: Result<u16, FrameError>) {
+        let byte = match word {
+            Err(err) => {
+                self.note(time_us, frame_error(err));
+                return;
+            },
+            Ok(byte) => byte as u8,
+        };
+
+        // More than a word's time between one byte and the next ends the burst
+        if !self.bytes.is_empty() && time_us - self.last_us > 2 * self.word_us {
+            self.flush();
+        }
+        if self.bytes.is_empty() {
+            self.first_us = time_us;
+        }
+        self.bytes.push(byte);
+        self.last_us = time_us;
+    }
+
+    fn note(&mut self, time_us: u64, text: &str) {
+        self.flush();
+        println!("# {time_us} {text}");
+    }
+
+    fn finish(&mut self, _time_us: u64) {
+        self.flush();
+    }
+}
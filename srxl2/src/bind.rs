@@ -7,12 +7,13 @@ use zerocopy::{
 };
 use crate::{
     flags::Flags,
-    packet::Header,
     device::DeviceId,
 };
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum Request {
     Enter = 0xEB,
     Status = 0xB5,
@@ -23,6 +24,8 @@ pub enum Request {
 /// Bit masks for Options byte
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum BindOption {
     None = 0,
     /// Set if this device should be enabled as the current telemetry device to tx over RF
@@ -35,6 +38,8 @@ pub enum BindOption {
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum BindStatus {
     NotBound            = 0x00,
     // Air types
@@ -52,6 +57,8 @@ pub enum BindStatus {
 
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct BindData {
     pub bind_type: u8,
     pub options: Flags<BindOption>,
@@ -61,9 +68,10 @@ pub struct BindData {
 
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct BindPayload {
     pub request: Request,
     pub device_id: DeviceId,
     pub data: BindData,
-    pub crc: u16,
 }
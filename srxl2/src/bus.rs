@@ -1,10 +1,11 @@
 use crate::{
-    device::{DeviceEntry, DeviceId, FullId, MAX_DEVICES},
+    device::{DeviceEntry, DeviceId, DeviceInfo, DeviceType, FullId, MAX_DEVICES},
     flags::Flags,
-    handshake::Baud,
-    internal::State,
-    packet::Packet,
+    handshake::{Baud, BaudRates},
+    internal::{PendingTest, State},
+    packet::{Packet, PacketType},
     receiver::ReceiverEntry,
+    transport::Transport,
     tx::TxFlag,
 };
 
@@ -20,47 +21,187 @@ pub const NUM_OF_BUSES: usize = 4;
 #[cfg(feature = "8_bus")]
 pub const NUM_OF_BUSES: usize = 8;
 
-pub const IS_HUB: bool = NUM_OF_BUSES > 1;
-
-pub const ALL_BUSES: usize = (1 << NUM_OF_BUSES) - 1;
+/// Polls in a row a device can leave unanswered before the master handshakes again
+pub const MAX_MISSED_POLLS: u8 = 5;
 
 pub struct Bus {
     /// Transmit packet buffer
-    srxl_out: Packet,
+    pub(crate) srxl_out: Packet,
     /// Receive packet buffer
-    srxl_in: Packet,
+    pub(crate) srxl_in: Packet,
     /// Current state of SRXL state machine
-    state: State,
+    pub(crate) state: State,
     /// Device ID and Bus Index of this device, set during init
-    full_id: FullId,
+    pub(crate) full_id: FullId,
     /// Number of other SRXL devices discovered via handshake
-    rx_dev_count: u8,
+    pub(crate) rx_dev_count: u8,
     /// Device entries for tracking SRXL telemetry priorities
-    rx_dev: [DeviceEntry; MAX_DEVICES],
+    pub(crate) rx_dev: [DeviceEntry; MAX_DEVICES],
     /// Sum of priorities requested for each discovered SRXL device
-    rx_dev_priority_sum: u16,
+    pub(crate) rx_dev_priority_sum: u16,
     /// Milliseconds since SRXL packet was received (incremented in srxlRun)
-    timeout_count_ms: u16,
+    pub(crate) timeout_count_ms: u16,
     /// Device ID to poll
-    request_id: DeviceId,
+    pub(crate) request_id: DeviceId,
     /// Baud rates this device can do: 0 = 115200, 1 = 400000
-    baud_supported: Baud,
+    pub(crate) baud_supported: BaudRates,
     /// Current baud rate: 0 = 115200, 1 = 400000
-    baud_rate: Baud,
+    pub(crate) baud_rate: Baud,
     /// Number of consecutive missed frames
-    frame_err_count: u8,
+    pub(crate) frame_err_count: u8,
     /// Pending outgoing packet types
-    tx_flags: Flags<TxFlag>,
+    pub(crate) tx_flags: Flags<TxFlag>,
     /// Index number of UART tied to this SRXL bus
-    uart: u8,
+    pub(crate) uart: u8,
     /// Receiver entry for the bus master, if one exists
-    master_rcvr: Option<ReceiverEntry>,
+    pub(crate) master_rcvr: Option<ReceiverEntry>,
     /// Mask for channels to be sent on master buses
-    channel_out_mask: u32,
+    pub(crate) channel_out_mask: u32,
     /// True if this device is the bus master on this bus
-    master: bool,
-    /// True if this bus master should poll all devices once more
-    poll_once_more: bool,
+    pub(crate) master: bool,
     /// True when this SRXL bus is initialized
-    initialized: bool,
+    pub(crate) initialized: bool,
+    /// Echo request waiting to be sent or answered
+    pub(crate) internal: Option<PendingTest>,
+    /// Milliseconds since the master last sent a frame
+    pub(crate) frame_ms: u16,
+    /// Index into the handshake poll list of the next device type to poll
+    pub(crate) poll_index: usize,
+    /// Weighted round robin counters for telemetry polling, parallel to rx_dev
+    pub(crate) poll_credit: [i16; MAX_DEVICES],
+    /// Destination of the next queued bind packet
+    pub(crate) bind_dest: DeviceId,
+    /// Baud rates supported by this device and every device that answered the handshake
+    pub(crate) baud_negotiated: BaudRates,
+    /// True from polling a device until something is received
+    pub(crate) reply_pending: bool,
+    /// Consecutive unanswered polls of each device, parallel to rx_dev
+    pub(crate) missed_polls: [u8; MAX_DEVICES],
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Self {
+            srxl_out: Packet::new(PacketType::Handshake, &[]),
+            srxl_in: Packet::new(PacketType::Handshake, &[]),
+            state: State::Disabled,
+            full_id: FullId {
+                device_id: DeviceId::new(DeviceType::None, 0),
+                bus_index: 0,
+            },
+            rx_dev_count: 0,
+            rx_dev: core::array::from_fn(|_| DeviceEntry::new(DeviceId::new(DeviceType::None, 0), 0, Flags::empty())),
+            rx_dev_priority_sum: 0,
+            timeout_count_ms: 0,
+            request_id: DeviceId::new(DeviceType::None, 0),
+            baud_supported: BaudRates::default(),
+            baud_rate: Baud::Baud115200,
+            frame_err_count: 0,
+            tx_flags: Flags::empty(),
+            uart: 0,
+            master_rcvr: None,
+            channel_out_mask: 0,
+            master: false,
+            initialized: false,
+            internal: None,
+            frame_ms: 0,
+            poll_index: 0,
+            poll_credit: [0; MAX_DEVICES],
+            bind_dest: DeviceId::broadcast(),
+            baud_negotiated: BaudRates::default(),
+            reply_pending: false,
+            missed_polls: [0; MAX_DEVICES],
+        }
+    }
+
+    /// Stores `packet` as the last one sent and puts it on the wire
+    pub(crate) fn send<T: Transport>(&mut self, packet: Packet, transport: &mut T) {
+        self.srxl_out = packet;
+        transport.send(self.uart, self.srxl_out.as_slice());
+    }
+
+    pub(crate) fn set_baud<T: Transport>(&mut self, baud: Baud, transport: &mut T) {
+        if self.baud_rate != baud {
+            self.baud_rate = baud;
+            transport.change_baud(self.uart, baud);
+        }
+    }
+
+    /// Records a device that answered the master's handshake, or updates it if it's
+    /// already known
+    pub(crate) fn add_device(&mut self, device_id: DeviceId, priority: u8, info: Flags<DeviceInfo>) {
+        let count = self.rx_dev_count as usize;
+        let index = match self.rx_dev_index(device_id) {
+            Some(index) => index,
+            None if count < MAX_DEVICES => {
+                self.rx_dev_count += 1;
+                self.poll_credit[count] = 0;
+                self.missed_polls[count] = 0;
+                count
+            },
+            None => return,
+        };
+
+        self.rx_dev[index] = DeviceEntry::new(device_id, priority, info);
+        self.rx_dev_priority_sum = self.rx_dev[..self.rx_dev_count as usize].iter()
+            .map(|entry| entry.priority as u16)
+            .sum();
+    }
+
+    /// Forgets all discovered devices, e.g. before handshaking again
+    pub(crate) fn clear_devices(&mut self) {
+        self.rx_dev_count = 0;
+        self.rx_dev_priority_sum = 0;
+    }
+
+    /// Picks the device to answer the next control packet, so that over time each device
+    /// gets a share of the replies proportional to its priority (smooth weighted round
+    /// robin). Returns the "no reply" ID 0x00 when nobody asked for telemetry.
+    pub(crate) fn next_reply_id(&mut self) -> DeviceId {
+        let count = self.rx_dev_count as usize;
+        if self.rx_dev_priority_sum == 0 {
+            return DeviceId::new(DeviceType::None, 0);
+        }
+
+        let mut best = 0;
+        for index in 0..count {
+            self.poll_credit[index] += self.rx_dev[index].priority as i16;
+            if self.poll_credit[index] > self.poll_credit[best] {
+                best = index;
+            }
+        }
+        self.poll_credit[best] -= self.rx_dev_priority_sum as i16;
+        self.rx_dev[best].device_id
+    }
+
+    /// Records that the device polled last has answered
+    pub(crate) fn poll_answered(&mut self) {
+        if !self.reply_pending {
+            return;
+        }
+        self.reply_pending = false;
+        if let Some(index) = self.rx_dev_index(self.request_id) {
+            self.missed_polls[index] = 0;
+        }
+    }
+
+    /// Counts a miss against the device polled last if it never answered. Returns true
+    /// once it has missed MAX_MISSED_POLLS in a row.
+    pub(crate) fn last_poll_missed(&mut self) -> bool {
+        if !self.reply_pending {
+            return false;
+        }
+        self.reply_pending = false;
+        match self.rx_dev_index(self.request_id) {
+            None => false,
+            Some(index) => {
+                self.missed_polls[index] = self.missed_polls[index].saturating_add(1);
+                self.missed_polls[index] >= MAX_MISSED_POLLS
+            },
+        }
+    }
+
+    fn rx_dev_index(&self, device_id: DeviceId) -> Option<usize> {
+        self.rx_dev[..self.rx_dev_count as usize].iter().position(|entry| entry.device_id == device_id)
+    }
 }
\ No newline at end of file
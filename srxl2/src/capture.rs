@@ -0,0 +1,108 @@
+//! Text format for raw bus traffic, so captures can be written by a device over a serial
+//! console and decoded later on a PC. Each line is one record: the time the first byte
+//! was received in microseconds (wrapping after about 71 minutes), a space, and the
+//! bytes in hex, e.g.
+//!
+//! ```text
+//! # receiver bus, 115200 baud
+//! 71000 A6210E2130000100070000009A76
+//! 72215 A6210E30211E0100070000005D3F
+//! ```
+//!
+//! Records don't have to line up with packets. Blank lines and lines starting with `#`
+//! are ignored, and the hex may have spaces between bytes.
+
+use crate::error::CaptureError;
+
+/// Longest line `Record::format` can produce for a record of `bytes` bytes
+pub const fn max_line_length(bytes: usize) -> usize {
+    // u32::MAX has 10 digits, then the space, the hex, and the newline
+    10 + 1 + 2 * bytes + 1
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Record<'a> {
+    pub timestamp_us: u32,
+    pub bytes: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+    /// Writes the record as a line of text, including the newline. Returns the length
+    /// of the line, or None if `out` is shorter than max_line_length.
+    pub fn format(&self, out: &mut [u8]) -> Option<usize> {
+        if out.len() < max_line_length(self.bytes.len()) {
+            return None;
+        }
+
+        let mut digits = [0u8; 10];
+        let mut count = 0;
+        let mut timestamp = self.timestamp_us;
+        loop {
+            digits[count] = b'0' + (timestamp % 10) as u8;
+            count += 1;
+            timestamp /= 10;
+            if timestamp == 0 {
+                break;
+            }
+        }
+
+        let mut len = 0;
+        for digit in digits[..count].iter().rev() {
+            out[len] = *digit;
+            len += 1;
+        }
+        out[len] = b' ';
+        len += 1;
+        for byte in self.bytes {
+            out[len] = HEX_DIGITS[(byte >> 4) as usize];
+            out[len + 1] = HEX_DIGITS[(byte & 0x0F) as usize];
+            len += 2;
+        }
+        out[len] = b'\n';
+        Some(len + 1)
+    }
+
+    /// Reads one line of a capture, storing the bytes in `buf`. Returns None for blank
+    /// lines and comments.
+    pub fn parse(line: &str, buf: &'a mut [u8]) -> Result<Option<Self>, CaptureError> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let (timestamp, hex) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let timestamp_us = match timestamp.parse() {
+            Err(_) => return Err(CaptureError::Timestamp),
+            Ok(timestamp_us) => timestamp_us,
+        };
+
+        let mut len = 0;
+        let mut digits = hex.bytes().filter(|c| !c.is_ascii_whitespace());
+        while let Some(high) = digits.next() {
+            let low = digits.next().ok_or(CaptureError::Hex)?;
+            let byte = match (hex_value(high), hex_value(low)) {
+                (Some(high), Some(low)) => high << 4 | low,
+                _ => return Err(CaptureError::Hex),
+            };
+            *buf.get_mut(len).ok_or(CaptureError::TooLong)? = byte;
+            len += 1;
+        }
+
+        Ok(Some(Self {
+            timestamp_us,
+            bytes: &buf[..len],
+        }))
+    }
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
@@ -5,10 +5,13 @@ use zerocopy::{
     IntoBytes,
 };
 
+pub const MAX_CHANNELS: usize = 32;
 
 /// Channel Data
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ChannelData {
     /// Best RSSI when sending channel data, or dropout RSSI when sending failsafe data
     pub rssi: i8,
@@ -29,4 +32,216 @@ impl ChannelData {
             values: [0; 32],
         }
     }
-}
\ No newline at end of file
+
+    /// True if the mask says the channel at `index` holds a value
+    pub fn has(&self, index: usize) -> bool {
+        index < MAX_CHANNELS && self.mask & (1 << index) != 0
+    }
+
+    /// The value of the channel at `index`, or None if it isn't present in the mask
+    pub fn get(&self, index: usize) -> Option<ChannelValue> {
+        if self.has(index) {
+            Some(ChannelValue::from_raw(self.values[index]))
+        }
+        else {
+            None
+        }
+    }
+
+    /// Stores a channel value and marks it present in the mask
+    pub fn set(&mut self, index: usize, value: ChannelValue) {
+        if index < MAX_CHANNELS {
+            self.values[index] = value.raw();
+            self.mask |= 1 << index;
+        }
+    }
+
+    /// Marks a channel as not present, leaving its last value in place
+    pub fn clear(&mut self, index: usize) {
+        if index < MAX_CHANNELS {
+            self.mask &= !(1 << index);
+        }
+    }
+
+    /// Wire form of the channels in `mask`: values are packed in channel order with no
+    /// gaps, so only the first `len` bytes of the result are sent
+    pub fn packed(&self, mask: u32) -> (Self, usize) {
+        let mask = mask & self.mask;
+        let mut packed = Self {
+            rssi: self.rssi,
+            frame_losses: self.frame_losses,
+            mask,
+            values: [0; 32],
+        };
+
+        let count = mask.count_ones() as usize;
+        for (slot, index) in (0..MAX_CHANNELS).filter(|index| mask & (1 << index) != 0).enumerate() {
+            packed.values[slot] = self.values[index];
+        }
+
+        (packed, size_of::<Self>() - size_of::<[u16; 32]>() + count * size_of::<u16>())
+    }
+
+    /// Takes in channel data as received on the wire, with values packed by `mask`.
+    /// Channels not in the packet keep their last value.
+    pub fn update_from_packed(&mut self, packed: &Self) {
+        self.rssi = packed.rssi;
+        self.frame_losses = packed.frame_losses;
+
+        let mask = packed.mask;
+        for (slot, index) in (0..MAX_CHANNELS).filter(|index| mask & (1 << index) != 0).enumerate() {
+            self.values[index] = packed.values[slot];
+        }
+        self.mask |= mask;
+    }
+
+    /// The index and value of every channel present in the mask, in order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, ChannelValue)> + '_ {
+        (0..MAX_CHANNELS).filter_map(|index| self.get(index).map(|value| (index, value)))
+    }
+}
+
+impl Default for ChannelData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One channel position as carried by SRXL2: 16 bits with 32768 at mid-scale and the
+/// lowest 2 bits reserved (always 0)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ChannelValue(u16);
+
+impl ChannelValue {
+    const RFU_MASK: u16 = 0b11;
+
+    pub const MIN: Self = Self(0);
+    pub const CENTER: Self = Self(0x8000);
+    pub const MAX: Self = Self(!Self::RFU_MASK);
+
+    /// Pulse width at the bottom and top of the range
+    pub const PULSE_MIN_US: u16 = 1000;
+    pub const PULSE_MAX_US: u16 = 2000;
+
+    /// Takes a value from the bus, dropping the reserved bits
+    pub const fn from_raw(raw: u16) -> Self {
+        Self(raw & !Self::RFU_MASK)
+    }
+
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+
+    /// Servo pulse width, mapping the full range linearly onto 1000-2000µs (1500µs center)
+    pub const fn to_us(self) -> u16 {
+        let span = (Self::PULSE_MAX_US - Self::PULSE_MIN_US) as u32;
+        Self::PULSE_MIN_US + ((self.0 as u32 * span + 0x8000) >> 16) as u16
+    }
+
+    /// Inverse of `to_us`, clamped to 1000-2000µs
+    pub const fn from_us(us: u16) -> Self {
+        let us = if us < Self::PULSE_MIN_US {
+            Self::PULSE_MIN_US
+        }
+        else if us > Self::PULSE_MAX_US {
+            Self::PULSE_MAX_US
+        }
+        else {
+            us
+        };
+
+        let span = (Self::PULSE_MAX_US - Self::PULSE_MIN_US) as u32;
+        let raw = ((us - Self::PULSE_MIN_US) as u32 * 0x10000 + span / 2) / span;
+        Self::from_rounded(raw)
+    }
+
+    /// Signed position, -1.0 at the bottom of the range and just under +1.0 at the top
+    pub const fn to_normalized(self) -> Normalized {
+        Normalized((self.0 as i32 - 0x8000) as i16)
+    }
+
+    pub const fn from_normalized(value: Normalized) -> Self {
+        Self::from_raw((value.0 as i32 + 0x8000) as u16)
+    }
+
+    /// Position in percent of travel from center, -100 to 100
+    pub const fn to_percent(self) -> i8 {
+        let offset = (self.0 as i32 - 0x8000) * 100;
+        // round half away from zero
+        let rounded = if offset < 0 { offset - 0x4000 } else { offset + 0x4000 };
+        (rounded / 0x8000) as i8
+    }
+
+    /// Inverse of `to_percent`, clamped to -100 to 100
+    pub const fn from_percent(percent: i8) -> Self {
+        let percent = if percent < -100 {
+            -100
+        }
+        else if percent > 100 {
+            100
+        }
+        else {
+            percent
+        };
+
+        // round half away from zero, straight to a step of the unreserved bits, so
+        // opposite percentages land the same distance either side of center
+        let step = 100 * (Self::RFU_MASK as i32 + 1);
+        let offset = percent as i32 * 0x8000;
+        let rounded = if offset < 0 { offset - step / 2 } else { offset + step / 2 };
+        Self::from_rounded((0x8000 + rounded / step * (Self::RFU_MASK as i32 + 1)) as u32)
+    }
+
+    /// Rounds to the nearest value with the reserved bits clear, saturating at MAX
+    const fn from_rounded(raw: u32) -> Self {
+        let raw = (raw + (Self::RFU_MASK as u32).div_ceil(2)) & !(Self::RFU_MASK as u32);
+        if raw > Self::MAX.0 as u32 {
+            Self::MAX
+        }
+        else {
+            Self(raw as u16)
+        }
+    }
+}
+
+impl Default for ChannelValue {
+    fn default() -> Self {
+        Self::CENTER
+    }
+}
+
+/// A signed fixed-point fraction in Q1.15 format: -32768 is -1.0 and 32767 is just
+/// under +1.0. Lets channel positions be scaled without floating point.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Normalized(pub i16);
+
+impl Normalized {
+    pub const MINUS_ONE: Self = Self(i16::MIN);
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(i16::MAX);
+
+    /// Multiplies `value` by this fraction, e.g. to scale a stick position to a speed
+    pub const fn scale(self, value: i16) -> i16 {
+        ((self.0 as i32 * value as i32) >> 15) as i16
+    }
+
+    /// The fraction `numerator / denominator`, saturating at -1.0 and +1.0
+    pub const fn from_ratio(numerator: i32, denominator: i32) -> Self {
+        if denominator == 0 {
+            return Self::ZERO;
+        }
+
+        let q15 = (numerator as i64 * 0x8000) / denominator as i64;
+        if q15 > i16::MAX as i64 {
+            Self::ONE
+        }
+        else if q15 < i16::MIN as i64 {
+            Self::MINUS_ONE
+        }
+        else {
+            Self(q15 as i16)
+        }
+    }
+}
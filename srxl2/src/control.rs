@@ -1,9 +1,6 @@
 use zerocopy::{
-    CastError,
-    TryCastError,
     KnownLayout,
     Immutable,
-    FromBytes,
     TryFromBytes,
     IntoBytes,
 };
@@ -11,7 +8,7 @@ use crate::{
     channel::ChannelData,
     device::DeviceId,
     fwd_pgm::FwdPgmData,
-    packet::Header,
+    packet::{Header, Packet, PacketType, SRXL_MAX_BUFFER_SIZE},
     vtx::VtxData
 };
 
@@ -20,7 +17,8 @@ pub const BASE_LENGTH: u8 = 3 + 2 + 2;
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum CmdCode {
     Channel = 0x00,
     ChannelFailsafe = 0x01,
@@ -28,6 +26,8 @@ pub enum CmdCode {
     FwdPgm = 0x03,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum Cmd {
     None,
     Channel,
@@ -64,6 +64,8 @@ const fn max3(a: usize, b: usize, c: usize) -> usize {
 
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ControlData {
     pub cmd: CmdCode,
     pub reply_id: DeviceId,
@@ -76,6 +78,8 @@ pub struct ControlData {
 
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ControlVtxData<'a> {
     pub cmd: &'a CmdCode,
     pub reply_id: &'a DeviceId,
@@ -84,6 +88,8 @@ pub struct ControlVtxData<'a> {
 
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ControlFwdPgmData<'a> {
     pub cmd: &'a CmdCode,
     pub reply_id: &'a DeviceId,
@@ -92,25 +98,44 @@ pub struct ControlFwdPgmData<'a> {
 
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ControlChannelData<'a> {
     pub cmd: &'a CmdCode,
     pub reply_id: &'a DeviceId,
     pub data: &'a ChannelData,
 }
 
+/// Builds a control data packet carrying the payload for `cmd`
+pub fn packet(cmd: CmdCode, reply_id: DeviceId, data: &[u8]) -> Packet {
+    let mut payload = [0u8; SRXL_MAX_BUFFER_SIZE];
+    payload[0] = cmd as u8;
+    payload[1] = reply_id.into();
+    payload[2..2 + data.len()].copy_from_slice(data);
+    Packet::new(PacketType::ControlData, &payload[..2 + data.len()])
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ControlPacket<'a> {
     pub hdr: &'a Header,
     pub control: &'a ControlData,
 }
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ControlVtxPacket<'a> {
     pub hdr: &'a Header,
     pub control: ControlVtxData<'a>,
 }
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ControlFwdPgmPacket<'a> {
     pub hdr: &'a Header,
     pub control: ControlFwdPgmData<'a>,
 }
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ControlChannelPacket<'a> {
     pub hdr: &'a Header,
     pub control: ControlChannelData<'a>,
@@ -118,14 +143,14 @@ pub struct ControlChannelPacket<'a> {
 
 impl<'a> ControlPacket<'a> {
     /// Used for Channel Data and Failsafe Channel Data commands
-    pub fn as_channel_ref(&self) -> Option<ControlChannelPacket> {
+    pub fn as_channel_ref(&self) -> Option<ControlChannelPacket<'_>> {
         if self.control.cmd != CmdCode::Channel && self.control.cmd != CmdCode::ChannelFailsafe {
             None
         }
         else {
-            match ChannelData::try_ref_from_bytes(self.control.data.as_slice()) {
+            match ChannelData::try_ref_from_prefix(self.control.data.as_slice()) {
                 Err(_) => None,
-                Ok(channel) => Some(ControlChannelPacket {
+                Ok((channel, _)) => Some(ControlChannelPacket {
                     hdr: self.hdr,
                     control: ControlChannelData {
                         cmd: &self.control.cmd,
@@ -139,14 +164,14 @@ impl<'a> ControlPacket<'a> {
     }
 
     /// Used for VTX commands
-    pub fn as_vtx_ref(&self) -> Option<ControlVtxPacket> {
+    pub fn as_vtx_ref(&self) -> Option<ControlVtxPacket<'_>> {
         if self.control.cmd != CmdCode::Vtx {
             None
         }
         else {
-            match VtxData::try_ref_from_bytes(self.control.data.as_slice()) {
+            match VtxData::try_ref_from_prefix(self.control.data.as_slice()) {
                 Err(_) => None,
-                Ok(vtx) => Some(ControlVtxPacket {
+                Ok((vtx, _)) => Some(ControlVtxPacket {
                     hdr: self.hdr,
                     control: ControlVtxData {
                         cmd: &self.control.cmd,
@@ -160,14 +185,14 @@ impl<'a> ControlPacket<'a> {
     }
 
     /// Used to pass forward programming data to an SRXL device
-    pub fn as_fwd_pgm_ref(&self) -> Option<ControlFwdPgmPacket> {
+    pub fn as_fwd_pgm_ref(&self) -> Option<ControlFwdPgmPacket<'_>> {
         if self.control.cmd != CmdCode::FwdPgm {
             None
         }
         else {
-            match FwdPgmData::try_ref_from_bytes(self.control.data.as_slice()) {
+            match FwdPgmData::try_ref_from_prefix(self.control.data.as_slice()) {
                 Err(_) => None,
-                Ok(fwd_pgm) => Some(ControlFwdPgmPacket {
+                Ok((fwd_pgm, _)) => Some(ControlFwdPgmPacket {
                     hdr: self.hdr,
                     control: ControlFwdPgmData {
                         cmd: &self.control.cmd,
@@ -1,19 +1,6 @@
 
-/// Set SRXL_CRC_OPTIMIZE_MODE in spm_srxl_config.h to one of the following values
-#[repr(u8)]
-pub enum CrcOptimizeMode {
-    /// Uses table lookup for CRC computation (requires 512 const bytes for CRC table)
-    Speed = 1,
-    /// Uses bitwise operations
-    Size = 2,
-    /// Uses STM32 register-level hardware acceleration (only available on STM32F30x devices for now)
-    StmHw = 3,
-    /// Uses STM32Cube HAL driver for hardware acceleration (only available on STM32F3/F7) -- see srxlCrc16() for details on HAL config
-    StmHal = 4,
-}
-
 #[cfg(feature = "crc_speed")]
-const crc_table: [u16; 256] =
+const CRC_TABLE: [u16; 256] =
 [
     0x0000, 0x1021, 0x2042, 0x3063, 0x4084, 0x50A5, 0x60C6, 0x70E7,
     0x8108, 0x9129, 0xA14A, 0xB16B, 0xC18C, 0xD1AD, 0xE1CE, 0xF1EF,
@@ -50,4 +37,28 @@ const crc_table: [u16; 256] =
     0x7C26, 0x6C07, 0x5C64, 0x4C45, 0x3CA2, 0x2C83, 0x1CE0, 0x0CC1,
     0xEF1F, 0xFF3E, 0xCF5D, 0xDF7C, 0xAF9B, 0xBFBA, 0x8FD9, 0x9FF8,
     0x6E17, 0x7E36, 0x4E55, 0x5E74, 0x2E93, 0x3EB2, 0x0ED1, 0x1EF0
-];
\ No newline at end of file
+];
+
+/// CRC-16-CCITT (XMODEM) over `data`, seeded with 0 as the SRXL2 spec requires.
+/// Called with everything in the packet except the two trailing CRC bytes.
+#[cfg(feature = "crc_speed")]
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc = (crc << 8) ^ CRC_TABLE[((crc >> 8) as u8 ^ byte) as usize];
+    }
+    crc
+}
+
+/// Bitwise version of the above, trading speed for the 512 bytes of table.
+#[cfg(not(feature = "crc_speed"))]
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
@@ -0,0 +1,342 @@
+//! TBS Crossfire / ExpressLRS serial protocol (CRSF): frames of an address, a length, a
+//! type, a payload, and a CRC8 (DVB-S2), sent at 420000 baud 8N1. Channels come out as
+//! `ChannelData` and link statistics as SRXL2 signal quality, the same as SRXL2.
+//! Payloads are big-endian.
+
+use zerocopy::{
+    byteorder::big_endian::U16,
+    KnownLayout,
+    Immutable,
+    FromBytes,
+    IntoBytes,
+    TryFromBytes,
+};
+use crate::{
+    channel::{ChannelData, ChannelValue},
+    rssi::{self, RssiPayload},
+    sbus::{pack_channels, unpack_channels, CHANNELS, PACKED_CHANNELS_LENGTH},
+};
+
+pub const BAUD: u32 = 420_000;
+/// Largest frame, from address to CRC
+pub const MAX_FRAME_LENGTH: usize = 64;
+/// Address and length bytes in front of the type, payload, and CRC counted by the length
+pub const HEADER_LENGTH: usize = 2;
+pub const MAX_PAYLOAD_LENGTH: usize = MAX_FRAME_LENGTH - HEADER_LENGTH - 2;
+
+/// Channel value at center; 172 and 1811 are ±100% travel (988 and 2012µs)
+pub const CHANNEL_CENTER: u16 = 992;
+
+/// Where a frame is going, in its first byte
+#[repr(u8)]
+#[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Address {
+    /// Also the sync byte on the receiver to flight controller link
+    FlightController = 0xC8,
+    RadioTransmitter = 0xEA,
+    Receiver = 0xEC,
+    TransmitterModule = 0xEE,
+}
+
+#[repr(u8)]
+#[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum FrameType {
+    Battery = 0x08,
+    LinkStatistics = 0x14,
+    RcChannelsPacked = 0x16,
+    FlightMode = 0x21,
+}
+
+/// CRC8 with polynomial 0xD5 (DVB-S2), over the type and payload
+pub fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in bytes {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0xD5 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// Link Statistics, sent by the receiver about every 200ms
+#[repr(C, packed)]
+#[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct LinkStatistics {
+    /// Uplink RSSI of each antenna, in -dBm
+    pub uplink_rssi_1: u8,
+    pub uplink_rssi_2: u8,
+    /// Uplink packets received, in percent
+    pub uplink_link_quality: u8,
+    /// Uplink signal to noise ratio, in dB
+    pub uplink_snr: i8,
+    pub active_antenna: u8,
+    pub rf_mode: u8,
+    pub uplink_tx_power: u8,
+    pub downlink_rssi: u8,
+    pub downlink_link_quality: u8,
+    pub downlink_snr: i8,
+}
+
+impl LinkStatistics {
+    /// Per-antenna RSSI as an SRXL2 signal quality report, in dBm
+    pub fn signal_quality(&self) -> RssiPayload {
+        let dbm = |rssi: u8| (rssi as i16).wrapping_neg().max(i8::MIN as i16) as i8;
+        RssiPayload {
+            request: rssi::Request::Send,
+            antenna_a: dbm(self.uplink_rssi_1),
+            antenna_b: dbm(self.uplink_rssi_2),
+            antenna_c: 0,
+            antenna_d: 0,
+        }
+    }
+
+    /// True if the receiver has stopped hearing the transmitter
+    pub fn is_link_lost(&self) -> bool {
+        self.uplink_link_quality == 0
+    }
+}
+
+/// Battery sensor telemetry, sent from the flight controller to the receiver
+#[repr(C, packed)]
+#[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Battery {
+    /// In 0.1V
+    pub voltage: U16,
+    /// In 0.1A
+    pub current: U16,
+    /// Capacity used, in mAh, as 24 bits
+    pub capacity: [u8; 3],
+    /// Remaining, in percent
+    pub remaining: u8,
+}
+
+impl Battery {
+    pub fn new(voltage_dv: u16, current_da: u16, capacity_mah: u32, remaining_pct: u8) -> Self {
+        let capacity = capacity_mah.min(0xFF_FFFF).to_be_bytes();
+        Self {
+            voltage: U16::new(voltage_dv),
+            current: U16::new(current_da),
+            capacity: [capacity[1], capacity[2], capacity[3]],
+            remaining: remaining_pct,
+        }
+    }
+
+    pub fn capacity_mah(&self) -> u32 {
+        u32::from_be_bytes([0, self.capacity[0], self.capacity[1], self.capacity[2]])
+    }
+}
+
+/// Scales a CRSF channel onto the SRXL2 16-bit range: the same 5/8µs steps as SBUS and
+/// satellites, but centered on 992
+pub fn to_channel_value(raw: u16) -> ChannelValue {
+    let raw = 0x8000 + (raw as i32 - CHANNEL_CENTER as i32) * 32;
+    ChannelValue::from_raw(raw.clamp(0, u16::MAX as i32) as u16)
+}
+
+pub fn from_channel_value(value: ChannelValue) -> u16 {
+    let raw = CHANNEL_CENTER as i32 + (value.raw() as i32 - 0x8000) / 32;
+    raw.clamp(0, 0x7FF) as u16
+}
+
+/// A received frame with a good CRC
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Frame<'a> {
+    pub address: u8,
+    pub frame_type: u8,
+    pub payload: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    /// Checks the length and CRC of a complete frame, from address to CRC
+    pub fn try_from_slice(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.len() < HEADER_LENGTH + 2 || bytes.len() > MAX_FRAME_LENGTH || bytes[1] as usize != bytes.len() - HEADER_LENGTH {
+            return None;
+        }
+
+        let (body, crc) = bytes[HEADER_LENGTH..].split_at(bytes.len() - HEADER_LENGTH - 1);
+        if crc8(body) != crc[0] {
+            return None;
+        }
+
+        Some(Self {
+            address: bytes[0],
+            frame_type: body[0],
+            payload: &body[1..],
+        })
+    }
+
+    pub fn kind(&self) -> Option<FrameType> {
+        FrameType::try_read_from_bytes(&[self.frame_type]).ok()
+    }
+
+    /// The 16 raw 11-bit channels of an RC Channels Packed frame
+    pub fn as_channels(&self) -> Option<[u16; CHANNELS]> {
+        match (self.kind(), <&[u8; PACKED_CHANNELS_LENGTH]>::try_from(self.payload)) {
+            (Some(FrameType::RcChannelsPacked), Ok(data)) => Some(unpack_channels(data)),
+            _ => None,
+        }
+    }
+
+    pub fn as_link_statistics(&self) -> Option<&'a LinkStatistics> {
+        match self.kind() {
+            Some(FrameType::LinkStatistics) => LinkStatistics::ref_from_bytes(self.payload).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_battery(&self) -> Option<&'a Battery> {
+        match self.kind() {
+            Some(FrameType::Battery) => Battery::ref_from_bytes(self.payload).ok(),
+            _ => None,
+        }
+    }
+
+    /// The flight mode name, up to its terminating null
+    pub fn as_flight_mode(&self) -> Option<&'a str> {
+        match self.kind() {
+            Some(FrameType::FlightMode) => {
+                let len = self.payload.iter().position(|byte| *byte == 0).unwrap_or(self.payload.len());
+                core::str::from_utf8(&self.payload[..len]).ok()
+            },
+            _ => None,
+        }
+    }
+
+    /// Writes the channels of an RC Channels Packed frame into `channels`
+    pub fn update_channels(&self, channels: &mut ChannelData) -> bool {
+        match self.as_channels() {
+            None => false,
+            Some(raw) => {
+                for (index, raw) in raw.iter().enumerate() {
+                    channels.set(index, to_channel_value(*raw));
+                }
+                true
+            },
+        }
+    }
+}
+
+/// A frame built for sending
+pub struct FrameBuf {
+    buf: [u8; MAX_FRAME_LENGTH],
+    len: usize,
+}
+
+impl FrameBuf {
+    /// Frames `payload`, cutting it short if it's longer than `MAX_PAYLOAD_LENGTH`
+    pub fn new(address: Address, frame_type: FrameType, payload: &[u8]) -> Self {
+        let payload = &payload[..payload.len().min(MAX_PAYLOAD_LENGTH)];
+        let mut buf = [0; MAX_FRAME_LENGTH];
+        buf[0] = address as u8;
+        buf[1] = (payload.len() + 2) as u8;
+        buf[2] = frame_type as u8;
+        buf[3..3 + payload.len()].copy_from_slice(payload);
+        let len = 3 + payload.len();
+        buf[len] = crc8(&buf[HEADER_LENGTH..len]);
+        Self { buf, len: len + 1 }
+    }
+
+    /// Channels 0-15 of `channels`, with missing ones centered
+    pub fn channels(channels: &ChannelData) -> Self {
+        let mut raw = [CHANNEL_CENTER; CHANNELS];
+        for (index, value) in channels.iter().filter(|(index, _)| *index < CHANNELS) {
+            raw[index] = from_channel_value(value);
+        }
+        Self::new(Address::FlightController, FrameType::RcChannelsPacked, &pack_channels(&raw))
+    }
+
+    /// Battery telemetry for the receiver to send down to the transmitter
+    pub fn battery(battery: &Battery) -> Self {
+        Self::new(Address::FlightController, FrameType::Battery, battery.as_bytes())
+    }
+
+    /// Flight mode telemetry; the name is cut short to fit
+    pub fn flight_mode(mode: &str) -> Self {
+        let mut payload = [0; MAX_PAYLOAD_LENGTH];
+        let len = mode.len().min(MAX_PAYLOAD_LENGTH - 1);
+        payload[..len].copy_from_slice(&mode.as_bytes()[..len]);
+        Self::new(Address::FlightController, FrameType::FlightMode, &payload[..len + 1])
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// Splits the byte stream from a CRSF receiver into frames. Bytes before an address are
+/// skipped, and a frame with a bad length or CRC is searched for the next address byte
+/// so the parser can sync up again.
+pub struct Crsf {
+    buf: [u8; MAX_FRAME_LENGTH],
+    len: usize,
+}
+
+impl Crsf {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_FRAME_LENGTH],
+            len: 0,
+        }
+    }
+
+    /// Adds one received byte, and returns the frame once its last byte arrives and its
+    /// CRC checks out
+    pub fn push(&mut self, byte: u8) -> Option<Frame<'_>> {
+        if self.len == 0 && !is_address(byte) {
+            return None;
+        }
+
+        self.buf[self.len] = byte;
+        self.len += 1;
+        if self.len < HEADER_LENGTH {
+            return None;
+        }
+
+        let length = HEADER_LENGTH + self.buf[1] as usize;
+        if (HEADER_LENGTH + 2..=MAX_FRAME_LENGTH).contains(&length) && self.len < length {
+            return None;
+        }
+
+        let len = core::mem::take(&mut self.len);
+        if len == length && Frame::try_from_slice(&self.buf[..len]).is_some() {
+            return Frame::try_from_slice(&self.buf[..len]);
+        }
+
+        // Bad length or CRC: restart from the next address byte
+        if let Some(start) = self.buf[1..len].iter().position(|byte| is_address(*byte)) {
+            self.buf.copy_within(start + 1..len, 0);
+            self.len = len - 1 - start;
+        }
+        None
+    }
+
+    /// Drops a partly received frame, e.g. after the line has been idle in the middle of one
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+
+    /// True if part of a frame has been received
+    pub fn is_receiving(&self) -> bool {
+        self.len > 0
+    }
+}
+
+impl Default for Crsf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_address(byte: u8) -> bool {
+    Address::try_read_from_bytes(&[byte]).is_ok()
+}
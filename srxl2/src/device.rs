@@ -1,4 +1,4 @@
-use core::convert::{From, TryFrom, Into};
+use core::convert::{From, TryFrom};
 use zerocopy::{
     KnownLayout,
     Immutable,
@@ -36,6 +36,8 @@ const DEFAULT_ID_OF_TYPE: [u8; 16] = [
 
 /// Supported SRXL device types (upper nibble of device ID)
 #[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum DeviceType {
     None                = 0x0,
     RemoteReceiver      = 0x1,
@@ -52,53 +54,54 @@ pub enum DeviceType {
 }
 
 impl DeviceType {
-    pub const fn default_value(self) -> u8 {
-        DEFAULT_ID_OF_TYPE[self as usize]
+    /// The ID a master polls for this type of device
+    pub const fn default_id(self) -> DeviceId {
+        DeviceId(DEFAULT_ID_OF_TYPE[self as usize])
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::RemoteReceiver => "remote receiver",
+            Self::Receiver => "receiver",
+            Self::FlightController => "flight controller",
+            Self::ESC => "ESC",
+            Self::SRXLServo1 => "servo",
+            Self::SRXLServo2 => "servo",
+            Self::VTX => "VTX",
+            Self::ExtRF => "external RF",
+            Self::RemoteId => "remote ID",
+            Self::Sensor => "sensor",
+            Self::Broadcast => "broadcast",
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
     }
 }
 
 impl TryFrom<u8> for DeviceType {
     type Error = ();
+    /// Takes the device type from the upper nibble of a device ID
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        let nibble = value >> 4;
-        if nibble == Self::None as u8 {
-            Ok(Self::None)
-        }
-        else if nibble == Self::RemoteReceiver as u8 {
-            Ok(Self::RemoteReceiver)
-        }
-        else if nibble == Self::Receiver as u8 {
-            Ok(Self::Receiver)
-        }
-        else if nibble == Self::FlightController as u8 {
-            Ok(Self::FlightController)
-        }
-        else if nibble == Self::ESC as u8 {
-            Ok(Self::ESC)
-        }
-        else if nibble == Self::SRXLServo1 as u8 {
-            Ok(Self::SRXLServo1)
-        }
-        else if nibble == Self::SRXLServo2 as u8 {
-            Ok(Self::SRXLServo2)
-        }
-        else if nibble == Self::VTX as u8 {
-            Ok(Self::VTX)
-        }
-        else if nibble == Self::ExtRF as u8 {
-            Ok(Self::ExtRF)
-        }
-        else if nibble == Self::RemoteId as u8 {
-            Ok(Self::RemoteId)
-        }
-        else if nibble == Self::Sensor as u8 {
-            Ok(Self::Sensor)
-        }
-        else if nibble == Self::Broadcast as u8 {
-            Ok(Self::Broadcast)
-        }
-        else {
-            Err(())
+        match value >> 4 {
+            0x0 => Ok(Self::None),
+            0x1 => Ok(Self::RemoteReceiver),
+            0x2 => Ok(Self::Receiver),
+            0x3 => Ok(Self::FlightController),
+            0x4 => Ok(Self::ESC),
+            0x6 => Ok(Self::SRXLServo1),
+            0x7 => Ok(Self::SRXLServo2),
+            0x8 => Ok(Self::VTX),
+            0x9 => Ok(Self::ExtRF),
+            0xA => Ok(Self::RemoteId),
+            0xB => Ok(Self::Sensor),
+            0xF => Ok(Self::Broadcast),
+            _ => Err(()),
         }
     }
 }
@@ -106,6 +109,8 @@ impl TryFrom<u8> for DeviceType {
 /// Bit masks for Device Info byte sent via Handshake
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, IntoBytes, TryFromBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum DeviceInfo {
     /// This is the base for non-RF devices
     NoRf = 0x00,
@@ -119,15 +124,85 @@ pub enum DeviceInfo {
 
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct DeviceId(u8);
 
+impl DeviceId {
+    /// Builds the ID of a device from its type and unit number (lower nibble, 0-15)
+    pub const fn new(device_type: DeviceType, unit: u8) -> Self {
+        Self(((device_type as u8) << 4) | (unit & 0x0F))
+    }
+
+    /// Addresses every device on the bus
+    pub const fn broadcast() -> Self {
+        Self(0xFF)
+    }
+
+    /// None for the unassigned upper nibbles (5, C, D, E)
+    pub fn device_type(&self) -> Option<DeviceType> {
+        DeviceType::try_from(self.0).ok()
+    }
+
+    /// Distinguishes multiple devices of the same type
+    pub const fn unit(&self) -> u8 {
+        self.0 & 0x0F
+    }
+
+    pub const fn is_broadcast(&self) -> bool {
+        self.0 == Self::broadcast().0
+    }
+
+    /// True if this is the ID a master would poll for its device type
+    pub fn is_default_for_type(&self) -> bool {
+        match self.device_type() {
+            None => false,
+            Some(device_type) => device_type.default_id() == *self,
+        }
+    }
+}
+
+/// Prints the ID in hex, since the nibbles are the type and unit
+#[cfg(feature = "std")]
+impl core::fmt::Debug for DeviceId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "DeviceId({:#04x})", self.0)
+    }
+}
+
+/// Prints the ID in hex with its device type, e.g. `21 (receiver)`
+#[cfg(feature = "std")]
+impl core::fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.device_type() {
+            None => write!(f, "{:02X} (unknown type)", self.0),
+            Some(device_type) => write!(f, "{:02X} ({device_type})", self.0),
+        }
+    }
+}
+
+impl From<u8> for DeviceId {
+    fn from(value: u8) -> Self {
+        Self(value)
+    }
+}
+
+impl From<DeviceId> for u8 {
+    fn from(value: DeviceId) -> Self {
+        value.0
+    }
+}
+
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct FullId {
     pub device_id: DeviceId,
     pub bus_index: u8,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct DeviceEntry {
     pub device_id: DeviceId,
     /// Requested telemetry priority of this device
@@ -137,6 +212,19 @@ pub struct DeviceEntry {
     pub rfu: u8,
 }
 
+impl DeviceEntry {
+    pub fn new(device_id: DeviceId, priority: u8, info: Flags<DeviceInfo>) -> Self {
+        Self {
+            device_id,
+            priority,
+            info,
+            rfu: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct Device {
     /// Device info for this local device, shared across all buses.
     pub dev_entry: DeviceEntry,
@@ -147,4 +235,21 @@ pub struct Device {
     pub rcvr: Option<ReceiverEntry>,
     /// Set true if this device can and should respond to VTX commands
     pub vtx_proxy: bool,
-}
\ No newline at end of file
+}
+
+impl Device {
+    pub fn new() -> Self {
+        Self {
+            dev_entry: DeviceEntry::new(DeviceId::new(DeviceType::None, 0), 0, Flags::empty()),
+            uid: 0,
+            rcvr: None,
+            vtx_proxy: false,
+        }
+    }
+}
+
+impl Default for Device {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,13 +1,87 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PacketCastError {
+    /// The bytes don't start with an SRXL2 header of a known packet type
     HeaderMismatch,
+    /// The payload doesn't fit the packet type
     Cast,
+    /// The length byte is too short for a packet, too long for the buffer, or longer
+    /// than the bytes received
+    Length,
+    /// The CRC doesn't match the packet contents
+    Crc,
+}
+
+impl PacketCastError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            Self::HeaderMismatch => "not an SRXL2 packet, or an unknown packet type",
+            Self::Cast => "payload doesn't fit the packet type",
+            Self::Length => "packet length out of range",
+            Self::Crc => "CRC mismatch",
+        }
+    }
 }
 
 impl core::fmt::Display for PacketCastError {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "")
+        f.write_str(self.message())
     }
 }
 
 impl core::error::Error for PacketCastError { }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureError {
+    /// The line doesn't start with a decimal timestamp
+    Timestamp,
+    /// The bytes aren't pairs of hex digits
+    Hex,
+    /// The record has more bytes than the buffer can hold
+    TooLong,
+}
+
+impl CaptureError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            Self::Timestamp => "missing or invalid timestamp",
+            Self::Hex => "invalid hex bytes",
+            Self::TooLong => "record too long",
+        }
+    }
+}
+
+impl core::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl core::error::Error for CaptureError { }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogicError {
+    /// The level or trigger after the magic isn't one of the known values
+    Header,
+    /// A number runs past 32 bits
+    Number,
+    /// The end of the block has an unknown status
+    Status,
+}
+
+impl LogicError {
+    pub const fn message(&self) -> &'static str {
+        match self {
+            Self::Header => "invalid capture header",
+            Self::Number => "number too long",
+            Self::Status => "invalid capture status",
+        }
+    }
+}
+
+impl core::fmt::Display for LogicError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl core::error::Error for LogicError { }
@@ -1,24 +1,199 @@
-use core::marker::PhantomData;
+use core::{
+    marker::PhantomData,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign},
+};
 use zerocopy::{
     KnownLayout,
     Immutable,
     FromBytes,
     IntoBytes,
-    FromZeros,
     TryFromBytes,
 };
 
+/// A set of single-bit protocol options, stored as the byte that goes on the wire.
+/// `T` is the `repr(u8)` enum of the individual bit masks. A zero variant such as
+/// `BindOption::None` only names the empty set: `has` is always true for it, inserting
+/// it does nothing, and `iter` never yields it.
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
 pub struct Flags<T>(u8, PhantomData<T>)
     where T: Immutable + TryFromBytes + IntoBytes;
+
 impl<T> Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    pub const fn empty() -> Self {
+        Self(0, PhantomData)
+    }
+
+    /// Takes the raw byte as-is, including any bits `T` has no variant for
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits, PhantomData)
+    }
+
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
     pub fn has(&self, flag: T) -> bool {
-        let bytes = flag.as_bytes();
-        self.0 & bytes[0] == bytes[0]
+        let bits = Self::bits_of(&flag);
+        self.0 & bits == bits
     }
 
     pub fn has_all(&self, flags: Self) -> bool {
         self.0 & flags.0 == flags.0
     }
-}
\ No newline at end of file
+
+    pub fn has_any(&self, flags: Self) -> bool {
+        self.0 & flags.0 != 0
+    }
+
+    pub fn insert(&mut self, flag: T) {
+        self.0 |= Self::bits_of(&flag);
+    }
+
+    pub fn remove(&mut self, flag: T) {
+        self.0 &= !Self::bits_of(&flag);
+    }
+
+    pub fn toggle(&mut self, flag: T) {
+        self.0 ^= Self::bits_of(&flag);
+    }
+
+    pub fn set(&mut self, flag: T, state: bool) {
+        if state {
+            self.insert(flag);
+        }
+        else {
+            self.remove(flag);
+        }
+    }
+
+    /// The set flags, lowest bit first. Bits that don't match a variant of `T` are skipped.
+    pub fn iter(&self) -> Iter<T> {
+        Iter {
+            bits: self.0,
+            _flag: PhantomData,
+        }
+    }
+
+    fn bits_of(flag: &T) -> u8 {
+        flag.as_bytes()[0]
+    }
+}
+
+impl<T> Clone for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Flags<T> where T: Immutable + TryFromBytes + IntoBytes { }
+
+impl<T> PartialEq for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> Eq for Flags<T> where T: Immutable + TryFromBytes + IntoBytes { }
+
+/// Prints the raw byte, then the flags in it that `T` has a variant for
+#[cfg(feature = "std")]
+impl<T> core::fmt::Debug for Flags<T> where T: Immutable + TryFromBytes + IntoBytes + core::fmt::Debug {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#04x} ", self.bits())?;
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Default for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T> From<T> for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    fn from(flag: T) -> Self {
+        Self::from_bits(Self::bits_of(&flag))
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    fn from(flags: [T; N]) -> Self {
+        flags.into_iter().collect()
+    }
+}
+
+impl<T> FromIterator<T> for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut flags = Self::empty();
+        for flag in iter {
+            flags.insert(flag);
+        }
+        flags
+    }
+}
+
+impl<T> BitOr for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self::from_bits(self.0 | rhs.0)
+    }
+}
+
+impl<T> BitOr<T> for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    type Output = Self;
+    fn bitor(mut self, rhs: T) -> Self {
+        self.insert(rhs);
+        self
+    }
+}
+
+impl<T> BitOrAssign for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl<T> BitAnd for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    type Output = Self;
+    fn bitand(self, rhs: Self) -> Self {
+        Self::from_bits(self.0 & rhs.0)
+    }
+}
+
+impl<T> BitAndAssign for Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl<T> IntoIterator for &Flags<T> where T: Immutable + TryFromBytes + IntoBytes {
+    type Item = T;
+    type IntoIter = Iter<T>;
+    fn into_iter(self) -> Iter<T> {
+        self.iter()
+    }
+}
+
+pub struct Iter<T> {
+    bits: u8,
+    _flag: PhantomData<T>,
+}
+
+impl<T> Iterator for Iter<T> where T: Immutable + TryFromBytes + IntoBytes {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        while self.bits != 0 {
+            let lowest = self.bits & self.bits.wrapping_neg();
+            self.bits &= !lowest;
+            if let Ok(flag) = T::try_read_from_bytes(&[lowest]) {
+                return Some(flag);
+            }
+        }
+        None
+    }
+}
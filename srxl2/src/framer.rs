@@ -0,0 +1,65 @@
+use crate::{
+    packet::{FRAMING_LENGTH, SRXL_MAX_BUFFER_SIZE},
+    types::SPEKTRUM_SRXL_ID,
+};
+
+/// Splits the byte stream received from a bus into packets, using the length in each
+/// header. Bytes before a start byte (0xA6) are skipped, and a header with an
+/// impossible length is dropped so the framer can sync up again.
+pub struct Framer {
+    buf: [u8; SRXL_MAX_BUFFER_SIZE],
+    len: usize,
+}
+
+impl Framer {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; SRXL_MAX_BUFFER_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Adds one received byte. Once the last byte of a packet arrives, returns the whole
+    /// packet, which still has to be CRC-checked by the interpreter.
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        if self.len == 0 && byte != SPEKTRUM_SRXL_ID {
+            return None;
+        }
+
+        self.buf[self.len] = byte;
+        self.len += 1;
+        if self.len < FRAMING_LENGTH {
+            return None;
+        }
+
+        let length = self.buf[2] as usize;
+        if !(FRAMING_LENGTH..=SRXL_MAX_BUFFER_SIZE).contains(&length) {
+            self.len = 0;
+            None
+        }
+        else if self.len == length {
+            self.len = 0;
+            Some(&self.buf[..length])
+        }
+        else {
+            None
+        }
+    }
+
+    /// Drops a partly received packet, e.g. when the line has been idle for longer than
+    /// a byte time in the middle of one
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+
+    /// True if part of a packet has been received
+    pub fn is_receiving(&self) -> bool {
+        self.len > 0
+    }
+}
+
+impl Default for Framer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
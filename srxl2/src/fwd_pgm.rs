@@ -11,6 +11,8 @@ pub const FWD_PGM_MAX_DATA_SIZE: usize = 64;
 /// Forward Programming Data
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct FwdPgmData {
     /// Best RSSI while sending forward programming data
     pub rssi: i8,
@@ -1,27 +1,124 @@
-use zerocopy::{KnownLayout, Immutable, TryFromBytes, IntoBytes};
+use core::ops::{BitAnd, BitAndAssign};
+use zerocopy::{KnownLayout, Immutable, FromBytes, TryFromBytes, IntoBytes};
 use crate::{
     device::{DeviceInfo, DeviceId},
     flags::Flags,
 };
 
-/// Supported additional baud rates besides default 115200
-/// NOTE: Treated as bitmask, ANDed with baud rates from slaves
+/// Baud rates an SRXL2 bus can run at. The value is the rate's bit in `BaudRates`,
+/// none for the default 115200.
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum Baud {
     Baud115200 = 0,
     Baud400000 = 1,
 }
 
+impl Baud {
+    pub const fn bits_per_second(self) -> u32 {
+        match self {
+            Self::Baud115200 => 115_200,
+            Self::Baud400000 => 400_000,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for Baud {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} baud", self.bits_per_second())
+    }
+}
+
+/// The baud rates a device can run at, as the bitmask sent in its handshake. 115200 is
+/// always supported and has no bit of its own, so this isn't a `Flags<Baud>`.
+#[repr(C, packed)]
+#[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct BaudRates(u8);
+
+impl BaudRates {
+    const ALL: [Baud; 2] = [Baud::Baud115200, Baud::Baud400000];
+
+    /// Takes the raw byte as-is, including any bits for rates this crate doesn't know
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub const fn supports(&self, baud: Baud) -> bool {
+        self.0 & baud as u8 == baud as u8
+    }
+
+    /// The fastest rate in the set
+    pub fn fastest(&self) -> Baud {
+        self.iter().last().unwrap_or(Baud::Baud115200)
+    }
+
+    /// The supported rates, slowest first
+    pub fn iter(&self) -> impl Iterator<Item = Baud> {
+        let rates = *self;
+        Self::ALL.into_iter().filter(move |&baud| rates.supports(baud))
+    }
+}
+
+/// Prints the raw byte, then the rates in it
+#[cfg(feature = "std")]
+impl core::fmt::Debug for BaudRates {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#04x} ", self.bits())?;
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl From<Baud> for BaudRates {
+    fn from(baud: Baud) -> Self {
+        Self(baud as u8)
+    }
+}
+
+impl<const N: usize> From<[Baud; N]> for BaudRates {
+    fn from(rates: [Baud; N]) -> Self {
+        rates.into_iter().collect()
+    }
+}
+
+impl FromIterator<Baud> for BaudRates {
+    fn from_iter<I: IntoIterator<Item = Baud>>(iter: I) -> Self {
+        Self(iter.into_iter().fold(0, |bits, baud| bits | baud as u8))
+    }
+}
+
+/// The rates both sides support, as a master works out from every device's handshake
+impl BitAnd for BaudRates {
+    type Output = Self;
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for BaudRates {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+
 /// Handshake
 #[repr(C,packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct HandshakeData {
     pub src_dev_id: DeviceId,
     pub dest_dev_id: DeviceId,
     pub priority: u8,
     /// 0 = 115200, 1 = 400000 (See SRXL_BAUD_xxx definitions above)
-    pub baud_supported: Flags<Baud>,
+    pub baud_supported: BaudRates,
     /// See SRXL_DEVINFO_xxx definitions above for defined bits
     pub info: Flags<DeviceInfo>,
     /// Unique/random id to allow detection of two devices on bus with same deviceID
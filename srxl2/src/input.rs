@@ -0,0 +1,135 @@
+//! A view of RC channels that doesn't depend on the protocol they arrived over, so robot
+//! code can take sticks from an SRXL2 receiver, a satellite, or anything else.
+
+use crate::{
+    channel::{ChannelData, ChannelValue, Normalized},
+    crsf,
+    interpreter::Srxl2Interpreter,
+    ppm,
+    satellite::{self, Satellite},
+    sbus,
+};
+
+/// How the link to the transmitter looks from this end
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum LinkState {
+    /// Nothing received yet
+    NoSignal,
+    /// Frames are arriving and carry live stick positions
+    Connected,
+    /// Frames have stopped, or the receiver says it has lost the transmitter
+    Lost,
+}
+
+/// Channel positions from any receiver protocol. Times are milliseconds from a
+/// free-running (wrapping) clock.
+pub trait RcInput {
+    /// Number of channels, counting up from channel 0 to the highest one received
+    fn channel_count(&self) -> usize;
+
+    /// Position of the channel at `index`, or None if it hasn't been received
+    fn channel(&self, index: usize) -> Option<Normalized>;
+
+    /// Time since the last frame, or None if there hasn't been one
+    fn frame_age_ms(&self, now_ms: u32) -> Option<u32>;
+
+    fn link_state(&self, now_ms: u32) -> LinkState;
+
+    /// True if the channels shouldn't be acted on: the link is down, or the receiver is
+    /// sending its failsafe positions
+    fn is_failsafe(&self, now_ms: u32) -> bool {
+        self.link_state(now_ms) != LinkState::Connected
+    }
+}
+
+/// `RcInput` for decoders that produce `ChannelData`: the channels plus when they last
+/// changed and whether they were failsafe positions
+pub struct ChannelInput {
+    pub channels: ChannelData,
+    failsafe: bool,
+    last_frame_ms: Option<u32>,
+    timeout_ms: u32,
+}
+
+impl ChannelInput {
+    /// An input that counts the link as lost after `timeout_ms` without a frame
+    pub fn new(timeout_ms: u32) -> Self {
+        Self {
+            channels: ChannelData::new(),
+            failsafe: false,
+            last_frame_ms: None,
+            timeout_ms,
+        }
+    }
+
+    /// Records that a frame has just updated `channels`
+    pub fn frame_received(&mut self, now_ms: u32, failsafe: bool) {
+        self.failsafe = failsafe;
+        self.last_frame_ms = Some(now_ms);
+    }
+
+    /// Takes in the channel data an SRXL2 interpreter has received since the last call
+    pub fn update_from_srxl2(&mut self, srxl: &mut Srxl2Interpreter, now_ms: u32) {
+        if srxl.take_channel_update() {
+            self.channels.clone_from(&srxl.channel_data);
+            self.frame_received(now_ms, srxl.is_failsafe());
+        }
+    }
+
+    /// Takes in a frame from a satellite. Satellites have no failsafe flag of their own;
+    /// they just stop sending.
+    pub fn update_from_satellite(&mut self, satellite: &mut Satellite, frame: &satellite::Frame, now_ms: u32) {
+        if satellite.update(frame, &mut self.channels) {
+            self.frame_received(now_ms, false);
+        }
+    }
+
+    /// Takes in a frame from an SBUS receiver
+    pub fn update_from_sbus(&mut self, frame: &sbus::Frame, now_ms: u32) {
+        frame.update_channels(&mut self.channels);
+        self.frame_received(now_ms, frame.is_failsafe());
+    }
+
+    /// Takes in a PPM frame, or a round of PWM pulses. Neither signal has a failsafe flag;
+    /// receivers either stop pulsing or send their failsafe positions as channels.
+    pub fn update_from_ppm(&mut self, frame: &ppm::Frame, now_ms: u32) {
+        frame.update_channels(&mut self.channels);
+        self.frame_received(now_ms, false);
+    }
+
+    /// Takes in a frame from a CRSF receiver. Link statistics go into `channels.rssi` as
+    /// link quality in percent (SRXL2's positive RSSI), and a link quality of 0 drops the
+    /// link until channels arrive again.
+    pub fn update_from_crsf(&mut self, frame: &crsf::Frame, now_ms: u32) {
+        if frame.update_channels(&mut self.channels) {
+            self.frame_received(now_ms, false);
+        }
+        else if let Some(stats) = frame.as_link_statistics() {
+            self.channels.rssi = stats.uplink_link_quality.min(100) as i8;
+            self.failsafe = stats.is_link_lost();
+        }
+    }
+}
+
+impl RcInput for ChannelInput {
+    fn channel_count(&self) -> usize {
+        (u32::BITS - self.channels.mask.leading_zeros()) as usize
+    }
+
+    fn channel(&self, index: usize) -> Option<Normalized> {
+        self.channels.get(index).map(ChannelValue::to_normalized)
+    }
+
+    fn frame_age_ms(&self, now_ms: u32) -> Option<u32> {
+        self.last_frame_ms.map(|last_frame_ms| now_ms.wrapping_sub(last_frame_ms))
+    }
+
+    fn link_state(&self, now_ms: u32) -> LinkState {
+        match self.frame_age_ms(now_ms) {
+            None => LinkState::NoSignal,
+            Some(age_ms) if age_ms > self.timeout_ms || self.failsafe => LinkState::Lost,
+            Some(_) => LinkState::Connected,
+        }
+    }
+}
@@ -1,15 +1,104 @@
-use zerocopy::{KnownLayout, Immutable, FromBytes, IntoBytes};
+use zerocopy::{KnownLayout, Immutable, TryFromBytes, IntoBytes};
 use crate::device::DeviceId;
 
+/// How long to wait for an internal test packet to be echoed before counting it as lost
+pub const LOOPBACK_TIMEOUT_MS: u16 = 50;
+
+/// Number of devices loopback statistics can be kept for at once
+pub const MAX_LOOPBACK_DEVICES: usize = 4;
+
+#[repr(u8)]
+#[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum InternalTest {
+    /// Ask the destination device to send the packet back with the same key
+    EchoRequest = 0x01,
+    /// Answer to an echo request
+    EchoReply = 0x02,
+}
+
 #[repr(C, packed)]
-#[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct InternalData {
     pub src_dev_id: DeviceId,
     pub dest_dev_id: DeviceId,
-    pub test: u8,
+    pub test: InternalTest,
+    pub key: u32,
+}
+
+/// An echo request that has been queued and not yet answered
+pub(crate) struct PendingTest {
+    pub dest_dev_id: DeviceId,
     pub key: u32,
+    /// Milliseconds since the request was queued, or since it was sent once it has been
+    /// (advanced in run)
+    pub elapsed_ms: u16,
+    /// False until the request has actually gone out on the bus
+    pub sent: bool,
+    /// The transport's clock when the request went out, if it has one
+    pub sent_at_ms: Option<u32>,
 }
 
+/// Round trip and error statistics for internal test packets sent to one device
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct LoopbackStats {
+    pub device_id: DeviceId,
+    /// Echo requests sent to the device
+    pub sent: u16,
+    /// Echoes received with the right key
+    pub received: u16,
+    /// Echoes received with the wrong key
+    pub mismatched: u16,
+    /// Requests that weren't sent and answered within LOOPBACK_TIMEOUT_MS
+    pub lost: u16,
+    pub rtt_last_ms: u16,
+    pub rtt_min_ms: u16,
+    pub rtt_max_ms: u16,
+    rtt_total_ms: u32,
+}
+
+impl LoopbackStats {
+    pub fn new(device_id: DeviceId) -> Self {
+        Self {
+            device_id,
+            sent: 0,
+            received: 0,
+            mismatched: 0,
+            lost: 0,
+            rtt_last_ms: 0,
+            rtt_min_ms: u16::MAX,
+            rtt_max_ms: 0,
+            rtt_total_ms: 0,
+        }
+    }
+
+    /// Mean round trip time of all good echoes
+    pub fn rtt_avg_ms(&self) -> u16 {
+        self.rtt_total_ms.checked_div(self.received as u32).unwrap_or(0) as u16
+    }
+
+    /// Percentage of answered or timed out requests that were lost or came back wrong
+    pub fn error_pct(&self) -> u8 {
+        let errors = self.mismatched as u32 + self.lost as u32;
+        let total = errors + self.received as u32;
+        (errors * 100).checked_div(total).unwrap_or(0) as u8
+    }
+
+    pub(crate) fn record_echo(&mut self, rtt_ms: u16) {
+        self.received = self.received.saturating_add(1);
+        self.rtt_last_ms = rtt_ms;
+        self.rtt_min_ms = self.rtt_min_ms.min(rtt_ms);
+        self.rtt_max_ms = self.rtt_max_ms.max(rtt_ms);
+        self.rtt_total_ms += rtt_ms as u32;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum State {
     /// Default state before initialized or if bus is subsequently disabled
     Disabled,
@@ -30,4 +119,4 @@ pub enum State {
     SendSetBindInfo,
     RequestBindInfo,
     SendInternal,
-}
\ No newline at end of file
+}
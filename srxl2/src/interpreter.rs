@@ -1,12 +1,59 @@
-use core::todo;
+use zerocopy::{FromZeros, IntoBytes};
 
 use crate::{
+    bind::{BindData, BindPayload, Request as BindRequest},
     bus::{Bus, NUM_OF_BUSES},
-    device::{Device, FullId},
+    control::{self, CmdCode},
+    device::{Device, DeviceEntry, DeviceId, DeviceInfo, DeviceType, FullId},
+    flags::Flags,
     channel::ChannelData,
-    telemetry::TelemetryData,
+    handshake::{Baud, BaudRates, HandshakeData},
+    internal::{
+        InternalData,
+        InternalTest,
+        LoopbackStats,
+        PendingTest,
+        State,
+        LOOPBACK_TIMEOUT_MS,
+        MAX_LOOPBACK_DEVICES,
+    },
+    packet::{Packet, PacketType},
+    receiver::{ReceiverEntry, ReceiverInfo, ReceiverStats},
+    telemetry::{TelemetryData, TelemetryPayload},
+    transport::Transport,
+    tx::TxFlag,
     vtx::VtxData,
 };
+
+/// Time to listen for an active bus after power up before starting the handshake
+pub const STARTUP_LISTEN_MS: u16 = 50;
+
+/// Time a slave waits for the master's handshake before assuming there is none
+pub const HANDSHAKE_LISTEN_MS: u16 = 150;
+
+/// Time without a valid packet after which a running bus is considered lost
+pub const TIMEOUT_MS: u16 = 50;
+
+/// Interval between the packets the bus master sends on its own
+pub const FRAME_PERIOD_MS: u16 = 11;
+
+/// Consecutive lost frames that count as a hold, unless set_hold_threshold says otherwise
+pub const DEFAULT_HOLD_THRESHOLD: u8 = 45;
+
+/// Device types the master polls for during the handshake, in order
+const HANDSHAKE_POLL: [DeviceType; 10] = [
+    DeviceType::RemoteReceiver,
+    DeviceType::Receiver,
+    DeviceType::FlightController,
+    DeviceType::ESC,
+    DeviceType::SRXLServo1,
+    DeviceType::SRXLServo2,
+    DeviceType::VTX,
+    DeviceType::ExtRF,
+    DeviceType::RemoteId,
+    DeviceType::Sensor,
+];
+
 pub struct Srxl2Interpreter {
     pub channel_data: ChannelData,
     pub telem_data: TelemetryData,
@@ -14,80 +61,796 @@ pub struct Srxl2Interpreter {
     this_dev: Device,
     bus: [Bus; NUM_OF_BUSES],
     ch_data_is_failsafe: bool,
-    telem_phase: bool,
+    /// Set when channel data arrives, until the application takes it
+    ch_data_updated: bool,
     failsafe_ch_mask: u32,
+    /// Frame loss and hold counters of a bus master
+    rx: ReceiverInfo,
+    /// Bind info of this receiver, or the last one reported by a receiver
+    bind_info: BindData,
+    /// Bind type the master asked this receiver to enter bind mode with, until the
+    /// application takes it
+    bind_request: Option<u8>,
+    /// Results of internal test packets, by destination device
+    loopback: [Option<LoopbackStats>; MAX_LOOPBACK_DEVICES],
+    /// Last key sent in an internal test packet (xorshift state)
+    internal_key: u32,
 }
 
 impl Srxl2Interpreter {
-    pub fn init_device(_device_id: u8, _priority: u8, _info: u8, _uid: u32) -> bool {
-        todo!()
+    pub fn new() -> Self {
+        Self {
+            channel_data: ChannelData::new(),
+            telem_data: TelemetryData::new_zeroed(),
+            vtx_data: VtxData::new(),
+            this_dev: Device::new(),
+            bus: core::array::from_fn(|_| Bus::new()),
+            ch_data_is_failsafe: false,
+            ch_data_updated: false,
+            failsafe_ch_mask: 0,
+            rx: ReceiverInfo::new(DEFAULT_HOLD_THRESHOLD),
+            bind_info: BindData::new_zeroed(),
+            bind_request: None,
+            loopback: [None; MAX_LOOPBACK_DEVICES],
+            internal_key: 1,
+        }
+    }
+
+    pub fn init_device(&mut self, device_id: DeviceId, priority: u8, info: Flags<DeviceInfo>, uid: u32) -> bool {
+        if device_id.device_type().is_none() {
+            return false;
+        }
+
+        self.this_dev.dev_entry = DeviceEntry::new(device_id, priority, info);
+        self.this_dev.uid = uid;
+        // xorshift must never be seeded with 0
+        self.internal_key = uid | 1;
+        true
+    }
+
+    pub fn init_bus(&mut self, bus_index: u8, uart: u8, baud_supported: BaudRates) -> bool {
+        let device_id = self.this_dev.dev_entry.device_id;
+        let device_type = match device_id.device_type() {
+            None | Some(DeviceType::None) => return false,
+            Some(device_type) => device_type,
+        };
+        let bus = match self.bus.get_mut(bus_index as usize) {
+            None => return false,
+            Some(bus) => bus,
+        };
+
+        *bus = Bus::new();
+        bus.full_id = FullId {
+            device_id,
+            bus_index,
+        };
+        bus.uart = uart;
+        bus.baud_supported = baud_supported;
+        bus.master = device_type == DeviceType::Receiver;
+        bus.state = State::ListenOnStartup;
+        bus.initialized = true;
+        true
+    }
+
+    pub fn is_bus_master(&self, bus_index: u8) -> bool {
+        match self.bus.get(bus_index as usize) {
+            None => false,
+            Some(bus) => bus.master,
+        }
+    }
+
+    pub fn get_timeout_count_ms(&self, bus_index: u8) -> u16 {
+        match self.bus.get(bus_index as usize) {
+            None => 0,
+            Some(bus) => bus.timeout_count_ms,
+        }
+    }
+
+    pub fn get_device_id(&self, bus_index: u8) -> DeviceId {
+        match self.bus.get(bus_index as usize) {
+            None => DeviceId::new(DeviceType::None, 0),
+            Some(bus) => bus.full_id.device_id,
+        }
+    }
+
+    pub fn get_state(&self, bus_index: u8) -> State {
+        match self.bus.get(bus_index as usize) {
+            None => State::Disabled,
+            Some(bus) => bus.state,
+        }
+    }
+
+    /// Baud rate the bus is currently running at
+    pub fn get_baud_rate(&self, bus_index: u8) -> Baud {
+        match self.bus.get(bus_index as usize) {
+            None => Baud::Baud115200,
+            Some(bus) => bus.baud_rate,
+        }
+    }
+
+    /// Devices that answered the master's handshake on the given bus
+    pub fn get_devices(&self, bus_index: u8) -> &[DeviceEntry] {
+        match self.bus.get(bus_index as usize) {
+            None => &[],
+            Some(bus) => &bus.rx_dev[..bus.rx_dev_count as usize],
+        }
+    }
+
+    /// True if the last channel data received was failsafe data, or if a master is in
+    /// a hold and sending failsafe data
+    pub fn is_failsafe(&self) -> bool {
+        self.ch_data_is_failsafe
+    }
+
+    /// True once after channel data has been received into `channel_data`
+    pub fn take_channel_update(&mut self) -> bool {
+        core::mem::take(&mut self.ch_data_updated)
+    }
+
+    pub fn get_comm_stats(&self) -> &ReceiverStats {
+        &self.rx
+    }
+
+    pub fn get_bind_info(&self) -> &BindData {
+        &self.bind_info
+    }
+
+    /// If the master asked this receiver to enter bind mode, returns the bind type it
+    /// asked for. The application is expected to start binding the RF link.
+    pub fn take_bind_request(&mut self) -> Option<u8> {
+        self.bind_request.take()
+    }
+
+    /// Validates and handles one complete packet received on the given bus, sending
+    /// any immediate reply. Returns false if the packet was malformed.
+    pub fn parse_packet<T: Transport>(&mut self, bus_index: u8, packet: &[u8], transport: &mut T) -> bool {
+        let bus = match self.bus.get_mut(bus_index as usize) {
+            Some(bus) if bus.initialized => bus,
+            _ => return false,
+        };
+
+        bus.srxl_in = match Packet::try_from_slice(packet) {
+            Err(_) => return false,
+            Ok(packet) => packet,
+        };
+        bus.timeout_count_ms = 0;
+        bus.poll_answered();
+
+        let bus_index = bus_index as usize;
+        match bus.srxl_in.hdr.packet_type {
+            PacketType::Handshake => self.parse_handshake(bus_index, transport),
+            PacketType::BindInfo => self.parse_bind(bus_index, transport),
+            PacketType::ControlData => self.parse_control(bus_index, transport),
+            PacketType::TelemetryData => self.parse_telemetry(bus_index),
+            PacketType::Internal => self.parse_internal(bus_index, transport),
+            PacketType::ParamConfig | PacketType::SignalQuality => (),
+        }
+        true
     }
 
-    pub fn init_bus(_bus_index: u8, _uart: u8, _baud_supported: u8) -> bool {
-        todo!()
+    /// Advances the state machine of the given bus by `timeout_delta_ms` and sends
+    /// anything that is due
+    pub fn run<T: Transport>(&mut self, bus_index: u8, timeout_delta_ms: i16, transport: &mut T) {
+        let bus = match self.bus.get_mut(bus_index as usize) {
+            Some(bus) if bus.initialized => bus,
+            _ => return,
+        };
+
+        let delta_ms = timeout_delta_ms.max(0) as u16;
+        bus.timeout_count_ms = bus.timeout_count_ms.saturating_add(delta_ms);
+        bus.frame_ms = bus.frame_ms.saturating_add(delta_ms);
+
+        // give up on echo requests that have been out too long, or never got a turn to go
+        // out at all
+        if let Some(pending) = bus.internal.as_mut() {
+            pending.elapsed_ms = pending.elapsed_ms.saturating_add(delta_ms);
+            if pending.elapsed_ms >= LOOPBACK_TIMEOUT_MS {
+                let dest_dev_id = pending.dest_dev_id;
+                if !pending.sent {
+                    bus.tx_flags.remove(TxFlag::SendInternal);
+                }
+                bus.internal = None;
+                if let Some(stats) = self.loopback_entry(dest_dev_id) {
+                    stats.lost = stats.lost.saturating_add(1);
+                }
+            }
+        }
+
+        let bus_index = bus_index as usize;
+        let bus = &mut self.bus[bus_index];
+        match bus.state {
+            State::ListenOnStartup if bus.timeout_count_ms >= STARTUP_LISTEN_MS => {
+                bus.timeout_count_ms = 0;
+                if bus.master {
+                    self.start_handshake(bus_index, transport);
+                }
+                else {
+                    bus.state = State::ListenForHandshake;
+                }
+            },
+            State::SendHandshake if bus.frame_ms >= FRAME_PERIOD_MS => {
+                bus.frame_ms = 0;
+                self.poll_handshake(bus_index, transport);
+            },
+            State::ListenForHandshake if bus.timeout_count_ms >= HANDSHAKE_LISTEN_MS => {
+                // nobody is going to handshake, so just run at the default baud rate
+                bus.timeout_count_ms = 0;
+                bus.state = State::Running;
+            },
+            State::Running if bus.master => {
+                if bus.frame_ms < FRAME_PERIOD_MS {
+                    return;
+                }
+                bus.frame_ms = 0;
+
+                if bus.last_poll_missed() {
+                    // the device has probably lost the baud rate, so go quiet until
+                    // every device has timed out, then handshake again
+                    bus.timeout_count_ms = 0;
+                    bus.set_baud(Baud::Baud115200, transport);
+                    bus.state = State::ListenOnStartup;
+                }
+                else if !self.send_queued(bus_index, transport) {
+                    self.send_control(bus_index, transport);
+                }
+            },
+            State::Running if bus.timeout_count_ms >= TIMEOUT_MS => {
+                // lost the master: go back to the default baud rate and wait for it to
+                // handshake again
+                bus.timeout_count_ms = 0;
+                bus.set_baud(Baud::Baud115200, transport);
+                bus.state = State::ListenForHandshake;
+            },
+            // still waiting, or a state that is only passed through while sending
+            _ => (),
+        }
     }
 
-    pub fn is_bus_master(_bus_index: u8) -> bool {
-        todo!()
+    /// Asks a receiver to enter bind mode: the one this device handshook with, or every
+    /// receiver if `broadcast` is set. On a receiver, requests binding from the
+    /// application directly.
+    pub fn enter_bind(&mut self, bind_type: u8, broadcast: bool) -> bool {
+        if self.is_receiver() {
+            self.bind_request = Some(bind_type);
+            return true;
+        }
+
+        self.bind_info.bind_type = bind_type;
+        let dest = if broadcast {
+            DeviceId::broadcast()
+        }
+        else {
+            self.master_receiver_id()
+        };
+        self.queue_bind(TxFlag::EnterBind, dest)
     }
 
-    pub fn get_timeout_count_ms(_bus_index: u8) -> u16 {
-        todo!()
+    /// On a receiver, stores its new bind info and reports it to the bus. Elsewhere,
+    /// tells the receiver to use this bind info.
+    pub fn set_bind_info(&mut self, bind_type: u8, guid: u64, uid: u32) -> bool {
+        self.bind_info.bind_type = bind_type;
+        self.bind_info.guid = guid;
+        self.bind_info.uid = uid;
+
+        if self.is_receiver() {
+            self.queue_bind(TxFlag::ReportBindInfo, DeviceId::broadcast())
+        }
+        else {
+            let dest = self.master_receiver_id();
+            self.queue_bind(TxFlag::SetBindInfo, dest)
+        }
     }
 
-    pub fn get_device_id(_bus_index: u8) -> u8 {
-        todo!()
+    /// Asks `dest_dev_id` to report its bind info, which then shows up in get_bind_info
+    pub fn request_bind_info(&mut self, bus_index: u8, dest_dev_id: DeviceId) -> bool {
+        match self.bus.get_mut(bus_index as usize) {
+            Some(bus) if bus.initialized => {
+                bus.bind_dest = dest_dev_id;
+                bus.tx_flags.insert(TxFlag::GetBindInfo);
+                true
+            },
+            _ => false,
+        }
     }
 
-    pub fn parse_packet(_bus_index: u8, _packet: &[u8]) -> bool {
-        todo!()
+    /// Marks channels in `mask` of channel_data as updated, to be sent in the next frame
+    /// of every bus this device is master of. Call once per good RF frame.
+    pub fn set_outgoing_channel_mask(&mut self, mask: u32) {
+        for bus in self.bus.iter_mut().filter(|bus| bus.initialized && bus.master) {
+            bus.channel_out_mask |= mask;
+            bus.frame_err_count = 0;
+        }
+        self.failsafe_ch_mask |= mask;
+        self.ch_data_is_failsafe = false;
+        self.update_comm_stats(false);
     }
 
-    pub fn run(_bus_index: u8, _timeout_delta_ms: i16) -> () {
-        todo!()
+    /// Counts a lost RF frame. After enough of them in a row, the master sends failsafe
+    /// data until set_outgoing_channel_mask is called again.
+    pub fn on_frame_error(&mut self, bus_index: u8) {
+        if let Some(bus) = self.bus.get_mut(bus_index as usize) {
+            bus.frame_err_count = bus.frame_err_count.saturating_add(1);
+        }
+        if self.update_comm_stats(true) {
+            self.ch_data_is_failsafe = true;
+        }
     }
 
-    pub fn enter_bind(_bind_type: u8, _broadcast: bool) -> bool {
-        todo!()
+    /// The device telemetry should be addressed to: this device if it's a bus master,
+    /// otherwise the receiver that handshook with it
+    pub fn get_telemetry_endpoint(&self) -> FullId {
+        match self.bus.iter().find(|bus| bus.initialized) {
+            Some(bus) if bus.master => FullId {
+                device_id: bus.full_id.device_id,
+                bus_index: bus.full_id.bus_index,
+            },
+            Some(_) => FullId {
+                device_id: self.master_receiver_id(),
+                bus_index: self.bus.iter()
+                    .position(|bus| bus.master_rcvr.is_some())
+                    .unwrap_or(0) as u8,
+            },
+            None => FullId {
+                device_id: DeviceId::new(DeviceType::None, 0),
+                bus_index: 0,
+            },
+        }
     }
 
-    pub fn set_bind_info(_bind_type: u8, _guid: u64, _uid: u32) -> bool {
-        todo!()
+    /// Queues VTX settings to be sent to the VTX on every bus this device is master of
+    pub fn set_vtx_data(&mut self, vtx_data: &VtxData) -> bool {
+        self.vtx_data = *vtx_data;
+
+        let mut queued = false;
+        for bus in self.bus.iter_mut().filter(|bus| bus.initialized && bus.master) {
+            bus.tx_flags.insert(TxFlag::SendVtxData);
+            queued = true;
+        }
+        queued
     }
 
-    pub fn request_bind_info(_bus_index: u8, _dest_dev_id: u8) -> bool {
-        todo!()
+    /// Forward programming pass-through isn't supported yet, so this always returns false
+    pub fn pass_thru_fwd_pgm(&mut self, _data: &[u8]) -> bool {
+        false
     }
 
-    pub fn on_frame_error(_bus_index: u8) -> () {
-        todo!()
+    /// Sets how many consecutive lost frames count as a hold (0 restores the default)
+    pub fn set_hold_threshold(&mut self, countdown_reset: u8) {
+        self.rx.loss_hold_count = match countdown_reset {
+            0 => DEFAULT_HOLD_THRESHOLD,
+            count => count,
+        };
+        self.rx.loss_countdown = self.rx.loss_hold_count;
     }
 
-    pub fn get_telemetry_endpoint() -> FullId {
-        todo!()
+    pub fn clear_comm_stats(&mut self) {
+        self.rx.frame_losses = 0;
+        self.rx.holds = 0;
+        self.rx.loss_countdown = self.rx.loss_hold_count;
     }
 
-    pub fn set_vtx_data(_vtx_data: &VtxData) -> bool {
-        todo!()
+    /// Counts a good frame or a lost one (`is_fade`). Returns true while in a hold.
+    pub fn update_comm_stats(&mut self, is_fade: bool) -> bool {
+        let rx = &mut self.rx;
+        if !is_fade {
+            rx.loss_countdown = rx.loss_hold_count;
+            return false;
+        }
+
+        if rx.loss_countdown > 0 {
+            rx.loss_countdown -= 1;
+            rx.frame_losses = rx.frame_losses.saturating_add(1);
+            if rx.loss_countdown == 0 {
+                // the losses that made up the hold are counted as the hold instead
+                rx.holds = rx.holds.saturating_add(1);
+                rx.frame_losses = rx.frame_losses.saturating_sub(rx.loss_hold_count as u16);
+            }
+        }
+        rx.loss_countdown == 0
+    }
+
+    /// Queues an internal test packet to `dest_dev_id`, to be sent on the next run of
+    /// the bus. The destination echoes it back, and the result is recorded in its
+    /// loopback stats. A request that isn't sent and answered within
+    /// `LOOPBACK_TIMEOUT_MS` is counted as lost. Returns false if a previous test on this
+    /// bus is still pending or there is no room to track another device.
+    pub fn send_internal_data(&mut self, bus_index: u8, dest_dev_id: DeviceId) -> bool {
+        match self.bus.get(bus_index as usize) {
+            Some(bus) if bus.initialized && bus.internal.is_none() => (),
+            _ => return false,
+        }
+        if self.loopback_entry(dest_dev_id).is_none() {
+            return false;
+        }
+
+        // xorshift32
+        self.internal_key ^= self.internal_key << 13;
+        self.internal_key ^= self.internal_key >> 17;
+        self.internal_key ^= self.internal_key << 5;
+
+        let bus = &mut self.bus[bus_index as usize];
+        bus.internal = Some(PendingTest {
+            dest_dev_id,
+            key: self.internal_key,
+            elapsed_ms: 0,
+            sent: false,
+            sent_at_ms: None,
+        });
+        bus.tx_flags.insert(TxFlag::SendInternal);
+        true
     }
 
-    pub fn pass_thru_fwd_pgm(_data: &[u8]) -> bool {
-        todo!()
+    /// Round trip statistics of the internal test packets sent to a device so far
+    pub fn loopback_stats(&self, dev_id: DeviceId) -> Option<&LoopbackStats> {
+        self.loopback.iter().flatten().find(|stats| stats.device_id == dev_id)
     }
 
-    pub fn set_hold_threshold(_countdown_reset: u8) -> () {
-        todo!()
+    pub fn clear_loopback_stats(&mut self) {
+        self.loopback = [None; MAX_LOOPBACK_DEVICES];
     }
 
-    pub fn clear_comm_stats() -> () {
-        todo!()
+    fn is_receiver(&self) -> bool {
+        self.this_dev.dev_entry.device_id.device_type() == Some(DeviceType::Receiver)
     }
 
-    pub fn update_comm_stats(_is_fade: bool) -> bool {
-        todo!()
+    /// ID of the receiver that handshook with this device, or the default receiver ID
+    /// if there hasn't been a handshake yet
+    fn master_receiver_id(&self) -> DeviceId {
+        match self.bus.iter().find_map(|bus| bus.master_rcvr.as_ref()) {
+            Some(rcvr) => rcvr.device_id,
+            None => DeviceType::Receiver.default_id(),
+        }
     }
 
-    pub fn send_internal_data(_bus_index: u8, _dest_dev_id: u8, _cmd: u8) -> () {
-        todo!()
+    /// Queues a bind packet to `dest` on every bus
+    fn queue_bind(&mut self, flag: TxFlag, dest: DeviceId) -> bool {
+        let mut queued = false;
+        for bus in self.bus.iter_mut().filter(|bus| bus.initialized) {
+            bus.bind_dest = dest;
+            bus.tx_flags.insert(flag);
+            queued = true;
+        }
+        queued
     }
-}
\ No newline at end of file
+
+    /// Forgets the devices found so far and starts polling for them again
+    fn start_handshake<T: Transport>(&mut self, bus_index: usize, transport: &mut T) {
+        let bus = &mut self.bus[bus_index];
+        bus.clear_devices();
+        bus.baud_negotiated = bus.baud_supported;
+        bus.poll_index = 0;
+        bus.frame_ms = 0;
+        bus.set_baud(Baud::Baud115200, transport);
+        bus.state = State::SendHandshake;
+    }
+
+    /// Polls the next device type for a handshake, or once all have been polled, tells
+    /// everyone the baud rate to use and starts running
+    fn poll_handshake<T: Transport>(&mut self, bus_index: usize, transport: &mut T) {
+        let own_id = self.bus[bus_index].full_id.device_id;
+        let next = HANDSHAKE_POLL[self.bus[bus_index].poll_index..].iter()
+            .map(|device_type| device_type.default_id())
+            .position(|device_id| device_id != own_id);
+
+        let bus = &mut self.bus[bus_index];
+        match next {
+            Some(offset) => {
+                let dest = HANDSHAKE_POLL[bus.poll_index + offset].default_id();
+                bus.poll_index += offset + 1;
+                bus.request_id = dest;
+                let baud_supported = bus.baud_supported;
+                self.send_handshake(bus_index, dest, baud_supported, transport);
+            },
+            None => {
+                let baud_negotiated = bus.baud_negotiated;
+                self.send_handshake(bus_index, DeviceId::broadcast(), baud_negotiated, transport);
+
+                let bus = &mut self.bus[bus_index];
+                bus.set_baud(baud_negotiated.fastest(), transport);
+                bus.timeout_count_ms = 0;
+                bus.state = State::Running;
+            },
+        }
+    }
+
+    fn send_handshake<T: Transport>(&mut self, bus_index: usize, dest: DeviceId, baud_supported: BaudRates, transport: &mut T) {
+        let bus = &mut self.bus[bus_index];
+        let dev_entry = &self.this_dev.dev_entry;
+        let handshake = HandshakeData {
+            src_dev_id: bus.full_id.device_id,
+            dest_dev_id: dest,
+            priority: dev_entry.priority,
+            baud_supported,
+            info: dev_entry.info,
+            uid: self.this_dev.uid,
+        };
+        bus.send(Packet::new(PacketType::Handshake, handshake.as_bytes()), transport);
+    }
+
+    /// Sends the highest priority packet off the transmit queue, if any
+    fn send_queued<T: Transport>(&mut self, bus_index: usize, transport: &mut T) -> bool {
+        let bus = &mut self.bus[bus_index];
+        let flag = match bus.tx_flags.iter().next() {
+            None => return false,
+            Some(flag) => flag,
+        };
+
+        bus.state = match flag {
+            TxFlag::EnterBind => State::SendEnterBind,
+            TxFlag::GetBindInfo => State::RequestBindInfo,
+            TxFlag::SetBindInfo | TxFlag::BroadcastBindInfo => State::SendSetBindInfo,
+            TxFlag::ReportBindInfo => State::SendBoundDataReport,
+            TxFlag::SendVtxData => State::SendVtx,
+            TxFlag::SendInternal => State::SendInternal,
+            // forward programming isn't supported yet, so there's nothing to send
+            TxFlag::SendFwdPgmData => State::Running,
+        };
+        bus.tx_flags.remove(flag);
+
+        let bind_dest = bus.bind_dest;
+        let own_id = bus.full_id.device_id;
+        match bus.state {
+            State::SendEnterBind => self.send_bind(bus_index, BindRequest::Enter, bind_dest, transport),
+            State::RequestBindInfo => self.send_bind(bus_index, BindRequest::Status, bind_dest, transport),
+            State::SendSetBindInfo => self.send_bind(bus_index, BindRequest::SetBind, bind_dest, transport),
+            State::SendBoundDataReport => self.send_bind(bus_index, BindRequest::BoundData, own_id, transport),
+            State::SendVtx => {
+                let vtx = control::packet(CmdCode::Vtx, DeviceId::new(DeviceType::None, 0), self.vtx_data.as_bytes());
+                self.bus[bus_index].send(vtx, transport);
+            },
+            State::SendInternal => self.send_internal(bus_index, transport),
+            _ => {
+                self.bus[bus_index].state = State::Running;
+                return false;
+            },
+        }
+
+        self.bus[bus_index].state = State::Running;
+        true
+    }
+
+    fn send_bind<T: Transport>(&mut self, bus_index: usize, request: BindRequest, device_id: DeviceId, transport: &mut T) {
+        let bind = BindPayload {
+            request,
+            device_id,
+            data: self.bind_info,
+        };
+        self.bus[bus_index].send(Packet::new(PacketType::BindInfo, bind.as_bytes()), transport);
+    }
+
+    /// Sends the master's channel data for this frame, polling one device for a reply
+    fn send_control<T: Transport>(&mut self, bus_index: usize, transport: &mut T) {
+        let bus = &mut self.bus[bus_index];
+        let reply_id = bus.next_reply_id();
+        bus.request_id = reply_id;
+        bus.reply_pending = reply_id != DeviceId::new(DeviceType::None, 0);
+
+        let (cmd, mask) = if self.ch_data_is_failsafe {
+            (CmdCode::ChannelFailsafe, self.failsafe_ch_mask)
+        }
+        else {
+            (CmdCode::Channel, bus.channel_out_mask)
+        };
+        bus.channel_out_mask = 0;
+
+        self.channel_data.frame_losses = self.rx.frame_losses;
+        let (channels, len) = self.channel_data.packed(mask);
+        bus.send(control::packet(cmd, reply_id, &channels.as_bytes()[..len]), transport);
+    }
+
+    /// Answers the master's poll with the current telemetry
+    fn send_telemetry<T: Transport>(&mut self, bus_index: usize, transport: &mut T) {
+        let telemetry = TelemetryPayload {
+            dest_dev: self.master_receiver_id(),
+            payload: self.telem_data,
+        };
+        self.bus[bus_index].send(Packet::new(PacketType::TelemetryData, telemetry.as_bytes()), transport);
+    }
+
+    fn parse_handshake<T: Transport>(&mut self, bus_index: usize, transport: &mut T) {
+        let bus = &mut self.bus[bus_index];
+        let (src_dev_id, dest_dev_id, priority, baud_supported, info) = match bus.srxl_in.as_handshake_ref() {
+            None => return,
+            Some(packet) => (
+                packet.handshake.src_dev_id,
+                packet.handshake.dest_dev_id,
+                packet.handshake.priority,
+                packet.handshake.baud_supported,
+                packet.handshake.info,
+            ),
+        };
+
+        if bus.master {
+            // a device answering our poll
+            if dest_dev_id == bus.full_id.device_id {
+                bus.add_device(src_dev_id, priority, info);
+                bus.baud_negotiated &= baud_supported;
+            }
+            return;
+        }
+
+        if src_dev_id.device_type() == Some(DeviceType::Receiver) {
+            bus.master_rcvr = Some(ReceiverEntry::new(src_dev_id, info));
+        }
+
+        if dest_dev_id == bus.full_id.device_id {
+            let own_baud = bus.baud_supported;
+            self.send_handshake(bus_index, src_dev_id, own_baud, transport);
+        }
+        else if dest_dev_id.is_broadcast() {
+            // the master is done polling and tells everyone the baud rate to use
+            let baud = baud_supported & bus.baud_supported;
+            bus.set_baud(baud.fastest(), transport);
+            bus.state = State::Running;
+        }
+    }
+
+    fn parse_control<T: Transport>(&mut self, bus_index: usize, transport: &mut T) {
+        let bus = &mut self.bus[bus_index];
+        if bus.master {
+            return;
+        }
+        // the master is already running, so the handshake has been missed
+        if matches!(bus.state, State::ListenOnStartup | State::ListenForHandshake) {
+            bus.state = State::Running;
+        }
+
+        let bus = &self.bus[bus_index];
+        let packet = match bus.srxl_in.as_control_ref() {
+            None => return,
+            Some(packet) => packet,
+        };
+        let cmd = packet.control.cmd;
+        let reply_id = packet.control.reply_id;
+
+        match cmd {
+            CmdCode::Channel | CmdCode::ChannelFailsafe => {
+                if let Some(channel) = packet.as_channel_ref() {
+                    self.channel_data.update_from_packed(channel.control.data);
+                    self.ch_data_is_failsafe = cmd == CmdCode::ChannelFailsafe;
+                    self.ch_data_updated = true;
+                }
+            },
+            CmdCode::Vtx => {
+                if let Some(vtx) = packet.as_vtx_ref() {
+                    self.vtx_data = *vtx.control.data;
+                }
+            },
+            CmdCode::FwdPgm => (),
+        }
+
+        if reply_id == bus.full_id.device_id && !self.send_queued(bus_index, transport) {
+            self.send_telemetry(bus_index, transport);
+        }
+    }
+
+    fn parse_telemetry(&mut self, bus_index: usize) {
+        let bus = &self.bus[bus_index];
+        match bus.srxl_in.as_telemetry_ref() {
+            Some(packet) if packet.telemetry.dest_dev == bus.full_id.device_id || packet.telemetry.dest_dev.is_broadcast() => {
+                self.telem_data = packet.telemetry.payload;
+            },
+            _ => (),
+        }
+    }
+
+    fn parse_bind<T: Transport>(&mut self, bus_index: usize, transport: &mut T) {
+        let bus = &self.bus[bus_index];
+        let (request, device_id, data) = match bus.srxl_in.as_bind_ref() {
+            None => return,
+            Some(packet) => (packet.bind.request, packet.bind.device_id, packet.bind.data),
+        };
+        let addressed = device_id == bus.full_id.device_id || device_id.is_broadcast();
+
+        match request {
+            BindRequest::Enter if addressed && self.is_receiver() => {
+                self.bind_request = Some(data.bind_type);
+            },
+            BindRequest::Status if addressed && self.is_receiver() => {
+                // the requester is waiting for the answer right away
+                let own_id = bus.full_id.device_id;
+                self.send_bind(bus_index, BindRequest::BoundData, own_id, transport);
+            },
+            BindRequest::SetBind if addressed && self.is_receiver() => {
+                self.bind_info = data;
+                self.queue_bind(TxFlag::ReportBindInfo, DeviceId::broadcast());
+            },
+            BindRequest::BoundData if !self.is_receiver() => {
+                self.bind_info = data;
+            },
+            _ => (),
+        }
+    }
+
+    /// Stats entry for the given device, claiming a free one if it has none yet
+    fn loopback_entry(&mut self, dev_id: DeviceId) -> Option<&mut LoopbackStats> {
+        let index = match self.loopback.iter().position(|entry| match entry {
+            Some(stats) => stats.device_id == dev_id,
+            None => false,
+        }) {
+            Some(index) => index,
+            None => {
+                let index = self.loopback.iter().position(|entry| entry.is_none())?;
+                self.loopback[index] = Some(LoopbackStats::new(dev_id));
+                index
+            },
+        };
+        self.loopback[index].as_mut()
+    }
+
+    fn send_internal<T: Transport>(&mut self, bus_index: usize, transport: &mut T) {
+        let bus = &mut self.bus[bus_index];
+        let pending = match bus.internal.as_mut() {
+            Some(pending) if !pending.sent => pending,
+            _ => return,
+        };
+
+        let data = InternalData {
+            src_dev_id: bus.full_id.device_id,
+            dest_dev_id: pending.dest_dev_id,
+            test: InternalTest::EchoRequest,
+            key: pending.key,
+        };
+        pending.sent = true;
+        pending.sent_at_ms = transport.now_ms();
+        pending.elapsed_ms = 0;
+        let dest_dev_id = pending.dest_dev_id;
+
+        bus.send(Packet::new(PacketType::Internal, data.as_bytes()), transport);
+
+        if let Some(stats) = self.loopback_entry(dest_dev_id) {
+            stats.sent = stats.sent.saturating_add(1);
+        }
+    }
+
+    fn parse_internal<T: Transport>(&mut self, bus_index: usize, transport: &mut T) {
+        let bus = &mut self.bus[bus_index];
+        let (src_dev_id, is_request, key) = match bus.srxl_in.as_internal_ref() {
+            Some(packet) if packet.internal.dest_dev_id == bus.full_id.device_id => (
+                packet.internal.src_dev_id,
+                packet.internal.test == InternalTest::EchoRequest,
+                packet.internal.key,
+            ),
+            _ => return,
+        };
+
+        if is_request {
+            let reply = InternalData {
+                src_dev_id: bus.full_id.device_id,
+                dest_dev_id: src_dev_id,
+                test: InternalTest::EchoReply,
+                key,
+            };
+            bus.send(Packet::new(PacketType::Internal, reply.as_bytes()), transport);
+            return;
+        }
+
+        let pending = match bus.internal.take() {
+            Some(pending) if pending.sent && pending.dest_dev_id == src_dev_id => pending,
+            // not the echo we're waiting for
+            other => {
+                bus.internal = other;
+                return;
+            },
+        };
+
+        let rtt_ms = match (pending.sent_at_ms, transport.now_ms()) {
+            (Some(sent_at_ms), Some(now_ms)) => now_ms.wrapping_sub(sent_at_ms).min(u16::MAX as u32) as u16,
+            _ => pending.elapsed_ms,
+        };
+        if let Some(stats) = self.loopback_entry(src_dev_id) {
+            if key == pending.key {
+                stats.record_echo(rtt_ms);
+            }
+            else {
+                stats.mismatched = stats.mismatched.saturating_add(1);
+            }
+        }
+    }
+}
+
+impl Default for Srxl2Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,25 +1,43 @@
 #![no_std]
 
-mod control;
-mod channel;
-mod bind;
-mod device;
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod control;
+pub mod channel;
+pub mod bind;
+pub mod device;
 mod types;
 mod interpreter;
-mod packet;
+pub mod packet;
 mod receiver;
-mod flags;
-mod handshake;
-mod internal;
-mod param;
-mod rssi;
-mod telemetry;
-mod vtx;
+pub mod flags;
+pub mod handshake;
+pub mod internal;
+pub mod param;
+pub mod rssi;
+pub mod telemetry;
+pub mod vtx;
 mod fwd_pgm;
-mod error;
+pub mod error;
 mod crc;
-mod tx;
+pub mod tx;
 mod bus;
+mod transport;
+pub mod framer;
+pub mod capture;
+pub mod satellite;
+pub mod sbus;
+pub mod crsf;
+pub mod ppm;
+pub mod uart;
+pub mod logic;
+pub mod input;
+#[cfg(feature = "std")]
+pub mod sim;
+#[cfg(feature = "ufmt")]
+mod udebug;
 
 pub use types::*;
 pub use interpreter::*;
+pub use transport::*;
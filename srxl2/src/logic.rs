@@ -0,0 +1,259 @@
+//! Binary format for a logic capture of one line: the level it starts at, then how long
+//! it holds each level before the next edge, so the waveform comes back exactly. Times
+//! are in ticks of a 16MHz timer, 62.5ns each. A capture is sent as one block:
+//!
+//! - `MAGIC`
+//! - the level at the start, 0 or 1
+//! - the trigger that started it: 0 right away, 1 at a falling edge, or 2 at an edge
+//!   after an idle gap, followed by the gap in ticks
+//! - the run lengths, in ticks, from the start to the first edge and between each edge
+//!   and the next, none of them 0
+//! - a 0, then why the capture ended: 0 the line went idle, 1 the buffer filled, or 2
+//!   edges came too close together to catch, and the rest would be wrong
+//!
+//! Numbers after the magic are unsigned LEB128: 7 bits to a byte, least significant
+//! first, with the top bit set on all but the last byte. Most runs fit in one or two.
+
+use crate::error::LogicError;
+
+/// Start of every capture block
+pub const MAGIC: [u8; 4] = *b"LCAP";
+/// Timer ticks in a microsecond
+pub const TICKS_PER_US: u32 = 16;
+/// Longest a number takes in LEB128
+pub const MAX_VARINT_BYTES: usize = 5;
+
+/// Writes `value` in LEB128 to the start of `out`. Returns the bytes written, or None if
+/// `out` is too short.
+pub fn encode_varint(value: u32, out: &mut [u8]) -> Option<usize> {
+    let mut value = value;
+    let mut len = 0;
+    loop {
+        let byte = out.get_mut(len)?;
+        len += 1;
+        if value < 0x80 {
+            *byte = value as u8;
+            return Some(len);
+        }
+        *byte = value as u8 | 0x80;
+        value >>= 7;
+    }
+}
+
+/// What starts a capture
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Trigger {
+    /// As soon as it's armed
+    Immediate,
+    /// The first falling edge: a start bit, on an idle-high UART line
+    FallingEdge,
+    /// The first edge after the line has been still for this many ticks, e.g. the start
+    /// of the first packet after a gap
+    IdleGap(u32),
+}
+
+/// The start of a capture block
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Header {
+    /// The level from the start to the first edge
+    pub level: bool,
+    pub trigger: Trigger,
+}
+
+impl Header {
+    /// Longest a header takes
+    pub const MAX_BYTES: usize = MAGIC.len() + 2 + MAX_VARINT_BYTES;
+
+    /// Returns the bytes and how many of them there are
+    pub fn encode(&self) -> ([u8; Self::MAX_BYTES], usize) {
+        let mut out = [0; Self::MAX_BYTES];
+        out[..MAGIC.len()].copy_from_slice(&MAGIC);
+        let mut len = MAGIC.len();
+        out[len] = self.level as u8;
+        len += 1;
+        out[len] = match self.trigger {
+            Trigger::Immediate => 0,
+            Trigger::FallingEdge => 1,
+            Trigger::IdleGap(_) => 2,
+        };
+        len += 1;
+        if let Trigger::IdleGap(ticks) = self.trigger {
+            // Always fits: MAX_BYTES leaves room
+            len += encode_varint(ticks, &mut out[len..]).unwrap_or(0);
+        }
+        (out, len)
+    }
+}
+
+/// Why a capture ended
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Status {
+    /// The line went still
+    Idle,
+    /// There was no room for more edges
+    Full,
+    /// An edge came before the one before it had been timed, so it was missed
+    EdgesLost,
+}
+
+impl Status {
+    /// The end of a block: the 0 that marks it, and the status
+    pub const fn encode(self) -> [u8; 2] {
+        let code = match self {
+            Self::Idle => 0,
+            Self::Full => 1,
+            Self::EdgesLost => 2,
+        };
+        [0, code]
+    }
+
+    const fn from_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(Self::Idle),
+            1 => Some(Self::Full),
+            2 => Some(Self::EdgesLost),
+            _ => None,
+        }
+    }
+}
+
+/// A piece of a capture block
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Event {
+    Start(Header),
+    /// Ticks the line held its level before the next edge
+    Run(u32),
+    End(Status),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    Magic(usize),
+    Level,
+    Trigger,
+    Gap,
+    Run,
+    Status,
+}
+
+/// Picks capture blocks out of a stream of bytes, one byte at a time. Anything outside a
+/// block is skipped, so a block can be found in the middle of other output.
+pub struct Decoder {
+    expect: Expect,
+    level: bool,
+    value: u32,
+    shift: u32,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            expect: Expect::Magic(0),
+            level: false,
+            value: 0,
+            shift: 0,
+        }
+    }
+
+    /// True between the start and end of a block
+    pub const fn in_block(&self) -> bool {
+        !matches!(self.expect, Expect::Magic(_))
+    }
+
+    /// Takes the next byte. A block cut short by something that doesn't fit the format is
+    /// an error, after which the decoder looks for the next block.
+    pub fn push(&mut self, byte: u8) -> Result<Option<Event>, LogicError> {
+        match self.expect {
+            Expect::Magic(index) => {
+                self.expect = if byte == MAGIC[index] {
+                    Expect::Magic(index + 1)
+                }
+                else if byte == MAGIC[0] {
+                    Expect::Magic(1)
+                }
+                else {
+                    Expect::Magic(0)
+                };
+                if self.expect == Expect::Magic(MAGIC.len()) {
+                    self.expect = Expect::Level;
+                }
+                Ok(None)
+            },
+            Expect::Level => {
+                if byte > 1 {
+                    return self.fail(LogicError::Header);
+                }
+                self.level = byte == 1;
+                self.expect = Expect::Trigger;
+                Ok(None)
+            },
+            Expect::Trigger => {
+                let trigger = match byte {
+                    0 => Trigger::Immediate,
+                    1 => Trigger::FallingEdge,
+                    2 => {
+                        self.expect = Expect::Gap;
+                        return Ok(None);
+                    },
+                    _ => return self.fail(LogicError::Header),
+                };
+                self.expect = Expect::Run;
+                Ok(Some(Event::Start(Header { level: self.level, trigger })))
+            },
+            Expect::Gap => {
+                let Some(ticks) = self.varint(byte)? else { return Ok(None) };
+                self.expect = Expect::Run;
+                Ok(Some(Event::Start(Header { level: self.level, trigger: Trigger::IdleGap(ticks) })))
+            },
+            Expect::Run => {
+                let Some(ticks) = self.varint(byte)? else { return Ok(None) };
+                if ticks == 0 {
+                    self.expect = Expect::Status;
+                    return Ok(None);
+                }
+                Ok(Some(Event::Run(ticks)))
+            },
+            Expect::Status => {
+                self.expect = Expect::Magic(0);
+                match Status::from_code(byte as u32) {
+                    Some(status) => Ok(Some(Event::End(status))),
+                    None => Err(LogicError::Status),
+                }
+            },
+        }
+    }
+
+    /// Takes a byte of a number, returning the number after its last byte
+    fn varint(&mut self, byte: u8) -> Result<Option<u32>, LogicError> {
+        let bits = (byte & 0x7F) as u32;
+        if self.shift >= 32 || (self.shift == 28 && bits > 0x0F) {
+            return self.fail(LogicError::Number);
+        }
+
+        self.value |= bits << self.shift;
+        if byte & 0x80 != 0 {
+            self.shift += 7;
+            return Ok(None);
+        }
+
+        let value = self.value;
+        self.value = 0;
+        self.shift = 0;
+        Ok(Some(value))
+    }
+
+    fn fail<T>(&mut self, error: LogicError) -> Result<T, LogicError> {
+        *self = Self::new();
+        Err(error)
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -4,12 +4,12 @@ use zerocopy::{
     FromBytes,
     TryFromBytes,
     IntoBytes,
-    CastError,
-    TryCastError,
-    ConvertError,
-    ValidityError,
 };
 use crate::{
+    crc::crc16,
+    error::PacketCastError,
+    internal::InternalData,
+    types::SPEKTRUM_SRXL_ID,
     handshake::HandshakeData,
     bind::BindPayload,
     param::ParamPayload,
@@ -20,9 +20,14 @@ use crate::{
 
 pub const SRXL_MAX_BUFFER_SIZE: usize = 80;
 
+/// Bytes in every packet that aren't payload: the header and the trailing CRC
+pub const FRAMING_LENGTH: usize = size_of::<Header>() + size_of::<u16>();
+
 /// Spektrum SRXL header
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct Header {
     /// Always 0xKA6 for SRXL2
     pub srxl_id: u8,
@@ -32,7 +37,8 @@ pub struct Header {
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum PacketType {
     /// Handshake packet
     Handshake = 0x21,
@@ -46,49 +52,197 @@ pub enum PacketType {
     TelemetryData = 0x80,
     /// Control data packet
     ControlData = 0xCD,
+    /// Internal test packet, only understood by devices running this implementation
+    Internal = 0x99,
 }
 
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone)]
 pub struct Packet {
     pub hdr: Header,
     pub raw: [u8; SRXL_MAX_BUFFER_SIZE - size_of::<Header>()],
 }
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct BindPacket<'a> {
     pub hdr: &'a Header,
     pub bind: &'a BindPayload,
 }
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct HandshakePacket<'a> {
     pub hdr: &'a Header,
     pub handshake: &'a HandshakeData,
 }
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ParamPacket<'a> {
     pub hdr: &'a Header,
     pub param: &'a ParamPayload,
 }
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct RssiPacket<'a> {
     pub hdr: &'a Header,
     pub rssi: &'a RssiPayload,
 }
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct TelemetryPacket<'a> {
     pub hdr: &'a Header,
     pub telemetry: &'a TelemetryPayload,
 }
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct InternalPacket<'a> {
+    pub hdr: &'a Header,
+    pub internal: &'a InternalData,
+}
+
+impl PacketType {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Handshake => "handshake",
+            Self::BindInfo => "bind info",
+            Self::ParamConfig => "parameter config",
+            Self::SignalQuality => "signal quality",
+            Self::TelemetryData => "telemetry",
+            Self::ControlData => "control data",
+            Self::Internal => "internal test",
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for PacketType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Only the bytes that go on the wire are compared
+impl PartialEq for Packet {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for Packet { }
+
+#[cfg(feature = "std")]
+impl core::fmt::Debug for Packet {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Packet")
+            .field("hdr", &self.hdr)
+            .field("payload", &self.payload())
+            .field("crc", &format_args!("{:#06x}", self.crc()))
+            .finish()
+    }
+}
+
 impl Packet {
-    pub fn as_bind_ref(&self) -> Option<BindPacket> {
+    /// Wraps `payload` in a packet of the given type, filling in the length and CRC
+    pub fn new(packet_type: PacketType, payload: &[u8]) -> Self {
+        let length = FRAMING_LENGTH + payload.len();
+        assert!(length <= SRXL_MAX_BUFFER_SIZE, "SRXL payload too large");
+
+        let mut packet = Self {
+            hdr: Header {
+                srxl_id: SPEKTRUM_SRXL_ID,
+                packet_type,
+                length: length as u8,
+            },
+            raw: [0; SRXL_MAX_BUFFER_SIZE - size_of::<Header>()],
+        };
+        packet.raw[..payload.len()].copy_from_slice(payload);
+
+        let crc = crc16(&packet.as_bytes()[..length - size_of::<u16>()]);
+        packet.raw[payload.len()..payload.len() + size_of::<u16>()].copy_from_slice(&crc.to_be_bytes());
+        packet
+    }
+
+    /// Copies a received packet out of `bytes`, validating the header, length, and CRC
+    pub fn try_from_slice(bytes: &[u8]) -> Result<Self, PacketCastError> {
+        let packet = Self::try_from_slice_unverified(bytes)?;
+        if packet.is_crc_valid() {
+            Ok(packet)
+        }
+        else {
+            Err(PacketCastError::Crc)
+        }
+    }
+
+    /// Like try_from_slice, but keeps a packet whose CRC doesn't match, e.g. to show
+    /// what was received anyway
+    pub fn try_from_slice_unverified(bytes: &[u8]) -> Result<Self, PacketCastError> {
+        if bytes.len() < FRAMING_LENGTH || bytes[0] != SPEKTRUM_SRXL_ID {
+            return Err(PacketCastError::HeaderMismatch);
+        }
+
+        let length = bytes[2] as usize;
+        if !(FRAMING_LENGTH..=SRXL_MAX_BUFFER_SIZE).contains(&length) || length > bytes.len() {
+            return Err(PacketCastError::Length);
+        }
+
+        let mut buffer = [0u8; SRXL_MAX_BUFFER_SIZE];
+        buffer[..length].copy_from_slice(&bytes[..length]);
+        match Self::try_read_from_bytes(buffer.as_slice()) {
+            // the header holds the only invalid bit patterns, so this is an unknown packet type
+            Err(_) => Err(PacketCastError::HeaderMismatch),
+            Ok(packet) => Ok(packet),
+        }
+    }
+
+    /// Total length of the packet on the wire, including header and CRC
+    pub fn len(&self) -> usize {
+        self.hdr.length as usize
+    }
+
+    /// True if the packet carries no payload, just the header and CRC
+    pub fn is_empty(&self) -> bool {
+        self.len() <= FRAMING_LENGTH
+    }
+
+    /// The bytes to put on the wire
+    pub fn as_slice(&self) -> &[u8] {
+        &self.as_bytes()[..self.len()]
+    }
+
+    /// The bytes between the header and the CRC
+    pub fn payload(&self) -> &[u8] {
+        &self.raw[..self.len() - FRAMING_LENGTH]
+    }
+
+    /// The CRC at the end of the packet
+    pub fn crc(&self) -> u16 {
+        let crc = &self.as_slice()[self.len() - size_of::<u16>()..];
+        u16::from_be_bytes([crc[0], crc[1]])
+    }
+
+    /// The CRC the packet should have, given its contents
+    pub fn expected_crc(&self) -> u16 {
+        crc16(&self.as_slice()[..self.len() - size_of::<u16>()])
+    }
+
+    pub fn is_crc_valid(&self) -> bool {
+        self.crc() == self.expected_crc()
+    }
+
+    pub fn as_bind_ref(&self) -> Option<BindPacket<'_>> {
         if self.hdr.packet_type != PacketType::BindInfo {
             None
         }
         else {
-            match BindPayload::try_ref_from_bytes(self.raw.as_slice()) {
+            match BindPayload::try_ref_from_prefix(self.raw.as_slice()) {
                 Err(_) => None,
-                Ok(bind) => Some(BindPacket {
+                Ok((bind, _)) => Some(BindPacket {
                     hdr: &self.hdr,
                     bind,
                 }),
@@ -96,14 +250,14 @@ impl Packet {
         }
     }
 
-    pub fn as_handshake_ref(&self) -> Option<HandshakePacket> {
+    pub fn as_handshake_ref(&self) -> Option<HandshakePacket<'_>> {
         if self.hdr.packet_type != PacketType::Handshake {
             None
         }
         else {
-            match HandshakeData::try_ref_from_bytes(self.raw.as_slice()) {
+            match HandshakeData::try_ref_from_prefix(self.raw.as_slice()) {
                 Err(_) => None,
-                Ok(handshake) => Some(HandshakePacket {
+                Ok((handshake, _)) => Some(HandshakePacket {
                     hdr: &self.hdr,
                     handshake,
                 }),
@@ -111,14 +265,14 @@ impl Packet {
         }
     }
 
-    pub fn as_param_ref(&self) -> Option<ParamPacket> {
+    pub fn as_param_ref(&self) -> Option<ParamPacket<'_>> {
         if self.hdr.packet_type != PacketType::ParamConfig {
             None
         }
         else {
-            match ParamPayload::try_ref_from_bytes(self.raw.as_slice()) {
+            match ParamPayload::try_ref_from_prefix(self.raw.as_slice()) {
                 Err(_) => None,
-                Ok(param) => Some(ParamPacket {
+                Ok((param, _)) => Some(ParamPacket {
                     hdr: &self.hdr,
                     param,
                 }),
@@ -126,14 +280,14 @@ impl Packet {
         }
     }
 
-    pub fn as_rssi_ref(&self) -> Option<RssiPacket> {
+    pub fn as_rssi_ref(&self) -> Option<RssiPacket<'_>> {
         if self.hdr.packet_type != PacketType::SignalQuality {
             None
         }
         else {
-            match RssiPayload::try_ref_from_bytes(self.raw.as_slice()) {
+            match RssiPayload::try_ref_from_prefix(self.raw.as_slice()) {
                 Err(_) => None,
-                Ok(rssi) => Some(RssiPacket {
+                Ok((rssi, _)) => Some(RssiPacket {
                     hdr: &self.hdr,
                     rssi,
                 }),
@@ -141,14 +295,14 @@ impl Packet {
         }
     }
 
-    pub fn as_telemetry_ref(&self) -> Option<TelemetryPacket> {
+    pub fn as_telemetry_ref(&self) -> Option<TelemetryPacket<'_>> {
         if self.hdr.packet_type != PacketType::TelemetryData {
             None
         }
         else {
-            match TelemetryPayload::ref_from_bytes(self.raw.as_slice()) {
+            match TelemetryPayload::ref_from_prefix(self.raw.as_slice()) {
                 Err(_) => None,
-                Ok(telemetry) => Some(TelemetryPacket {
+                Ok((telemetry, _)) => Some(TelemetryPacket {
                     hdr: &self.hdr,
                     telemetry,
                 }),
@@ -156,18 +310,33 @@ impl Packet {
         }
     }
 
-    pub fn as_control_ref(&self) -> Option<ControlPacket> {
+    pub fn as_control_ref(&self) -> Option<ControlPacket<'_>> {
         if self.hdr.packet_type != PacketType::ControlData {
             None
         }
         else {
-            match ControlData::try_ref_from_bytes(self.raw.as_slice()) {
+            match ControlData::try_ref_from_prefix(self.raw.as_slice()) {
                 Err(_) => None,
-                Ok(control) => Some(ControlPacket {
+                Ok((control, _)) => Some(ControlPacket {
                     hdr: &self.hdr,
                     control,
                 }),
             }
         }
     }
+
+    pub fn as_internal_ref(&self) -> Option<InternalPacket<'_>> {
+        if self.hdr.packet_type != PacketType::Internal {
+            None
+        }
+        else {
+            match InternalData::try_ref_from_prefix(self.raw.as_slice()) {
+                Err(_) => None,
+                Ok((internal, _)) => Some(InternalPacket {
+                    hdr: &self.hdr,
+                    internal,
+                }),
+            }
+        }
+    }
 }
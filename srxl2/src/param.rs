@@ -5,12 +5,13 @@ use zerocopy::{
     IntoBytes,
 };
 use crate::{
-    packet::Header,
     device::DeviceId,
 };
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum Request {
     Query = 0x50,
     Write = 0x57,
@@ -19,10 +20,11 @@ pub enum Request {
 /// Parameter Config
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ParamPayload {
     pub request: Request,
     pub dest_dev_id: DeviceId,
     pub param_id: u32,
     pub param_val: u32,
-    pub crc: u16,
 }
@@ -0,0 +1,361 @@
+//! PPM sum and PWM servo signals from older receivers, decoded from edge timestamps.
+//! A PPM sum signal carries every channel on one wire as the time between successive
+//! pulses, with a longer gap between frames; PWM puts each channel on its own wire as
+//! the width of a high pulse. Widths are in microseconds and land in `ChannelData`
+//! through `ChannelValue::from_us`.
+
+use crate::channel::{ChannelData, ChannelValue};
+
+/// Most channels a frame can hold
+pub const MAX_CHANNELS: usize = 12;
+/// Fewest channels in a PPM frame; anything shorter is noise
+pub const MIN_CHANNELS: usize = 4;
+/// Shortest time between pulses that counts as the gap between frames
+pub const SYNC_GAP_US: u32 = 2_700;
+/// Channel widths outside this are glitches
+pub const CHANNEL_MIN_US: u32 = 750;
+pub const CHANNEL_MAX_US: u32 = 2_250;
+/// How long the last positions are held after frames stop, before the signal counts as
+/// lost
+pub const HOLD_US: u32 = 100_000;
+
+/// Frame length most transmitters send 8 channels in
+pub const FRAME_US: u32 = 22_500;
+/// Width of the pulse in front of each channel
+pub const PULSE_US: u16 = 300;
+/// Shortest gap sent between frames, well over `SYNC_GAP_US`
+pub const MIN_GAP_US: u32 = 4_000;
+/// Time a test pattern channel takes to sweep from one end to the other and back
+pub const SWEEP_MS: u32 = 2_000;
+
+/// Which way the pulses go from the idle level
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Polarity {
+    /// Idle low, pulsing high
+    Positive,
+    /// Idle high, pulsing low
+    Negative,
+}
+
+/// Channel widths from one PPM frame or one round of PWM pulses
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Frame {
+    widths: [u16; MAX_CHANNELS],
+    len: u8,
+}
+
+impl Frame {
+    pub const fn new() -> Self {
+        Self {
+            widths: [0; MAX_CHANNELS],
+            len: 0,
+        }
+    }
+
+    /// A frame of the first `MAX_CHANNELS` of `widths`, in microseconds
+    pub fn from_widths(widths: &[u16]) -> Self {
+        let mut frame = Self::new();
+        for width in widths.iter().take(MAX_CHANNELS) {
+            frame.push(*width);
+        }
+        frame
+    }
+
+    /// Widths of the channels in the frame, in microseconds
+    pub fn widths(&self) -> &[u16] {
+        &self.widths[..self.len as usize]
+    }
+
+    /// Adds a channel to the end, returning false if the frame is full
+    pub fn push(&mut self, width_us: u16) -> bool {
+        if self.len as usize >= MAX_CHANNELS {
+            return false;
+        }
+
+        self.widths[self.len as usize] = width_us;
+        self.len += 1;
+        true
+    }
+
+    /// The first `count` channels of `channels`, with missing ones centered
+    pub fn from_channels(channels: &ChannelData, count: usize) -> Self {
+        let mut frame = Self::new();
+        for index in 0..count.min(MAX_CHANNELS) {
+            frame.push(channels.get(index).unwrap_or_default().to_us());
+        }
+        frame
+    }
+
+    /// `count` channels sweeping back and forth over the full range, each a little behind
+    /// the one before, at `time_ms` into the pattern
+    pub fn test_pattern(count: usize, time_ms: u32) -> Self {
+        let span = (ChannelValue::PULSE_MAX_US - ChannelValue::PULSE_MIN_US) as u32;
+        let mut frame = Self::new();
+        for index in 0..count.min(MAX_CHANNELS) {
+            let phase = time_ms.wrapping_add(index as u32 * SWEEP_MS / 8) % SWEEP_MS;
+            let ramp = if phase < SWEEP_MS / 2 { phase } else { SWEEP_MS - phase };
+            frame.push(ChannelValue::PULSE_MIN_US + (ramp * span / (SWEEP_MS / 2)) as u16);
+        }
+        frame
+    }
+
+    /// Writes the channels into `channels`, starting from channel 0
+    pub fn update_channels(&self, channels: &mut ChannelData) {
+        for (index, width) in self.widths().iter().enumerate() {
+            channels.set(index, ChannelValue::from_us(*width));
+        }
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const fn is_channel_width(width_us: u32) -> bool {
+    width_us >= CHANNEL_MIN_US && width_us <= CHANNEL_MAX_US
+}
+
+const fn is_signal_lost(last_frame_us: Option<u32>, now_us: u32) -> bool {
+    match last_frame_us {
+        None => true,
+        Some(last_frame_us) => now_us.wrapping_sub(last_frame_us) > HOLD_US,
+    }
+}
+
+/// Turns the leading edges of the pulses in a PPM sum signal into frames. Only one edge
+/// of each pulse is needed, so the polarity doesn't matter here. A frame is only
+/// passed on once the gap after it has been seen, and only if it has as many channels
+/// as the one before: a missed edge can merge two channels into something that looks
+/// like a gap. A glitch throws away the rest of the frame it lands in.
+pub struct PpmDecoder {
+    last_edge_us: Option<u32>,
+    frame: Frame,
+    synced: bool,
+    /// Channels in the last frame received whole
+    channels: u8,
+    last_frame_us: Option<u32>,
+}
+
+impl PpmDecoder {
+    pub const fn new() -> Self {
+        Self {
+            last_edge_us: None,
+            frame: Frame::new(),
+            synced: false,
+            channels: 0,
+            last_frame_us: None,
+        }
+    }
+
+    /// Adds the leading edge of a pulse, timestamped in microseconds by a free-running
+    /// (wrapping) clock. Returns the frame the edge completes, if any.
+    pub fn push(&mut self, edge_us: u32) -> Option<Frame> {
+        let last_edge_us = self.last_edge_us.replace(edge_us)?;
+        let width_us = edge_us.wrapping_sub(last_edge_us);
+        if width_us >= SYNC_GAP_US {
+            let frame = core::mem::take(&mut self.frame);
+            let synced = core::mem::replace(&mut self.synced, true);
+            if !synced {
+                return None;
+            }
+
+            let channels = core::mem::replace(&mut self.channels, frame.len);
+            if frame.len != channels || (frame.len as usize) < MIN_CHANNELS {
+                return None;
+            }
+
+            self.last_frame_us = Some(edge_us);
+            return Some(frame);
+        }
+
+        if self.synced && !(is_channel_width(width_us) && self.frame.push(width_us as u16)) {
+            // Wait for the next gap rather than pass on shifted channels
+            self.synced = false;
+        }
+        None
+    }
+
+    /// Forgets the last edge and any partly received frame, e.g. after edges were missed
+    pub fn reset(&mut self) {
+        self.last_edge_us = None;
+        self.frame = Frame::new();
+        self.synced = false;
+        self.channels = 0;
+    }
+
+    /// True if no frame has arrived within `HOLD_US`, or ever
+    pub fn is_signal_lost(&self, now_us: u32) -> bool {
+        is_signal_lost(self.last_frame_us, now_us)
+    }
+}
+
+impl Default for PpmDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Measures the high pulses on `N` PWM channels, and makes a frame of them each time
+/// every channel has sent a new one
+pub struct PwmDecoder<const N: usize> {
+    rise_us: [Option<u32>; N],
+    frame: Frame,
+    /// A bit for each channel measured since the last frame
+    fresh: u32,
+    last_frame_us: Option<u32>,
+}
+
+impl<const N: usize> PwmDecoder<N> {
+    const ALL_FRESH: u32 = (1 << N) - 1;
+
+    pub const fn new() -> Self {
+        assert!(N > 0 && N <= MAX_CHANNELS);
+        Self {
+            rise_us: [None; N],
+            frame: Frame {
+                widths: [0; MAX_CHANNELS],
+                len: N as u8,
+            },
+            fresh: 0,
+            last_frame_us: None,
+        }
+    }
+
+    /// Adds an edge on `channel`: `high` for the start of a pulse and not for its end,
+    /// timestamped in microseconds by a free-running (wrapping) clock. Returns the new
+    /// frame once every channel has been measured again.
+    pub fn edge(&mut self, channel: usize, high: bool, time_us: u32) -> Option<Frame> {
+        if channel >= N {
+            return None;
+        }
+        if high {
+            self.rise_us[channel] = Some(time_us);
+            return None;
+        }
+
+        let rise_us = self.rise_us[channel].take()?;
+        let width_us = time_us.wrapping_sub(rise_us);
+        if !is_channel_width(width_us) {
+            return None;
+        }
+
+        self.frame.widths[channel] = width_us as u16;
+        self.fresh |= 1 << channel;
+        if self.fresh != Self::ALL_FRESH {
+            return None;
+        }
+
+        self.fresh = 0;
+        self.last_frame_us = Some(time_us);
+        Some(self.frame)
+    }
+
+    /// Forgets the pulses in progress and the channels measured towards the next frame,
+    /// e.g. after edges were missed
+    pub fn reset(&mut self) {
+        self.rise_us = [None; N];
+        self.fresh = 0;
+    }
+
+    /// True if some channel hasn't pulsed within `HOLD_US`, or no frame has been made yet
+    pub fn is_signal_lost(&self, now_us: u32) -> bool {
+        is_signal_lost(self.last_frame_us, now_us)
+    }
+}
+
+impl<const N: usize> Default for PwmDecoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How a PPM signal is sent
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Timing {
+    /// Time from the start of one frame to the start of the next. Frames with too many
+    /// channels to fit, with `MIN_GAP_US` after them, are stretched.
+    pub frame_us: u32,
+    /// Width of the pulse in front of each channel; the channel's width counts it in
+    pub pulse_us: u16,
+    pub polarity: Polarity,
+}
+
+impl Timing {
+    /// 22.5ms frames of 300µs positive pulses
+    pub const fn new() -> Self {
+        Self {
+            frame_us: FRAME_US,
+            pulse_us: PULSE_US,
+            polarity: Polarity::Positive,
+        }
+    }
+
+    /// The line levels that send `frame`, from the first channel's pulse to the end of
+    /// the gap after the last one. Widths are clamped to the range a decoder takes.
+    pub fn steps(&self, frame: &Frame) -> Steps {
+        Steps {
+            timing: *self,
+            frame: *frame,
+            step: 0,
+        }
+    }
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A line level and how long to hold it
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Step {
+    pub high: bool,
+    pub duration_us: u32,
+}
+
+/// The steps of one frame: a pulse and the rest of the channel for each channel, then a
+/// pulse and the gap
+pub struct Steps {
+    timing: Timing,
+    frame: Frame,
+    step: u8,
+}
+
+impl Steps {
+    fn width_us(&self, channel: usize) -> u32 {
+        match self.frame.widths().get(channel) {
+            Some(width) => (*width as u32).clamp(CHANNEL_MIN_US, CHANNEL_MAX_US),
+            None => {
+                let channels_us: u32 = (0..self.frame.len as usize).map(|channel| self.width_us(channel)).sum();
+                self.timing.frame_us.saturating_sub(channels_us).max(MIN_GAP_US)
+            },
+        }
+    }
+}
+
+impl Iterator for Steps {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        let channel = self.step as usize / 2;
+        if channel > self.frame.len as usize {
+            return None;
+        }
+
+        let pulse = self.step.is_multiple_of(2);
+        let pulse_us = self.timing.pulse_us as u32;
+        let duration_us = if pulse { pulse_us } else { self.width_us(channel).saturating_sub(pulse_us) };
+        self.step += 1;
+        Some(Step {
+            high: pulse == (self.timing.polarity == Polarity::Positive),
+            duration_us,
+        })
+    }
+}
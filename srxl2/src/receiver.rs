@@ -5,13 +5,15 @@ use zerocopy::{
     IntoBytes,
 };
 use crate::{
-    device::{DeviceInfo, DeviceId}, bus::NUM_OF_BUSES
+    device::{DeviceInfo, DeviceId, DeviceType}, bus::NUM_OF_BUSES, flags::Flags,
 };
 
 const MAX_RCVRS: usize = 2 * NUM_OF_BUSES;
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum Received {
     None = 0,
     Dbm = 1,
@@ -19,13 +21,15 @@ pub enum Received {
     Both = 3,
 }
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct ReceiverEntry {
     /// SRXL device ID of the receiver
     pub device_id: DeviceId,
     /// Supports 8 buses, with each bit corresponding to busIndex (bit 0 = bus 0, bit 7 = bus 7)
     pub bus_bits: u8,
     /// Info bits reported during handshake - See SRXL_DEVINFO_XXX mask bits in header
-    pub info: DeviceInfo,
+    pub info: Flags<DeviceInfo>,
     /// 0 = none, 1 = dBm, 2 = percent, 3 = both dBm and percent
     pub rssi_received: Received,
     /// Latest RSSI dBm value reported by receiver (negative, varies with receiver type)
@@ -38,6 +42,21 @@ pub struct ReceiverEntry {
     pub channel_mask: u32,
 }
 
+impl ReceiverEntry {
+    pub fn new(device_id: DeviceId, info: Flags<DeviceInfo>) -> Self {
+        Self {
+            device_id,
+            bus_bits: 0,
+            info,
+            rssi_received: Received::None,
+            rssi_dbm: 0,
+            rssi_pct: 0,
+            fades: 0,
+            channel_mask: 0,
+        }
+    }
+}
+
 pub struct ReceiverInfo {
     /// Stats for each receiver, filled when ch data is received
     pub rcvr_entry: [ReceiverEntry; MAX_RCVRS],
@@ -65,6 +84,24 @@ pub struct ReceiverInfo {
 }
 
 impl<'a> ReceiverInfo {
+    pub fn new(loss_hold_count: u8) -> Self {
+        Self {
+            rcvr_entry: core::array::from_fn(|_| ReceiverEntry::new(DeviceId::new(DeviceType::None, 0), Flags::empty())),
+            rcvr_sorted_idx: core::array::from_fn(|i| i),
+            rcvr_sort_insert: 0,
+            rcvr_count: 0,
+            rx_bus_bits: 0,
+            best_rssi_dbm: 0,
+            best_rssi_pct: 0,
+            loss_countdown: loss_hold_count,
+            loss_hold_count,
+            frame_losses: 0,
+            holds: 0,
+            telem_rcvr_idx: 0,
+            bind_rcvr_idx: 0,
+        }
+    }
+
     /// Pointers to receiver entries sorted in telemetry range order
     pub fn rcvr_sorted(&'a self) -> [&'a ReceiverEntry; MAX_RCVRS] {
         self.rcvr_sorted_idx.map(|i| &self.rcvr_entry[i])
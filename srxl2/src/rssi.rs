@@ -7,6 +7,8 @@ use zerocopy::{
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum Request {
     Request = 0x52,
     Send = 0x53,
@@ -15,11 +17,12 @@ pub enum Request {
 /// Signal Quality
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct RssiPayload {
     pub request: Request,
     pub antenna_a: i8,
     pub antenna_b: i8,
     pub antenna_c: i8,
     pub antenna_d: i8,
-    pub crc: u16,
 }
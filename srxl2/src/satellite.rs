@@ -0,0 +1,306 @@
+//! Decoder for Spektrum DSM2/DSMX remote receivers ("satellites"), which send a 16-byte
+//! frame of servo positions every 11 or 22ms at 125000 baud, 8N1. Positions come out as
+//! `ChannelData`, the same as channel data received over SRXL2.
+
+use zerocopy::TryFromBytes;
+use crate::{
+    bind::BindStatus,
+    channel::{ChannelData, ChannelValue},
+};
+
+pub const FRAME_LENGTH: usize = 16;
+pub const BAUD: u32 = 125_000;
+/// Number of servo words in a frame
+pub const SERVOS_PER_FRAME: usize = 7;
+/// A servo slot the remote has nothing to send in
+pub const EMPTY_SERVO: u16 = 0xFFFF;
+/// Bytes within a frame follow each other back to back, and frames are at least 9ms
+/// apart, so a longer silence than this starts a new frame
+pub const FRAME_GAP_US: u32 = 5_000;
+/// How long the last positions are held after frames stop, before failsafe
+pub const HOLD_US: u32 = 1_000_000;
+
+/// How long a freshly powered remote is left to start up before the bind pulses, well
+/// inside the 200ms it listens for them
+pub const BIND_DELAY_US: u32 = 72_000;
+/// Length of each low pulse, and of the high time after it. The spec only gives the
+/// count; this is what other flight controllers use.
+pub const BIND_PULSE_US: u32 = 120;
+
+/// Frames looked at before settling on a resolution
+const DETECT_FRAMES: u8 = 4;
+/// Channel IDs defined by the spec: throttle, aileron, elevator, rudder, gear, aux 1-7
+const DETECT_CHANNELS: u8 = 12;
+
+/// Width of the position field in each servo word
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Resolution {
+    /// 10-bit positions with a 6-bit channel ID; only used by DSM2 at 22ms
+    Bits10,
+    /// 11-bit positions with a 4-bit channel ID and a phase bit on top
+    Bits11,
+}
+
+impl Resolution {
+    /// Splits a servo word into its channel ID and position
+    pub const fn decode(self, servo: u16) -> (u8, u16) {
+        match self {
+            Self::Bits10 => (((servo & 0xFC00) >> 10) as u8, servo & 0x03FF),
+            Self::Bits11 => (((servo & 0x7800) >> 11) as u8, servo & 0x07FF),
+        }
+    }
+
+    /// Scales a position onto the SRXL2 16-bit range. The full position range is ±150%
+    /// travel on both the satellite and SRXL2, so this is only a shift.
+    pub const fn to_channel_value(self, position: u16) -> ChannelValue {
+        match self {
+            Self::Bits10 => ChannelValue::from_raw(position << 6),
+            Self::Bits11 => ChannelValue::from_raw(position << 5),
+        }
+    }
+}
+
+/// How a remote was bound, which decides the number of bind pulses and the frame header
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Remote {
+    /// Bound on its own and connected straight to the flight controller; sends a system
+    /// byte in each frame
+    Internal,
+    /// Bound through a main receiver; sends a 16-bit fade count
+    External,
+}
+
+/// One complete frame, as received
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Frame(pub [u8; FRAME_LENGTH]);
+
+impl Frame {
+    /// The protocol in the second byte, which only a remote bound as an internal receiver
+    /// sends. An external remote has the low byte of its fade count there instead.
+    pub fn system(&self) -> Option<BindStatus> {
+        match BindStatus::try_read_from_bytes(&self.0[1..2]) {
+            Ok(status @ (BindStatus::Dsm2_1024_22ms | BindStatus::Dsm2_2048_11ms | BindStatus::Dsmx22Ms | BindStatus::Dsmx11Ms)) => Some(status),
+            _ => None,
+        }
+    }
+
+    /// Frames the remote has missed: one byte when it sends a system byte, two otherwise.
+    /// Updated a frame late by the remote.
+    pub fn fades(&self) -> u16 {
+        match self.system() {
+            Some(_) => self.0[0] as u16,
+            None => u16::from_be_bytes([self.0[0], self.0[1]]),
+        }
+    }
+
+    /// The raw servo word in `slot`, 0 to 6
+    pub fn servo(&self, slot: usize) -> u16 {
+        let offset = 2 + slot * 2;
+        u16::from_be_bytes([self.0[offset], self.0[offset + 1]])
+    }
+
+    /// Every servo word that isn't an empty slot
+    pub fn servos(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..SERVOS_PER_FRAME).map(|slot| self.servo(slot)).filter(|servo| *servo != EMPTY_SERVO)
+    }
+
+    /// Bit mask of the channel IDs in this frame at `resolution`, or None if the frame
+    /// can't be at that resolution: an ID outside the spec or the same ID twice
+    fn channel_ids(&self, resolution: Resolution) -> Option<u16> {
+        let mut ids = 0u16;
+        for servo in self.servos() {
+            let (id, _) = resolution.decode(servo);
+            if id >= DETECT_CHANNELS || ids & (1 << id) != 0 {
+                return None;
+            }
+            ids |= 1 << id;
+        }
+        Some(ids)
+    }
+}
+
+/// Tries both resolutions against the first few frames. Real channel IDs are unique in
+/// each frame and, over a few frames, cover channel 0 up without gaps; read at the wrong
+/// width, they repeat, run past the spec, or leave holes.
+struct Detector {
+    frames: u8,
+    ids_10: Option<u16>,
+    ids_11: Option<u16>,
+}
+
+impl Detector {
+    const fn new() -> Self {
+        Self {
+            frames: 0,
+            ids_10: Some(0),
+            ids_11: Some(0),
+        }
+    }
+
+    fn push(&mut self, frame: &Frame) -> Option<Resolution> {
+        self.ids_10 = self.ids_10.zip(frame.channel_ids(Resolution::Bits10)).map(|(all, ids)| all | ids);
+        self.ids_11 = self.ids_11.zip(frame.channel_ids(Resolution::Bits11)).map(|(all, ids)| all | ids);
+        self.frames += 1;
+        if self.frames < DETECT_FRAMES {
+            return None;
+        }
+
+        let contiguous = |ids: Option<u16>| matches!(ids, Some(ids) if ids != 0 && ids & (ids + 1) == 0);
+        let detected = match (contiguous(self.ids_10), contiguous(self.ids_11)) {
+            (true, false) => Some(Resolution::Bits10),
+            (false, true) => Some(Resolution::Bits11),
+            _ => None,
+        };
+        *self = Self::new();
+        detected
+    }
+}
+
+/// Turns the byte stream from a satellite into channel positions. Frames are found by
+/// the silence between them, and the resolution is worked out from the channel IDs
+/// unless it's given up front.
+pub struct Satellite {
+    buf: [u8; FRAME_LENGTH],
+    len: usize,
+    last_byte_us: u32,
+    last_frame_us: Option<u32>,
+    resolution: Option<Resolution>,
+    detector: Detector,
+}
+
+impl Satellite {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; FRAME_LENGTH],
+            len: 0,
+            last_byte_us: 0,
+            last_frame_us: None,
+            resolution: None,
+            detector: Detector::new(),
+        }
+    }
+
+    /// A decoder for a remote whose resolution is already known, e.g. from how it was bound
+    pub const fn with_resolution(resolution: Resolution) -> Self {
+        let mut satellite = Self::new();
+        satellite.resolution = Some(resolution);
+        satellite
+    }
+
+    /// Adds one received byte, timestamped in microseconds by a free-running (wrapping)
+    /// clock. Returns the frame once its last byte arrives.
+    pub fn push(&mut self, byte: u8, now_us: u32) -> Option<Frame> {
+        if self.len > 0 && now_us.wrapping_sub(self.last_byte_us) > FRAME_GAP_US {
+            // Started mid-frame or lost a byte: this one begins the next frame
+            self.len = 0;
+        }
+        self.last_byte_us = now_us;
+
+        self.buf[self.len] = byte;
+        self.len += 1;
+        if self.len < FRAME_LENGTH {
+            return None;
+        }
+
+        self.len = 0;
+        self.last_frame_us = Some(now_us);
+        Some(Frame(self.buf))
+    }
+
+    /// Writes the positions in `frame` into `channels`, by channel ID, and its fade count
+    /// into `frame_losses`. Returns false, leaving `channels` alone, while the resolution
+    /// is still being worked out.
+    pub fn update(&mut self, frame: &Frame, channels: &mut ChannelData) -> bool {
+        if self.resolution.is_none() {
+            self.resolution = self.detector.push(frame);
+        }
+        let resolution = match self.resolution {
+            None => return false,
+            Some(resolution) => resolution,
+        };
+
+        for servo in frame.servos() {
+            let (id, position) = resolution.decode(servo);
+            channels.set(id as usize, resolution.to_channel_value(position));
+        }
+        channels.frame_losses = frame.fades();
+        true
+    }
+
+    /// The resolution in use, once known
+    pub fn resolution(&self) -> Option<Resolution> {
+        self.resolution
+    }
+
+    /// True if no frame has arrived in the last second, or ever, so the outputs should go
+    /// to failsafe
+    pub fn is_failsafe(&self, now_us: u32) -> bool {
+        match self.last_frame_us {
+            None => true,
+            Some(last_frame_us) => now_us.wrapping_sub(last_frame_us) > HOLD_US,
+        }
+    }
+}
+
+/// Number of falling pulses that put a freshly powered remote into bind mode for
+/// `system`, or None for protocols a remote can't be bound to
+pub const fn bind_pulse_count(system: BindStatus, remote: Remote) -> Option<u8> {
+    let internal = match system {
+        BindStatus::Dsm2_1024_22ms => 3,
+        BindStatus::Dsm2_2048_11ms => 5,
+        BindStatus::Dsmx22Ms => 7,
+        BindStatus::Dsmx11Ms => 9,
+        _ => return None,
+    };
+    match remote {
+        Remote::Internal => Some(internal),
+        Remote::External => Some(internal + 1),
+    }
+}
+
+/// One stretch of the bind pulse train: the data line held `high` or low for `duration_us`
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BindStep {
+    pub high: bool,
+    pub duration_us: u32,
+}
+
+/// The levels to drive on a remote's data line, starting the moment it's powered up:
+/// high while it starts, then the bind pulses, each a low followed by a high
+pub struct BindPulses {
+    pulses: u8,
+    step: u8,
+}
+
+impl BindPulses {
+    pub const fn new(system: BindStatus, remote: Remote) -> Option<Self> {
+        match bind_pulse_count(system, remote) {
+            None => None,
+            Some(pulses) => Some(Self { pulses, step: 0 }),
+        }
+    }
+}
+
+impl Iterator for BindPulses {
+    type Item = BindStep;
+
+    fn next(&mut self) -> Option<BindStep> {
+        let step = match self.step {
+            0 => BindStep { high: true, duration_us: BIND_DELAY_US },
+            step if step <= self.pulses * 2 => BindStep { high: step % 2 == 0, duration_us: BIND_PULSE_US },
+            _ => return None,
+        };
+        self.step += 1;
+        Some(step)
+    }
+}
+
+impl Default for Satellite {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,231 @@
+//! Futaba SBUS: a 25-byte frame of 16 11-bit channels, two digital channels, and
+//! frame-lost and failsafe flags, sent every 7 or 14ms at 100000 baud, 8E2, with the
+//! line inverted. Channels come out as `ChannelData`, the same as SRXL2.
+
+use zerocopy::{
+    KnownLayout,
+    Immutable,
+    TryFromBytes,
+    IntoBytes,
+};
+use crate::{
+    channel::{ChannelData, ChannelValue},
+    flags::Flags,
+};
+
+pub const FRAME_LENGTH: usize = 25;
+pub const BAUD: u32 = 100_000;
+pub const HEADER: u8 = 0x0F;
+pub const FOOTER: u8 = 0x00;
+/// Proportional channels in a frame
+pub const CHANNELS: usize = 16;
+/// Index in `ChannelData` of the first digital channel; the second follows it
+pub const DIGITAL_CHANNEL: usize = CHANNELS;
+
+const CHANNEL_BITS: u32 = 11;
+const CHANNEL_MASK: u16 = (1 << CHANNEL_BITS) - 1;
+
+/// Bytes taken by 16 packed 11-bit channels, as in SBUS and CRSF
+pub(crate) const PACKED_CHANNELS_LENGTH: usize = CHANNELS * CHANNEL_BITS as usize / 8;
+
+/// Unpacks 16 11-bit channels, packed least significant bit first
+pub(crate) fn unpack_channels(data: &[u8; PACKED_CHANNELS_LENGTH]) -> [u16; CHANNELS] {
+    let mut channels = [0; CHANNELS];
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut data = data.iter();
+    for channel in &mut channels {
+        while count < CHANNEL_BITS {
+            // 22 bytes hold exactly 16 channels, so this never runs out
+            bits |= (*data.next().unwrap_or(&0) as u32) << count;
+            count += 8;
+        }
+        *channel = bits as u16 & CHANNEL_MASK;
+        bits >>= CHANNEL_BITS;
+        count -= CHANNEL_BITS;
+    }
+    channels
+}
+
+pub(crate) fn pack_channels(channels: &[u16; CHANNELS]) -> [u8; PACKED_CHANNELS_LENGTH] {
+    let mut data = [0; PACKED_CHANNELS_LENGTH];
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut offset = 0;
+    for channel in channels {
+        bits |= ((channel & CHANNEL_MASK) as u32) << count;
+        count += CHANNEL_BITS;
+        while count >= 8 {
+            data[offset] = bits as u8;
+            offset += 1;
+            bits >>= 8;
+            count -= 8;
+        }
+    }
+    data
+}
+
+/// Bits of the flags byte
+#[repr(u8)]
+#[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum SbusFlag {
+    /// Digital channel 17
+    Channel17 = 0x01,
+    /// Digital channel 18
+    Channel18 = 0x02,
+    /// The receiver missed the frame from the transmitter and repeated the last one
+    FrameLost = 0x04,
+    /// The receiver has lost the transmitter and is sending its failsafe positions
+    Failsafe = 0x08,
+}
+
+/// True for the footers receivers send: 0x00, or the telemetry slot number in the high
+/// nibble over 0x04 from FASSTest receivers
+const fn is_footer(byte: u8) -> bool {
+    byte == FOOTER || byte & 0xCF == 0x04
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Frame {
+    /// Raw 11-bit positions, 1024 at center
+    pub channels: [u16; CHANNELS],
+    pub flags: Flags<SbusFlag>,
+}
+
+impl Frame {
+    /// A frame with every channel centered
+    pub const fn new() -> Self {
+        Self {
+            channels: [1024; CHANNELS],
+            flags: Flags::empty(),
+        }
+    }
+
+    /// Unpacks a frame, or None if the header or footer are wrong
+    pub fn decode(bytes: &[u8; FRAME_LENGTH]) -> Option<Self> {
+        if bytes[0] != HEADER || !is_footer(bytes[FRAME_LENGTH - 1]) {
+            return None;
+        }
+
+        let data = bytes[1..1 + PACKED_CHANNELS_LENGTH].try_into().ok()?;
+        Some(Self {
+            channels: unpack_channels(data),
+            flags: Flags::from_bits(bytes[23] & 0x0F),
+        })
+    }
+
+    pub fn encode(&self) -> [u8; FRAME_LENGTH] {
+        let mut bytes = [0; FRAME_LENGTH];
+        bytes[0] = HEADER;
+
+        bytes[1..1 + PACKED_CHANNELS_LENGTH].copy_from_slice(&pack_channels(&self.channels));
+        bytes[23] = self.flags.bits();
+        bytes[24] = FOOTER;
+        bytes
+    }
+
+    /// The channels present in `channels`, with missing ones centered. Digital channels
+    /// are on above center.
+    pub fn from_channels(channels: &ChannelData, flags: Flags<SbusFlag>) -> Self {
+        let mut frame = Self { flags, ..Self::new() };
+        for (index, value) in channels.iter() {
+            match index {
+                index if index < CHANNELS => frame.channels[index] = value.raw() >> 5,
+                DIGITAL_CHANNEL => frame.flags.set(SbusFlag::Channel17, value > ChannelValue::CENTER),
+                index if index == DIGITAL_CHANNEL + 1 => frame.flags.set(SbusFlag::Channel18, value > ChannelValue::CENTER),
+                _ => (),
+            }
+        }
+        frame
+    }
+
+    /// Writes all 18 channels into `channels`, the digital ones as full deflection, and
+    /// counts the frame in `frame_losses` if the receiver flags it lost. The full 11-bit
+    /// range is about ±150% travel, the same as SRXL2's 16 bits, so this is only a shift.
+    pub fn update_channels(&self, channels: &mut ChannelData) {
+        for (index, raw) in self.channels.iter().enumerate() {
+            channels.set(index, ChannelValue::from_raw(raw << 5));
+        }
+        for (index, flag) in [(DIGITAL_CHANNEL, SbusFlag::Channel17), (DIGITAL_CHANNEL + 1, SbusFlag::Channel18)] {
+            channels.set(index, if self.flags.has(flag) { ChannelValue::MAX } else { ChannelValue::MIN });
+        }
+        if self.flags.has(SbusFlag::FrameLost) {
+            channels.frame_losses = channels.frame_losses.wrapping_add(1);
+        }
+    }
+
+    pub fn is_failsafe(&self) -> bool {
+        self.flags.has(SbusFlag::Failsafe)
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Splits the byte stream from an SBUS receiver into frames. Bytes before a header are
+/// skipped, and a frame without a valid footer is searched for the next header so the
+/// decoder can sync up again.
+pub struct Sbus {
+    buf: [u8; FRAME_LENGTH],
+    len: usize,
+}
+
+impl Sbus {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; FRAME_LENGTH],
+            len: 0,
+        }
+    }
+
+    /// Adds one received byte, already un-inverted, and returns the frame once its last
+    /// byte arrives
+    pub fn push(&mut self, byte: u8) -> Option<Frame> {
+        if self.len == 0 && byte != HEADER {
+            return None;
+        }
+
+        self.buf[self.len] = byte;
+        self.len += 1;
+        if self.len < FRAME_LENGTH {
+            return None;
+        }
+
+        if let Some(frame) = Frame::decode(&self.buf) {
+            self.len = 0;
+            return Some(frame);
+        }
+
+        // Started on a data byte that looked like a header: restart from the next one
+        self.len = match self.buf[1..].iter().position(|byte| *byte == HEADER) {
+            None => 0,
+            Some(start) => {
+                self.buf.copy_within(start + 1.., 0);
+                FRAME_LENGTH - 1 - start
+            },
+        };
+        None
+    }
+
+    /// Drops a partly received frame, e.g. after the line has been idle in the middle of one
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+
+    /// True if part of a frame has been received
+    pub fn is_receiving(&self) -> bool {
+        self.len > 0
+    }
+}
+
+impl Default for Sbus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
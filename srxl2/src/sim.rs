@@ -0,0 +1,338 @@
+//! A model of a half-duplex SRXL2 bus that runs several interpreters against each other
+//! on the host, in virtual time. Bytes take as long on the wire as they would on a real
+//! UART, devices listening at the wrong baud rate hear nothing, and overlapping
+//! transmissions corrupt each other. Only built with the `std` feature.
+
+use std::{string::String, vec::Vec};
+
+use crate::{
+    capture::{self, Record},
+    framer::Framer,
+    handshake::Baud,
+    packet::SRXL_MAX_BUFFER_SIZE,
+    transport::Transport,
+    Srxl2Interpreter,
+};
+
+const NS_PER_MS: u64 = 1_000_000;
+
+/// Start bit, 8 data bits, and stop bit
+const BITS_PER_BYTE: u64 = 10;
+
+/// Time one byte takes on the wire
+pub const fn byte_time_ns(baud: Baud) -> u64 {
+    BITS_PER_BYTE * 1_000_000_000 / baud.bits_per_second() as u64
+}
+
+/// Faults injected into every transmission, as probabilities from 0.0 to 1.0
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Faults {
+    /// Chance of each byte having one bit flipped
+    pub bit_error: f64,
+    /// Chance of each byte being lost entirely, e.g. to a framing error
+    pub dropped_byte: f64,
+    /// Chance of each packet arriving with a bad CRC
+    pub crc_corruption: f64,
+}
+
+/// A packet as it was put on the wire, before any faults
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub start_ns: u64,
+    /// Index of the node that sent it
+    pub sender: usize,
+    pub baud: Baud,
+    pub bytes: Vec<u8>,
+}
+
+/// A node's UART
+struct Port {
+    baud: Baud,
+    /// Packets sent since the simulator last looked, with the baud rate at the time
+    outbox: Vec<(Baud, Vec<u8>)>,
+}
+
+impl Transport for Port {
+    fn send(&mut self, _uart: u8, packet: &[u8]) {
+        self.outbox.push((self.baud, packet.to_vec()));
+    }
+
+    fn change_baud(&mut self, _uart: u8, baud: Baud) {
+        self.baud = baud;
+    }
+}
+
+struct Node {
+    interpreter: Srxl2Interpreter,
+    port: Port,
+    framer: Framer,
+    /// End of the last byte received, to notice the line going idle mid-packet
+    last_rx_ns: Option<u64>,
+    connected: bool,
+}
+
+/// Bytes on their way across the wire
+struct Transmission {
+    sender: usize,
+    start_ns: u64,
+    byte_ns: u64,
+    baud: Baud,
+    /// None for bytes the receivers won't see
+    bytes: Vec<Option<u8>>,
+    /// Index of the next byte to be delivered
+    next: usize,
+}
+
+impl Transmission {
+    fn byte_start_ns(&self, index: usize) -> u64 {
+        self.start_ns + index as u64 * self.byte_ns
+    }
+
+    fn byte_end_ns(&self, index: usize) -> u64 {
+        self.byte_start_ns(index + 1)
+    }
+
+    fn end_ns(&self) -> u64 {
+        self.byte_start_ns(self.bytes.len())
+    }
+}
+
+/// A single SRXL2 bus with any number of devices on it, all using bus index 0 and
+/// UART 0 of their interpreter. Each interpreter's run is called every millisecond.
+pub struct SimBus {
+    pub faults: Faults,
+    nodes: Vec<Node>,
+    in_flight: Vec<Transmission>,
+    now_ns: u64,
+    next_tick_ns: u64,
+    rng: u64,
+    log: Vec<Frame>,
+    collisions: u32,
+}
+
+impl SimBus {
+    /// `seed` drives the fault injection, so a run can be repeated exactly
+    pub fn new(seed: u64) -> Self {
+        Self {
+            faults: Faults::default(),
+            nodes: Vec::new(),
+            in_flight: Vec::new(),
+            now_ns: 0,
+            next_tick_ns: 0,
+            // xorshift must never be seeded with 0
+            rng: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed },
+            log: Vec::new(),
+            collisions: 0,
+        }
+    }
+
+    /// Connects a device, which should already have had init_device and init_bus
+    /// called. Returns its node index.
+    pub fn add_node(&mut self, interpreter: Srxl2Interpreter) -> usize {
+        self.nodes.push(Node {
+            interpreter,
+            port: Port {
+                baud: Baud::Baud115200,
+                outbox: Vec::new(),
+            },
+            framer: Framer::new(),
+            last_rx_ns: None,
+            connected: true,
+        });
+        self.nodes.len() - 1
+    }
+
+    pub fn node(&self, index: usize) -> &Srxl2Interpreter {
+        &self.nodes[index].interpreter
+    }
+
+    pub fn node_mut(&mut self, index: usize) -> &mut Srxl2Interpreter {
+        &mut self.nodes[index].interpreter
+    }
+
+    /// Unplugs or plugs back in a device. A disconnected device is frozen: it neither
+    /// runs nor hears anything.
+    pub fn set_connected(&mut self, index: usize, connected: bool) {
+        let node = &mut self.nodes[index];
+        node.connected = connected;
+        node.framer.reset();
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        self.now_ns / NS_PER_MS
+    }
+
+    /// Everything sent so far, in order
+    pub fn log(&self) -> &[Frame] {
+        &self.log
+    }
+
+    pub fn clear_log(&mut self) {
+        self.log.clear();
+    }
+
+    /// The log in capture format, as a device listening at the right baud rate would
+    /// have recorded it
+    pub fn capture(&self) -> String {
+        let mut text = String::new();
+        let mut line = [0u8; capture::max_line_length(SRXL_MAX_BUFFER_SIZE)];
+        for frame in &self.log {
+            let record = Record {
+                timestamp_us: (frame.start_ns / 1000) as u32,
+                bytes: &frame.bytes,
+            };
+            if let Some(len) = record.format(&mut line) {
+                text.extend(line[..len].iter().map(|&c| c as char));
+            }
+        }
+        text
+    }
+
+    /// Number of bytes that were corrupted by two devices talking at once
+    pub fn collisions(&self) -> u32 {
+        self.collisions
+    }
+
+    /// Advances virtual time, delivering bytes and running every device as it goes
+    pub fn run_for_ms(&mut self, ms: u64) {
+        let end_ns = self.now_ns + ms * NS_PER_MS;
+        loop {
+            let next_byte = self.in_flight.iter()
+                .enumerate()
+                .map(|(index, tx)| (tx.byte_end_ns(tx.next), index))
+                .min();
+
+            match next_byte {
+                Some((byte_ns, index)) if byte_ns <= self.next_tick_ns && byte_ns <= end_ns => {
+                    self.now_ns = byte_ns;
+                    self.deliver_byte(index);
+                },
+                _ if self.next_tick_ns <= end_ns => {
+                    self.now_ns = self.next_tick_ns;
+                    self.next_tick_ns += NS_PER_MS;
+                    self.tick();
+                },
+                _ => break,
+            }
+        }
+        self.now_ns = end_ns;
+    }
+
+    fn tick(&mut self) {
+        for index in 0..self.nodes.len() {
+            let node = &mut self.nodes[index];
+            if node.connected {
+                node.interpreter.run(0, 1, &mut node.port);
+                self.flush(index);
+            }
+        }
+    }
+
+    /// Starts transmitting whatever a node has sent, after anything it's still sending
+    fn flush(&mut self, sender: usize) {
+        let outbox = core::mem::take(&mut self.nodes[sender].port.outbox);
+        for (baud, bytes) in outbox {
+            let start_ns = self.in_flight.iter()
+                .filter(|tx| tx.sender == sender)
+                .map(Transmission::end_ns)
+                .fold(self.now_ns, u64::max);
+
+            self.log.push(Frame {
+                start_ns,
+                sender,
+                baud,
+                bytes: bytes.clone(),
+            });
+
+            let crc_index = bytes.len() - 1;
+            let corrupt_crc = self.chance(self.faults.crc_corruption);
+            let bytes = bytes.into_iter()
+                .enumerate()
+                .map(|(index, mut byte)| {
+                    if self.chance(self.faults.dropped_byte) {
+                        return None;
+                    }
+                    if self.chance(self.faults.bit_error) {
+                        byte ^= 1 << (self.next_random() % 8);
+                    }
+                    if corrupt_crc && index == crc_index {
+                        byte ^= 1 << (self.next_random() % 8);
+                    }
+                    Some(byte)
+                })
+                .collect();
+
+            self.in_flight.push(Transmission {
+                sender,
+                start_ns,
+                byte_ns: byte_time_ns(baud),
+                baud,
+                bytes,
+                next: 0,
+            });
+        }
+    }
+
+    fn deliver_byte(&mut self, tx_index: usize) {
+        let tx = &self.in_flight[tx_index];
+        let byte_index = tx.next;
+        let (start_ns, end_ns) = (tx.byte_start_ns(byte_index), tx.byte_end_ns(byte_index));
+        let (sender, baud, byte_ns) = (tx.sender, tx.baud, tx.byte_ns);
+        let mut byte = tx.bytes[byte_index];
+
+        // the line idles high, so when two devices drive it at once, zeros win
+        for (other_index, other) in self.in_flight.iter().enumerate() {
+            if other_index == tx_index || other.start_ns >= end_ns || other.end_ns() <= start_ns {
+                continue;
+            }
+            self.collisions += 1;
+            let overlapping = (start_ns.saturating_sub(other.start_ns) / other.byte_ns) as usize;
+            byte = match (byte, other.bytes.get(overlapping).copied().flatten()) {
+                (Some(ours), Some(theirs)) => Some(ours & theirs),
+                (ours, _) => ours,
+            };
+        }
+
+        let tx = &mut self.in_flight[tx_index];
+        tx.next += 1;
+        if tx.next == tx.bytes.len() {
+            self.in_flight.remove(tx_index);
+        }
+
+        let byte = match byte {
+            None => return,
+            Some(byte) => byte,
+        };
+        for index in 0..self.nodes.len() {
+            let node = &mut self.nodes[index];
+            if index == sender || !node.connected || node.port.baud != baud {
+                continue;
+            }
+
+            if node.last_rx_ns.is_some_and(|last_ns| start_ns > last_ns + byte_ns) {
+                node.framer.reset();
+            }
+            node.last_rx_ns = Some(end_ns);
+
+            if let Some(packet) = node.framer.push(byte) {
+                let packet = packet.to_vec();
+                node.interpreter.parse_packet(0, &packet, &mut node.port);
+                self.flush(index);
+            }
+        }
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        // 53 random bits give a uniform value in [0, 1)
+        let sample = (self.next_random() >> 11) as f64 / (1u64 << 53) as f64;
+        probability > 0.0 && sample < probability
+    }
+
+    /// xorshift64
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
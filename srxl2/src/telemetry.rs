@@ -8,6 +8,8 @@ use crate::device::DeviceId;
 
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct TelemetryData {
     pub sensor_id: u8,
     pub secondary_id: u8,
@@ -16,8 +18,9 @@ pub struct TelemetryData {
 
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, FromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct TelemetryPayload {
     pub dest_dev: DeviceId,
     pub payload: TelemetryData,
-    pub crc: u16,
 }
\ No newline at end of file
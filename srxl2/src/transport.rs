@@ -0,0 +1,19 @@
+use crate::handshake::Baud;
+
+/// The application's connection to one or more SRXL2 buses (srxlSendOnUart and
+/// srxlChangeBaudRate in the reference implementation)
+pub trait Transport {
+    /// Puts a complete packet on the wire of the given UART
+    fn send(&mut self, uart: u8, packet: &[u8]);
+
+    /// Switches the given UART to a new baud rate. The packet passed to send just before
+    /// must still go out at the old rate.
+    fn change_baud(&mut self, uart: u8, baud: Baud);
+
+    /// Milliseconds on a free-running clock, wrapping, which times echo round trips more
+    /// finely than calls to run do. With the default, None, they're timed in run's ticks,
+    /// and an echo that comes back before the next tick takes 0ms.
+    fn now_ms(&mut self) -> Option<u32> {
+        None
+    }
+}
@@ -2,6 +2,8 @@ use zerocopy::{KnownLayout, Immutable, TryFromBytes, IntoBytes};
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum TxFlag {
     EnterBind = 0x01,
     GetBindInfo = 0x02,
@@ -0,0 +1,447 @@
+//! Asynchronous serial framing a bit at a time, for UARTs done in software. `Bits` gives
+//! the line levels that send one word, and `Sampler` puts a word back together from the
+//! levels sampled in the middle of each bit. Neither touches hardware or time: the
+//! caller steps them once per bit time. `BaudDivider` works out how close a clock can
+//! come to a baud rate.
+//!
+//! `LineReceiver` does the stepping for a recorded line, given as how long it held each
+//! level, and `estimate_baud` works out the rate it was sent at.
+
+/// Which end of the word goes out first
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum BitOrder {
+    /// As nearly every UART sends
+    LsbFirst,
+    MsbFirst,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Parity {
+    None,
+    /// The parity bit makes the count of ones even
+    Even,
+    /// The parity bit makes the count of ones odd
+    Odd,
+}
+
+impl Parity {
+    const fn bits(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Even | Self::Odd => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+/// The shape of one word on the line
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Framing {
+    /// 7, 8, or 9
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub bit_order: BitOrder,
+    /// Idle low with a high start bit, as SBUS sends, rather than idle high
+    pub inverted: bool,
+}
+
+/// Fewest and most data bits in a word
+pub const MIN_DATA_BITS: u8 = 7;
+pub const MAX_DATA_BITS: u8 = 9;
+
+impl Framing {
+    /// 8N1, least significant bit first, idle high
+    pub const fn new() -> Self {
+        Self {
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            bit_order: BitOrder::LsbFirst,
+            inverted: false,
+        }
+    }
+
+    /// Futaba SBUS: 8E2 on an inverted line
+    pub const SBUS: Self = Self {
+        data_bits: 8,
+        parity: Parity::Even,
+        stop_bits: StopBits::Two,
+        bit_order: BitOrder::LsbFirst,
+        inverted: true,
+    };
+
+    /// False if the word size is out of range
+    pub const fn is_valid(&self) -> bool {
+        self.data_bits >= MIN_DATA_BITS && self.data_bits <= MAX_DATA_BITS
+    }
+
+    /// Bit times in a word, from the start bit to the end of the last stop bit
+    pub const fn frame_bits(&self) -> u8 {
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        1 + self.data_bits + self.parity.bits() + stop_bits
+    }
+
+    /// The bits of a word that get sent
+    pub const fn word_mask(&self) -> u16 {
+        (1 << self.data_bits) - 1
+    }
+
+    /// The level of the line between words
+    pub const fn idle_level(&self) -> bool {
+        !self.inverted
+    }
+
+    /// The line levels that send `word`, one per bit time, from the start bit to the
+    /// last stop bit. Bits above `data_bits` are left out.
+    pub const fn bits(&self, word: u16) -> Bits {
+        Bits {
+            framing: *self,
+            word: word & self.word_mask(),
+            index: 0,
+        }
+    }
+
+    /// Data bit `index` in the order they're sent
+    const fn data_bit(&self, word: u16, index: u8) -> bool {
+        let shift = match self.bit_order {
+            BitOrder::LsbFirst => index,
+            BitOrder::MsbFirst => self.data_bits - 1 - index,
+        };
+        word >> shift & 1 != 0
+    }
+
+    /// The parity bit for `word`; meaningless with `Parity::None`
+    const fn parity_bit(&self, word: u16) -> bool {
+        let odd_ones = (word & self.word_mask()).count_ones() % 2 == 1;
+        match self.parity {
+            Parity::Odd => !odd_ones,
+            Parity::None | Parity::Even => odd_ones,
+        }
+    }
+}
+
+impl Default for Framing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Line levels of one word, true for high
+#[derive(Clone, Copy)]
+pub struct Bits {
+    framing: Framing,
+    word: u16,
+    index: u8,
+}
+
+impl Bits {
+    /// The word being sent
+    pub const fn word(&self) -> u16 {
+        self.word
+    }
+}
+
+impl Iterator for Bits {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        let framing = &self.framing;
+        let data_end = 1 + framing.data_bits;
+        let parity_end = data_end + framing.parity.bits();
+        let bit = match self.index {
+            0 => false,
+            index if index < data_end => framing.data_bit(self.word, index - 1),
+            index if index < parity_end => framing.parity_bit(self.word),
+            index if index < framing.frame_bits() => true,
+            _ => return None,
+        };
+        self.index += 1;
+        Some(bit != framing.inverted)
+    }
+}
+
+/// Why a received word was dropped
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum FrameError {
+    /// The stop bit was at the start bit's level: the bits were sampled out of step with
+    /// the sender, or the line is in a break
+    Framing,
+    /// The parity bit doesn't match the data
+    Parity,
+}
+
+/// Puts received words back together from the line level in the middle of each bit
+/// after the start bit. Only the first stop bit is checked; any more are idle time
+/// before the next start bit.
+#[derive(Clone, Copy)]
+pub struct Sampler {
+    framing: Framing,
+    word: u16,
+    /// Bits taken of the current word
+    index: u8,
+    parity_bit: bool,
+}
+
+impl Sampler {
+    pub const fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            word: 0,
+            index: 0,
+            parity_bit: false,
+        }
+    }
+
+    pub const fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Takes the level sampled for the next bit. Returns the word, or why it was dropped,
+    /// at the first stop bit, ready for the next word's first data bit.
+    pub fn push(&mut self, level: bool) -> Option<Result<u16, FrameError>> {
+        let framing = self.framing;
+        let bit = level != framing.inverted;
+        let index = self.index;
+        self.index += 1;
+
+        if index < framing.data_bits {
+            self.word = match framing.bit_order {
+                BitOrder::LsbFirst => self.word | (bit as u16) << index,
+                BitOrder::MsbFirst => self.word << 1 | bit as u16,
+            };
+            return None;
+        }
+        if index < framing.data_bits + framing.parity.bits() {
+            self.parity_bit = bit;
+            return None;
+        }
+
+        let word = self.word;
+        let parity_bit = self.parity_bit;
+        self.reset();
+        if !bit {
+            Some(Err(FrameError::Framing))
+        }
+        else if framing.parity != Parity::None && parity_bit != framing.parity_bit(word) {
+            Some(Err(FrameError::Parity))
+        }
+        else {
+            Some(Ok(word))
+        }
+    }
+
+    /// Drops a partly received word, e.g. when the sampling lost track of the start bit
+    pub fn reset(&mut self) {
+        self.word = 0;
+        self.index = 0;
+        self.parity_bit = false;
+    }
+
+    /// True once some of a word has been taken
+    pub const fn is_receiving(&self) -> bool {
+        self.index > 0
+    }
+}
+
+/// Furthest a baud rate can be from the one asked for, in tenths of a percent. With both
+/// ends this far off in opposite directions, the last sample of a 12-bit word is still
+/// under half a bit from the middle.
+pub const MAX_BAUD_ERROR_PERMILLE: u32 = 20;
+
+/// A baud rate as a clock makes it: divided by a prescaler, then counted to a whole
+/// number of ticks per bit
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct BaudDivider {
+    pub prescale: u32,
+    /// Prescaled clock ticks per bit
+    pub count: u32,
+    pub requested: u32,
+    /// The rate it comes out at, rounded to the nearest baud
+    pub actual: u32,
+}
+
+impl BaudDivider {
+    pub const fn new(clock_hz: u32, requested: u32, prescale: u32, count: u32) -> Self {
+        let divisor = prescale as u64 * count as u64;
+        Self {
+            prescale,
+            count,
+            requested,
+            actual: ((clock_hz as u64 + divisor / 2) / divisor) as u32,
+        }
+    }
+
+    /// The divider nearest to `baud` with any of `prescales` and a count from 1 to
+    /// `max_count`, or None if they can't get within a count of it
+    pub fn closest(clock_hz: u32, baud: u32, prescales: &[u32], max_count: u32) -> Option<Self> {
+        let mut best: Option<Self> = None;
+        for prescale in prescales {
+            let divisor = *prescale as u64 * baud as u64;
+            let count = (clock_hz as u64 + divisor / 2) / divisor;
+            if count == 0 || count > max_count as u64 {
+                continue;
+            }
+
+            let divider = Self::new(clock_hz, baud, *prescale, count as u32);
+            if best.is_none_or(|best| divider.actual.abs_diff(baud) < best.actual.abs_diff(baud)) {
+                best = Some(divider);
+            }
+        }
+        best
+    }
+
+    /// How far off the actual rate is, in tenths of a percent; positive if it's fast
+    pub const fn error_permille(&self) -> i32 {
+        let difference = self.actual as i64 - self.requested as i64;
+        (difference * 1000 / self.requested as i64) as i32
+    }
+
+    /// True if the actual rate is within `MAX_BAUD_ERROR_PERMILLE` of the one asked for
+    pub const fn is_within_tolerance(&self) -> bool {
+        self.error_permille().unsigned_abs() <= MAX_BAUD_ERROR_PERMILLE
+    }
+}
+
+/// Rates serial links are commonly run at, SRXL2's 115200 and 400000 among them
+pub const COMMON_BAUDS: [u32; 9] = [9600, 19200, 38400, 57600, 100_000, 115_200, 230_400, 400_000, 420_000];
+
+/// The common rate within `MAX_BAUD_ERROR_PERMILLE` of `baud`, if there is one
+pub fn snap_baud(baud: u32) -> Option<u32> {
+    COMMON_BAUDS.into_iter().find(|common| {
+        (common.abs_diff(baud) as u64 * 1000) <= MAX_BAUD_ERROR_PERMILLE as u64 * *common as u64
+    })
+}
+
+/// Most bits a word can hold the line still for: nine data bits of 0 and a parity bit
+const MAX_RUN_BITS: u64 = 11;
+
+/// Works out the baud rate of a recorded line from how long it held each level between
+/// edges, in ticks of a `tick_hz` clock. The shortest runs, as long as there are enough of
+/// them not to be glitches, are taken as one bit. Then every run up to a word long is
+/// rounded to whole bits, and the rate is worked out over all of them. Sorts `runs`.
+/// Returns None if there's nothing to go on.
+pub fn estimate_baud(runs: &mut [u32], tick_hz: u32) -> Option<u32> {
+    runs.sort_unstable();
+    // A single bit is one of the commonest runs in any traffic
+    let enough = runs.len().div_ceil(20);
+    let (_, shortest) = runs.iter().enumerate().find(|(index, run)| {
+        let cluster = runs.partition_point(|other| *other <= run.saturating_add(**run / 2));
+        **run > 0 && cluster - index >= enough
+    })?;
+
+    // Ticks per bit, in 256ths
+    let mut bit = *shortest as u64 * 256;
+    for _ in 0..2 {
+        let (mut ticks, mut bits) = (0u64, 0u64);
+        for run in runs.iter() {
+            let run_bits = (*run as u64 * 256 + bit / 2) / bit;
+            if run_bits > MAX_RUN_BITS {
+                break;
+            }
+            ticks += *run as u64;
+            bits += run_bits;
+        }
+        if bits == 0 {
+            return None;
+        }
+        bit = (ticks * 256 + bits / 2) / bits;
+    }
+    Some(((tick_hz as u64 * 256 + bit / 2) / bit) as u32)
+}
+
+/// Puts words back together from a recorded line, given a run at a time: a level, and
+/// how long it held before the next edge. Each start bit is found by its edge, and the
+/// rest of the word is sampled in the middle of each bit from there, as a UART would.
+/// Times are in ticks of a `tick_hz` clock, counted from the start of the first run.
+pub struct LineReceiver {
+    sampler: Sampler,
+    tick_hz: u32,
+    baud: u32,
+    /// When the next run starts
+    now: u64,
+    /// The level of the last run, unknown before the first
+    level: Option<bool>,
+    /// When the start bit of the word being received began
+    word_start: Option<u64>,
+    /// The next bit of that word to sample, 0 for the start bit
+    bit: u32,
+}
+
+impl LineReceiver {
+    pub const fn new(framing: Framing, tick_hz: u32, baud: u32) -> Self {
+        Self {
+            sampler: Sampler::new(framing),
+            tick_hz,
+            baud,
+            now: 0,
+            level: None,
+            word_start: None,
+            bit: 0,
+        }
+    }
+
+    /// Says what level the line was at before the next run, e.g. idle before the edge
+    /// that triggered a capture, so a start bit right at the start isn't missed
+    pub fn set_level(&mut self, level: bool) {
+        self.level = Some(level);
+    }
+
+    /// Ticks from the start of the first run to the start of the next
+    pub const fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// True partway through a word
+    pub const fn is_receiving(&self) -> bool {
+        self.word_start.is_some()
+    }
+
+    /// Takes the next run, and calls `on_word` with each word that ends in it: when its
+    /// start bit began, and the word or why it was dropped. A start bit that's gone by its
+    /// middle was a glitch, and is ignored.
+    pub fn push(&mut self, level: bool, ticks: u32, mut on_word: impl FnMut(u64, Result<u16, FrameError>)) {
+        let idle = self.sampler.framing().idle_level();
+        let end = self.now + ticks as u64;
+        if self.word_start.is_none() && self.level == Some(idle) && level != idle {
+            self.word_start = Some(self.now);
+            self.bit = 0;
+        }
+
+        while let Some(word_start) = self.word_start {
+            // The middle of the bit, to the nearest tick
+            let sample = word_start + ((2 * self.bit as u64 + 1) * self.tick_hz as u64) / (2 * self.baud as u64);
+            if sample >= end {
+                break;
+            }
+
+            if self.bit == 0 {
+                if level == idle {
+                    self.word_start = None;
+                }
+            }
+            else if let Some(word) = self.sampler.push(level) {
+                self.word_start = None;
+                on_word(word_start, word);
+            }
+            self.bit += 1;
+        }
+
+        self.level = Some(level);
+        self.now = end;
+    }
+}
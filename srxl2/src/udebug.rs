@@ -0,0 +1,457 @@
+//! `ufmt` versions of the `Debug` and `Display` impls, for printing over a serial console
+//! on targets where pulling in `core::fmt` costs too much flash. Only built with the
+//! `ufmt` feature.
+//!
+//! Most structs are `repr(packed)`, so their fields are copied out before printing
+//! rather than borrowed.
+
+use ufmt::{uDebug, uDisplay, uWrite, Formatter};
+use zerocopy::{Immutable, IntoBytes, TryFromBytes};
+
+use crate::{
+    bind::{self, BindData, BindOption, BindPayload, BindStatus},
+    capture::Record,
+    channel::{ChannelData, ChannelValue, Normalized},
+    control::{
+        Cmd,
+        CmdCode,
+        ControlChannelData,
+        ControlChannelPacket,
+        ControlData,
+        ControlFwdPgmData,
+        ControlFwdPgmPacket,
+        ControlPacket,
+        ControlVtxData,
+        ControlVtxPacket,
+    },
+    crsf::{self, Address, Battery, FrameType, LinkStatistics},
+    device::{Device, DeviceEntry, DeviceId, DeviceInfo, DeviceType, FullId},
+    error::{CaptureError, LogicError, PacketCastError},
+    flags::Flags,
+    fwd_pgm::FwdPgmData,
+    handshake::{Baud, BaudRates, HandshakeData},
+    input::LinkState,
+    logic::{self, Trigger},
+    internal::{InternalData, InternalTest, LoopbackStats, State},
+    packet::{
+        BindPacket,
+        HandshakePacket,
+        Header,
+        InternalPacket,
+        Packet,
+        PacketType,
+        ParamPacket,
+        RssiPacket,
+        TelemetryPacket,
+    },
+    param::{self, ParamPayload},
+    ppm::{self, Polarity, Step, Timing},
+    receiver::{ReceiverEntry, Received},
+    rssi::{self, RssiPayload},
+    satellite::{BindStep, Frame, Remote, Resolution},
+    sbus::{self, SbusFlag},
+    telemetry::{TelemetryData, TelemetryPayload},
+    tx::TxFlag,
+    types::StmTargetFamily,
+    uart::{BaudDivider, BitOrder, FrameError, Framing, Parity, StopBits},
+    vtx::{Band, Mode, Power, Region, VtxData},
+};
+
+/// Prints fieldless enums as their variant name
+macro_rules! enums {
+    ($($ty:ty { $($variant:ident),* $(,)? })*) => { $(
+        impl uDebug for $ty {
+            fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+                f.write_str(match self {
+                    $(Self::$variant => stringify!($variant),)*
+                })
+            }
+        }
+    )* };
+}
+
+/// Prints structs whose fields are all `Copy` and `uDebug`
+macro_rules! structs {
+    ($($ty:ident $(<$lt:lifetime>)? { $($field:ident),* $(,)? })*) => { $(
+        impl$(<$lt>)? uDebug for $ty$(<$lt>)? {
+            fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+                f.debug_struct(stringify!($ty))?
+                    $(.field(stringify!($field), &{ self.$field })?)*
+                    .finish()
+            }
+        }
+    )* };
+}
+
+enums! {
+    PacketType { Handshake, BindInfo, ParamConfig, SignalQuality, TelemetryData, ControlData, Internal }
+    DeviceType {
+        None,
+        RemoteReceiver,
+        Receiver,
+        FlightController,
+        ESC,
+        SRXLServo1,
+        SRXLServo2,
+        VTX,
+        ExtRF,
+        RemoteId,
+        Sensor,
+        Broadcast,
+    }
+    DeviceInfo { NoRf, TelemTxEnabled, TelemFullRange, FwdProgSupport }
+    Baud { Baud115200, Baud400000 }
+    bind::Request { Enter, Status, BoundData, SetBind }
+    BindOption { None, TelemTxEnable, BindTxEnable, UsPower }
+    BindStatus {
+        NotBound,
+        Dsm2_1024_22ms,
+        Dsm2_1024Mc24,
+        Dsm2_2048_11ms,
+        Dsmx22Ms,
+        Dsmx11Ms,
+        SurfaceDsm1,
+        SurfaceDsm2_16p6ms,
+        Dsmr11ms22ms,
+        Dsmr5p5ms,
+    }
+    param::Request { Query, Write }
+    rssi::Request { Request, Send }
+    CmdCode { Channel, ChannelFailsafe, Vtx, FwdPgm }
+    Cmd {
+        None,
+        Channel,
+        ChannelFs,
+        Vtx,
+        FwdPgm,
+        Rssi,
+        Handshake,
+        Telemtry,
+        EnterBind,
+        ReqBindInfo,
+        SetBind,
+        BindInfo,
+        Internal,
+    }
+    InternalTest { EchoRequest, EchoReply }
+    State {
+        Disabled,
+        ListenOnStartup,
+        SendHandshake,
+        ListenForHandshake,
+        Running,
+        SendTelemetry,
+        SendVtx,
+        SendEnterBind,
+        SendBoundDataReport,
+        SendSetBindInfo,
+        RequestBindInfo,
+        SendInternal,
+    }
+    Band { FatShark, RaceBand, EBand, BBand, ABand }
+    Mode { Race, Pit }
+    Power { Off, P1To14Mw, P15To99Mw, P26To99Mw, P100To299Mw, P300To600Mw, P601MwPlus, Manual }
+    Region { Us, Eu }
+    TxFlag {
+        EnterBind,
+        GetBindInfo,
+        SetBindInfo,
+        BroadcastBindInfo,
+        ReportBindInfo,
+        SendVtxData,
+        SendFwdPgmData,
+        SendInternal,
+    }
+    Received { None, Dbm, Pct, Both }
+    StmTargetFamily { F3, F7 }
+    PacketCastError { HeaderMismatch, Cast, Length, Crc }
+    CaptureError { Timestamp, Hex, TooLong }
+    LogicError { Header, Number, Status }
+    logic::Status { Idle, Full, EdgesLost }
+    Resolution { Bits10, Bits11 }
+    Remote { Internal, External }
+    LinkState { NoSignal, Connected, Lost }
+    SbusFlag { Channel17, Channel18, FrameLost, Failsafe }
+    Address { FlightController, RadioTransmitter, Receiver, TransmitterModule }
+    FrameType { Battery, LinkStatistics, RcChannelsPacked, FlightMode }
+    Polarity { Positive, Negative }
+    BitOrder { LsbFirst, MsbFirst }
+    Parity { None, Even, Odd }
+    StopBits { One, Two }
+    FrameError { Framing, Parity }
+}
+
+structs! {
+    Header { srxl_id, packet_type, length }
+    BindPacket<'a> { hdr, bind }
+    HandshakePacket<'a> { hdr, handshake }
+    ParamPacket<'a> { hdr, param }
+    RssiPacket<'a> { hdr, rssi }
+    TelemetryPacket<'a> { hdr, telemetry }
+    InternalPacket<'a> { hdr, internal }
+    ControlPacket<'a> { hdr, control }
+    ControlVtxPacket<'a> { hdr, control }
+    ControlFwdPgmPacket<'a> { hdr, control }
+    ControlChannelPacket<'a> { hdr, control }
+    ControlVtxData<'a> { cmd, reply_id, data }
+    ControlFwdPgmData<'a> { cmd, reply_id, data }
+    ControlChannelData<'a> { cmd, reply_id, data }
+    HandshakeData { src_dev_id, dest_dev_id, priority, baud_supported, info, uid }
+    BindData { bind_type, options, guid, uid }
+    BindPayload { request, device_id, data }
+    ParamPayload { request, dest_dev_id, param_id, param_val }
+    RssiPayload { request, antenna_a, antenna_b, antenna_c, antenna_d }
+    TelemetryPayload { dest_dev, payload }
+    InternalData { src_dev_id, dest_dev_id, test, key }
+    VtxData { band, channel, pit, power, power_dec, region }
+    FullId { device_id, bus_index }
+    DeviceEntry { device_id, priority, info, rfu }
+    Device { dev_entry, uid, rcvr, vtx_proxy }
+    ReceiverEntry { device_id, bus_bits, info, rssi_received, rssi_dbm, rssi_pct, fades, channel_mask }
+    LoopbackStats { device_id, sent, received, mismatched, lost, rtt_last_ms, rtt_min_ms, rtt_max_ms }
+    Record<'a> { timestamp_us, bytes }
+    BindStep { high, duration_us }
+    LinkStatistics {
+        uplink_rssi_1,
+        uplink_rssi_2,
+        uplink_link_quality,
+        uplink_snr,
+        active_antenna,
+        rf_mode,
+        uplink_tx_power,
+        downlink_rssi,
+        downlink_link_quality,
+        downlink_snr,
+    }
+    Timing { frame_us, pulse_us, polarity }
+    Step { high, duration_us }
+    Framing { data_bits, parity, stop_bits, bit_order, inverted }
+    BaudDivider { prescale, count, requested, actual }
+}
+
+impl uDebug for Packet {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_struct("Packet")?
+            .field("hdr", &{ self.hdr })?
+            .field("payload", &self.payload())?
+            .field("crc", &self.crc())?
+            .finish()
+    }
+}
+
+impl uDebug for ControlData {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_struct("ControlData")?
+            .field("cmd", &{ self.cmd })?
+            .field("reply_id", &{ self.reply_id })?
+            .field("data", &&self.data[..])?
+            .finish()
+    }
+}
+
+impl uDebug for ChannelData {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        let values = self.values;
+        f.debug_struct("ChannelData")?
+            .field("rssi", &{ self.rssi })?
+            .field("frame_losses", &{ self.frame_losses })?
+            .field("mask", &{ self.mask })?
+            .field("values", &&values[..])?
+            .finish()
+    }
+}
+
+impl uDebug for TelemetryData {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_struct("TelemetryData")?
+            .field("sensor_id", &{ self.sensor_id })?
+            .field("secondary_id", &{ self.secondary_id })?
+            .field("data", &&self.data[..])?
+            .finish()
+    }
+}
+
+impl uDebug for FwdPgmData {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_struct("FwdPgmData")?
+            .field("rssi", &{ self.rssi })?
+            .field("rfu", &&self.rfu[..])?
+            .field("data", &&self.data[..])?
+            .finish()
+    }
+}
+
+impl uDebug for Frame {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_tuple("Frame")?.field(&&self.0[..])?.finish()
+    }
+}
+
+impl uDebug for sbus::Frame {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_struct("Frame")?
+            .field("channels", &&self.channels[..])?
+            .field("flags", &self.flags)?
+            .finish()
+    }
+}
+
+impl uDebug for crsf::Frame<'_> {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_struct("Frame")?
+            .field("address", &self.address)?
+            .field("frame_type", &self.frame_type)?
+            .field("payload", &self.payload)?
+            .finish()
+    }
+}
+
+impl uDebug for Battery {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_struct("Battery")?
+            .field("voltage", &self.voltage.get())?
+            .field("current", &self.current.get())?
+            .field("capacity", &self.capacity_mah())?
+            .field("remaining", &self.remaining)?
+            .finish()
+    }
+}
+
+impl uDebug for ppm::Frame {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_struct("Frame")?
+            .field("widths", &self.widths())?
+            .finish()
+    }
+}
+
+impl uDebug for Trigger {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        match self {
+            Self::Immediate => f.write_str("Immediate"),
+            Self::FallingEdge => f.write_str("FallingEdge"),
+            Self::IdleGap(ticks) => f.debug_tuple("IdleGap")?.field(ticks)?.finish(),
+        }
+    }
+}
+
+impl uDebug for logic::Header {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_struct("Header")?
+            .field("level", &self.level)?
+            .field("trigger", &self.trigger)?
+            .finish()
+    }
+}
+
+impl uDebug for logic::Event {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        match self {
+            Self::Start(header) => f.debug_tuple("Start")?.field(header)?.finish(),
+            Self::Run(ticks) => f.debug_tuple("Run")?.field(ticks)?.finish(),
+            Self::End(status) => f.debug_tuple("End")?.field(status)?.finish(),
+        }
+    }
+}
+
+impl uDebug for ChannelValue {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_tuple("ChannelValue")?.field(&self.raw())?.finish()
+    }
+}
+
+impl uDebug for Normalized {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.debug_tuple("Normalized")?.field(&self.0)?.finish()
+    }
+}
+
+/// Prints the raw byte, then the flags in it that `T` has a variant for
+impl<T> uDebug for Flags<T> where T: Immutable + TryFromBytes + IntoBytes + uDebug {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        write_hex(f, self.bits())?;
+        f.write_char(' ')?;
+        let mut set = f.debug_set()?;
+        for flag in self.iter() {
+            set.entry(&flag)?;
+        }
+        set.finish()
+    }
+}
+
+/// Prints the raw byte, then the rates in it
+impl uDebug for BaudRates {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        write_hex(f, self.bits())?;
+        f.write_char(' ')?;
+        let mut set = f.debug_set()?;
+        for baud in self.iter() {
+            set.entry(&baud)?;
+        }
+        set.finish()
+    }
+}
+
+impl uDebug for DeviceId {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.write_str("DeviceId(0x")?;
+        write_hex(f, u8::from(*self))?;
+        f.write_char(')')
+    }
+}
+
+impl uDisplay for DeviceId {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        write_hex(f, u8::from(*self))?;
+        f.write_str(" (")?;
+        f.write_str(match self.device_type() {
+            None => "unknown type",
+            Some(device_type) => device_type.name(),
+        })?;
+        f.write_char(')')
+    }
+}
+
+impl uDisplay for DeviceType {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.write_str(self.name())
+    }
+}
+
+impl uDisplay for PacketType {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.write_str(self.name())
+    }
+}
+
+impl uDisplay for Baud {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        uDisplay::fmt(&self.bits_per_second(), f)?;
+        f.write_str(" baud")
+    }
+}
+
+impl uDisplay for PacketCastError {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.write_str(self.message())
+    }
+}
+
+impl uDisplay for CaptureError {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.write_str(self.message())
+    }
+}
+
+impl uDisplay for LogicError {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> where W: uWrite + ?Sized {
+        f.write_str(self.message())
+    }
+}
+
+/// Two uppercase hex digits
+fn write_hex<W>(f: &mut Formatter<'_, W>, byte: u8) -> Result<(), W::Error> where W: uWrite + ?Sized {
+    const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    f.write_char(DIGITS[(byte >> 4) as usize] as char)?;
+    f.write_char(DIGITS[(byte & 0x0F) as usize] as char)
+}
@@ -7,6 +7,8 @@ use zerocopy::{
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum Band {
     FatShark = 0,
     RaceBand = 1,
@@ -17,6 +19,8 @@ pub enum Band {
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum Mode {
     Race = 0,
     Pit = 1,
@@ -24,6 +28,8 @@ pub enum Mode {
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum Power {
     Off = 0,
     P1To14Mw = 1,
@@ -37,6 +43,8 @@ pub enum Power {
 
 #[repr(u8)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub enum Region {
     Us = 0,
     Eu = 1,
@@ -45,6 +53,8 @@ pub enum Region {
 /// VTX Data
 #[repr(C, packed)]
 #[derive(KnownLayout, Immutable, TryFromBytes, IntoBytes)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
 pub struct VtxData {
     pub band: Band,
     pub channel: u8,
@@ -53,3 +63,22 @@ pub struct VtxData {
     pub power_dec: u16,
     pub region: Region,
 }
+
+impl VtxData {
+    pub fn new() -> Self {
+        Self {
+            band: Band::FatShark,
+            channel: 0,
+            pit: Mode::Race,
+            power: Power::Off,
+            power_dec: 0,
+            region: Region::Us,
+        }
+    }
+}
+
+impl Default for VtxData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,65 @@
+use srxl2::{
+    capture::{max_line_length, Record},
+    error::CaptureError,
+};
+
+#[test]
+fn format_and_parse_round_trip() {
+    let bytes = [0xA6, 0x21, 0x0E, 0x00, 0xFF];
+    let record = Record {
+        timestamp_us: 1_043_211,
+        bytes: &bytes,
+    };
+
+    let mut line = [0u8; max_line_length(5)];
+    let len = record.format(&mut line).unwrap();
+    let line = core::str::from_utf8(&line[..len]).unwrap();
+    assert_eq!(line, "1043211 A6210E00FF\n");
+
+    let mut buf = [0u8; 8];
+    let parsed = Record::parse(line, &mut buf).unwrap().unwrap();
+    assert_eq!(parsed.timestamp_us, 1_043_211);
+    assert_eq!(parsed.bytes, bytes);
+}
+
+#[test]
+fn format_needs_room_for_the_longest_line() {
+    let record = Record {
+        timestamp_us: 0,
+        bytes: &[1, 2],
+    };
+    let mut line = [0u8; max_line_length(2) - 1];
+    assert!(record.format(&mut line).is_none());
+
+    let mut line = [0u8; max_line_length(2)];
+    assert_eq!(record.format(&mut line), Some(7));
+    assert_eq!(&line[..7], b"0 0102\n");
+}
+
+#[test]
+fn parse_accepts_spaced_lowercase_hex() {
+    let mut buf = [0u8; 8];
+    let parsed = Record::parse("  42 a6 cd\t0f  ", &mut buf).unwrap().unwrap();
+    assert_eq!(parsed.timestamp_us, 42);
+    assert_eq!(parsed.bytes, [0xA6, 0xCD, 0x0F]);
+
+    let parsed = Record::parse("42", &mut buf).unwrap().unwrap();
+    assert!(parsed.bytes.is_empty());
+}
+
+#[test]
+fn parse_skips_comments_and_blank_lines() {
+    let mut buf = [0u8; 8];
+    assert!(Record::parse("# receiver bus", &mut buf).unwrap().is_none());
+    assert!(Record::parse("   ", &mut buf).unwrap().is_none());
+}
+
+#[test]
+fn parse_rejects_bad_lines() {
+    let mut buf = [0u8; 2];
+    assert!(matches!(Record::parse("A6 21", &mut buf), Err(CaptureError::Timestamp)));
+    assert!(matches!(Record::parse("-5 A6", &mut buf), Err(CaptureError::Timestamp)));
+    assert!(matches!(Record::parse("5 A6G1", &mut buf), Err(CaptureError::Hex)));
+    assert!(matches!(Record::parse("5 A62", &mut buf), Err(CaptureError::Hex)));
+    assert!(matches!(Record::parse("5 A62101", &mut buf), Err(CaptureError::TooLong)));
+}
@@ -0,0 +1,118 @@
+use srxl2::channel::{ChannelData, ChannelValue, Normalized};
+
+/// Every value with the reserved bits clear
+fn all_values() -> impl Iterator<Item = ChannelValue> {
+    (0..=u16::MAX).step_by(4).map(ChannelValue::from_raw)
+}
+
+#[test]
+fn ends_and_center_in_us() {
+    assert_eq!(ChannelValue::MIN.to_us(), 1000);
+    assert_eq!(ChannelValue::CENTER.to_us(), 1500);
+    assert_eq!(ChannelValue::MAX.to_us(), 2000);
+
+    assert_eq!(ChannelValue::from_us(1000), ChannelValue::MIN);
+    assert_eq!(ChannelValue::from_us(1500), ChannelValue::CENTER);
+    assert_eq!(ChannelValue::from_us(2000), ChannelValue::MAX);
+}
+
+#[test]
+fn us_round_trips() {
+    for us in 1000..=2000 {
+        assert_eq!(ChannelValue::from_us(us).to_us(), us, "{us}µs");
+    }
+    for value in all_values() {
+        let back = ChannelValue::from_us(value.to_us());
+        assert!(back.raw().abs_diff(value.raw()) <= 33, "{value:?} came back as {back:?}");
+    }
+}
+
+#[test]
+fn us_is_clamped() {
+    // ±150% travel, and beyond
+    for us in [0, 750, 999] {
+        assert_eq!(ChannelValue::from_us(us), ChannelValue::MIN, "{us}µs");
+    }
+    for us in [2001, 2250, u16::MAX] {
+        assert_eq!(ChannelValue::from_us(us), ChannelValue::MAX, "{us}µs");
+    }
+}
+
+#[test]
+fn ends_and_center_in_percent() {
+    assert_eq!(ChannelValue::MIN.to_percent(), -100);
+    assert_eq!(ChannelValue::CENTER.to_percent(), 0);
+    assert_eq!(ChannelValue::MAX.to_percent(), 100);
+
+    assert_eq!(ChannelValue::from_percent(-100), ChannelValue::MIN);
+    assert_eq!(ChannelValue::from_percent(0), ChannelValue::CENTER);
+    assert_eq!(ChannelValue::from_percent(100), ChannelValue::MAX);
+}
+
+#[test]
+fn percent_round_trips() {
+    for percent in -100..=100 {
+        assert_eq!(ChannelValue::from_percent(percent).to_percent(), percent, "{percent}%");
+    }
+    for value in all_values() {
+        let back = ChannelValue::from_percent(value.to_percent());
+        assert!(back.raw().abs_diff(value.raw()) <= 164, "{value:?} came back as {back:?}");
+    }
+}
+
+#[test]
+fn percent_is_clamped() {
+    for percent in [-128, -101] {
+        assert_eq!(ChannelValue::from_percent(percent), ChannelValue::MIN, "{percent}%");
+    }
+    for percent in [101, 127] {
+        assert_eq!(ChannelValue::from_percent(percent), ChannelValue::MAX, "{percent}%");
+    }
+}
+
+#[test]
+fn percent_rounds_the_same_both_ways() {
+    // +100% saturates at MAX, one step short of mirroring MIN
+    for percent in 1..100 {
+        let up = ChannelValue::from_percent(percent).raw() - ChannelValue::CENTER.raw();
+        let down = ChannelValue::CENTER.raw() - ChannelValue::from_percent(-percent).raw();
+        assert_eq!(up, down, "{percent}%");
+    }
+    for value in all_values() {
+        let mirrored = ChannelValue::from_raw(0u16.wrapping_sub(value.raw()));
+        if value != ChannelValue::MIN {
+            assert_eq!(mirrored.to_percent(), -value.to_percent(), "{value:?}");
+        }
+    }
+}
+
+#[test]
+fn normalized_ends_and_center() {
+    assert_eq!(ChannelValue::MIN.to_normalized(), Normalized::MINUS_ONE);
+    assert_eq!(ChannelValue::CENTER.to_normalized(), Normalized::ZERO);
+    assert_eq!(ChannelValue::MAX.to_normalized(), Normalized(i16::MAX - 3));
+
+    assert_eq!(ChannelValue::from_normalized(Normalized::MINUS_ONE), ChannelValue::MIN);
+    assert_eq!(ChannelValue::from_normalized(Normalized::ZERO), ChannelValue::CENTER);
+    assert_eq!(ChannelValue::from_normalized(Normalized::ONE), ChannelValue::MAX);
+}
+
+#[test]
+fn normalized_round_trips() {
+    for value in all_values() {
+        assert_eq!(ChannelValue::from_normalized(value.to_normalized()), value);
+    }
+    // the reserved bits are dropped on the way back
+    for fraction in [-32767, -3, 1, 2, 3, 12345] {
+        let value = ChannelValue::from_normalized(Normalized(fraction));
+        assert_eq!(value.to_normalized(), Normalized(fraction & !3));
+    }
+}
+
+#[test]
+fn channel_data_default_is_empty() {
+    let data = ChannelData::default();
+    assert_eq!({ data.mask }, 0);
+    assert_eq!(data.iter().count(), 0);
+    assert_eq!(data.get(0), None);
+}
@@ -0,0 +1,192 @@
+//! CRSF frames built and parsed on the host: CRC, channel packing and scaling, link
+//! statistics, battery and flight mode telemetry, and resyncing after line noise.
+
+use proptest::prelude::*;
+use zerocopy::IntoBytes;
+
+use srxl2::{
+    channel::{ChannelData, ChannelValue},
+    crsf::{self, Address, Battery, Crsf, Frame, FrameBuf, FrameType, LinkStatistics, CHANNEL_CENTER, MAX_FRAME_LENGTH},
+    input::{ChannelInput, LinkState, RcInput},
+    rssi,
+};
+
+/// Every frame `bytes` holds, as (type, payload)
+fn parse(bytes: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    let mut crsf = Crsf::new();
+    bytes.iter()
+        .filter_map(|byte| crsf.push(*byte).map(|frame| (frame.frame_type, frame.payload.to_vec())))
+        .collect()
+}
+
+fn link_statistics(link_quality: u8) -> LinkStatistics {
+    LinkStatistics {
+        uplink_rssi_1: 45,
+        uplink_rssi_2: 130,
+        uplink_link_quality: link_quality,
+        uplink_snr: 9,
+        active_antenna: 0,
+        rf_mode: 4,
+        uplink_tx_power: 2,
+        downlink_rssi: 50,
+        downlink_link_quality: 100,
+        downlink_snr: 7,
+    }
+}
+
+#[test]
+fn crc_is_dvb_s2() {
+    assert_eq!(crsf::crc8(b"123456789"), 0xBC);
+    assert_eq!(crsf::crc8(&[]), 0);
+}
+
+#[test]
+fn frames_are_laid_out_as_address_length_type_payload_crc() {
+    let frame = FrameBuf::new(Address::FlightController, FrameType::FlightMode, b"ACRO\0");
+    let bytes = frame.as_slice();
+    assert_eq!(&bytes[..8], &[0xC8, 7, 0x21, b'A', b'C', b'R', b'O', 0]);
+    assert_eq!(bytes[8], crsf::crc8(&bytes[2..8]));
+    assert_eq!(bytes.len(), 9);
+
+    let parsed = Frame::try_from_slice(bytes).unwrap();
+    assert_eq!(parsed.address, 0xC8);
+    assert_eq!(parsed.kind(), Some(FrameType::FlightMode));
+    assert_eq!(parsed.as_flight_mode(), Some("ACRO"));
+    assert_eq!(parsed.as_battery(), None);
+
+    let mut corrupt = bytes.to_vec();
+    corrupt[4] ^= 1;
+    assert_eq!(Frame::try_from_slice(&corrupt), None);
+}
+
+#[test]
+fn channels_scale_onto_the_srxl2_range() {
+    assert_eq!(crsf::to_channel_value(CHANNEL_CENTER), ChannelValue::CENTER);
+    assert_eq!(crsf::to_channel_value(172).raw(), 0x8000 - 820 * 32);
+    assert_eq!(crsf::to_channel_value(1811).raw(), 0x8000 + 819 * 32);
+    assert_eq!(crsf::to_channel_value(0), ChannelValue::from_raw(0x8000 - 992 * 32));
+    assert_eq!(crsf::to_channel_value(2047), ChannelValue::MAX);
+    assert_eq!(crsf::from_channel_value(ChannelValue::CENTER), CHANNEL_CENTER);
+    for raw in [0, 172, 992, 1500, 1811] {
+        assert_eq!(crsf::from_channel_value(crsf::to_channel_value(raw)), raw);
+    }
+}
+
+#[test]
+fn channels_reach_channel_input() {
+    let mut sent = ChannelData::new();
+    sent.set(0, crsf::to_channel_value(172));
+    sent.set(2, crsf::to_channel_value(1500));
+    let frame = FrameBuf::channels(&sent);
+    assert_eq!(frame.as_slice().len(), 26);
+
+    let mut input = ChannelInput::new(100);
+    let mut crsf = Crsf::new();
+    for byte in frame.as_slice() {
+        if let Some(frame) = crsf.push(*byte) {
+            input.update_from_crsf(&frame, 10);
+        }
+    }
+    assert_eq!(input.link_state(10), LinkState::Connected);
+    assert_eq!(input.channel_count(), 16);
+    assert_eq!(input.channels.get(0), sent.get(0));
+    assert_eq!(input.channels.get(1), Some(ChannelValue::CENTER));
+    assert_eq!(input.channels.get(2), sent.get(2));
+}
+
+#[test]
+fn link_statistics_map_onto_srxl2_signal_quality() {
+    let stats = link_statistics(87);
+    let quality = stats.signal_quality();
+    assert_eq!(quality.request, rssi::Request::Send);
+    assert_eq!((quality.antenna_a, quality.antenna_b), (-45, -128));
+
+    let mut input = ChannelInput::new(100);
+    for frame in [FrameBuf::channels(&ChannelData::new()), FrameBuf::new(Address::FlightController, FrameType::LinkStatistics, stats.as_bytes())] {
+        input.update_from_crsf(&Frame::try_from_slice(frame.as_slice()).unwrap(), 0);
+    }
+    assert_eq!(input.channels.rssi, 87);
+    assert_eq!(input.link_state(0), LinkState::Connected);
+
+    let lost = FrameBuf::new(Address::FlightController, FrameType::LinkStatistics, link_statistics(0).as_bytes());
+    let lost = Frame::try_from_slice(lost.as_slice()).unwrap();
+    assert_eq!(lost.as_link_statistics(), Some(&link_statistics(0)));
+    input.update_from_crsf(&lost, 5);
+    assert!(input.is_failsafe(5));
+}
+
+#[test]
+fn battery_telemetry_round_trip() {
+    let battery = Battery::new(126, 153, 1_234_567, 42);
+    let frame = FrameBuf::battery(&battery);
+    let bytes = frame.as_slice();
+    // Voltage, current, and capacity are big-endian
+    assert_eq!(&bytes[..11], &[0xC8, 10, 0x08, 0, 126, 0, 153, 0x12, 0xD6, 0x87, 42]);
+
+    let parsed = Frame::try_from_slice(bytes).unwrap();
+    let received = parsed.as_battery().unwrap();
+    assert_eq!(received, &battery);
+    assert_eq!(received.voltage.get(), 126);
+    assert_eq!(received.capacity_mah(), 1_234_567);
+}
+
+#[test]
+fn long_flight_modes_are_cut_short() {
+    let name = "X".repeat(100);
+    let frame = FrameBuf::flight_mode(&name);
+    assert_eq!(frame.as_slice().len(), MAX_FRAME_LENGTH);
+    let parsed = Frame::try_from_slice(frame.as_slice()).unwrap();
+    assert_eq!(parsed.as_flight_mode().unwrap().len(), MAX_FRAME_LENGTH - 5);
+}
+
+#[test]
+fn parser_resyncs_after_noise_and_bad_frames() {
+    let mode = FrameBuf::flight_mode("ANGL");
+    let battery = FrameBuf::battery(&Battery::new(111, 0, 0, 100));
+    let mut corrupt = battery.as_slice().to_vec();
+    corrupt[5] ^= 0x40;
+
+    let mut stream = vec![0x00, 0x55, 0xC8, 0xFF];
+    stream.extend(&corrupt);
+    stream.extend(mode.as_slice());
+    stream.extend(battery.as_slice());
+    let frames = parse(&stream);
+    assert_eq!(frames, vec![
+        (FrameType::FlightMode as u8, b"ANGL\0".to_vec()),
+        (FrameType::Battery as u8, battery.as_slice()[3..11].to_vec()),
+    ]);
+}
+
+proptest! {
+    #[test]
+    fn channels_round_trip(raw in prop::array::uniform16(0..2048u16)) {
+        let mut channels = ChannelData::new();
+        for (index, raw) in raw.iter().enumerate() {
+            channels.set(index, crsf::to_channel_value(*raw));
+        }
+        let frame = FrameBuf::channels(&channels);
+        let parsed = Frame::try_from_slice(frame.as_slice()).unwrap();
+        let expected: Vec<u16> = raw.iter().map(|raw| crsf::from_channel_value(crsf::to_channel_value(*raw))).collect();
+        prop_assert_eq!(parsed.as_channels().unwrap().to_vec(), expected);
+    }
+
+    #[test]
+    fn any_payload_round_trips(frame_type: u8, payload in prop::collection::vec(any::<u8>(), 0..60)) {
+        let mut bytes = vec![0xEC, payload.len() as u8 + 2, frame_type];
+        bytes.extend(&payload);
+        bytes.push(crsf::crc8(&bytes[2..]));
+        prop_assert_eq!(parse(&bytes), vec![(frame_type, payload)]);
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+        let mut crsf = Crsf::new();
+        let mut input = ChannelInput::new(100);
+        for byte in bytes {
+            if let Some(frame) = crsf.push(byte) {
+                let _ = (frame.as_channels(), frame.as_link_statistics(), frame.as_battery(), frame.as_flight_mode());
+                input.update_from_crsf(&frame, 0);
+            }
+        }
+    }
+}
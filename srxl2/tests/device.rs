@@ -0,0 +1,72 @@
+use srxl2::device::{DeviceId, DeviceType};
+
+const TYPES: [DeviceType; 12] = [
+    DeviceType::None,
+    DeviceType::RemoteReceiver,
+    DeviceType::Receiver,
+    DeviceType::FlightController,
+    DeviceType::ESC,
+    DeviceType::SRXLServo1,
+    DeviceType::SRXLServo2,
+    DeviceType::VTX,
+    DeviceType::ExtRF,
+    DeviceType::RemoteId,
+    DeviceType::Sensor,
+    DeviceType::Broadcast,
+];
+
+#[test]
+fn type_and_unit_round_trip() {
+    for device_type in TYPES {
+        for unit in 0..16 {
+            let id = DeviceId::new(device_type, unit);
+            assert_eq!(id.device_type(), Some(device_type));
+            assert_eq!(id.unit(), unit);
+        }
+    }
+}
+
+#[test]
+fn unit_is_the_lower_nibble() {
+    assert_eq!(DeviceId::new(DeviceType::ESC, 0x13).unit(), 0x3);
+    assert_eq!(DeviceId::from(0x21).unit(), 1);
+    assert_eq!(DeviceId::from(0x5A).unit(), 0xA);
+    assert_eq!(u8::from(DeviceId::new(DeviceType::Sensor, 0x1F)), 0xBF);
+}
+
+#[test]
+fn unassigned_type_nibbles_have_no_type() {
+    for nibble in [0x5, 0xC, 0xD, 0xE] {
+        for unit in 0..16 {
+            let id = DeviceId::from((nibble << 4) | unit);
+            assert_eq!(id.device_type(), None);
+            assert!(!id.is_default_for_type());
+        }
+    }
+}
+
+#[test]
+fn default_ids() {
+    assert_eq!(u8::from(DeviceType::Receiver.default_id()), 0x21);
+    assert_eq!(u8::from(DeviceType::FlightController.default_id()), 0x30);
+    assert_eq!(u8::from(DeviceType::VTX.default_id()), 0x81);
+    assert!(DeviceType::Broadcast.default_id().is_broadcast());
+
+    for device_type in TYPES {
+        let default_id = device_type.default_id();
+        assert_eq!(default_id.device_type(), Some(device_type));
+        assert!(default_id.is_default_for_type());
+
+        let others = (0..16).filter(|&unit| unit != default_id.unit());
+        for unit in others {
+            assert!(!DeviceId::new(device_type, unit).is_default_for_type());
+        }
+    }
+}
+
+#[test]
+fn broadcast() {
+    assert!(DeviceId::broadcast().is_broadcast());
+    assert_eq!(DeviceId::broadcast().device_type(), Some(DeviceType::Broadcast));
+    assert!(!DeviceId::new(DeviceType::Broadcast, 0).is_broadcast());
+}
@@ -0,0 +1,118 @@
+use srxl2::{
+    bind::BindOption,
+    flags::Flags,
+    handshake::{Baud, BaudRates},
+    sbus::SbusFlag,
+};
+
+#[test]
+fn insert_remove_and_has() {
+    let mut flags = Flags::empty();
+    assert!(flags.is_empty());
+    assert!(!flags.has(SbusFlag::FrameLost));
+
+    flags.insert(SbusFlag::FrameLost);
+    flags.insert(SbusFlag::FrameLost);
+    assert_eq!(flags.bits(), 0x04);
+    assert!(flags.has(SbusFlag::FrameLost));
+    assert!(!flags.has(SbusFlag::Failsafe));
+
+    flags.remove(SbusFlag::Failsafe);
+    assert_eq!(flags.bits(), 0x04);
+    flags.remove(SbusFlag::FrameLost);
+    assert!(flags.is_empty());
+}
+
+#[test]
+fn toggle_and_set() {
+    let mut flags = Flags::from(SbusFlag::Channel17);
+    flags.toggle(SbusFlag::Channel18);
+    assert_eq!(flags.bits(), 0x03);
+    flags.toggle(SbusFlag::Channel17);
+    assert_eq!(flags.bits(), 0x02);
+
+    flags.set(SbusFlag::Failsafe, true);
+    flags.set(SbusFlag::Failsafe, true);
+    assert_eq!(flags.bits(), 0x0A);
+    flags.set(SbusFlag::Channel18, false);
+    flags.set(SbusFlag::Channel17, false);
+    assert_eq!(flags.bits(), 0x08);
+}
+
+#[test]
+fn has_all_and_has_any() {
+    let flags = Flags::from([SbusFlag::Channel17, SbusFlag::FrameLost]);
+    assert!(flags.has_all(Flags::from(SbusFlag::FrameLost)));
+    assert!(flags.has_all(flags));
+    assert!(!flags.has_all(Flags::from([SbusFlag::FrameLost, SbusFlag::Failsafe])));
+    assert!(flags.has_any(Flags::from([SbusFlag::FrameLost, SbusFlag::Failsafe])));
+    assert!(!flags.has_any(Flags::from(SbusFlag::Failsafe)));
+    assert!(!flags.has_any(Flags::empty()));
+}
+
+#[test]
+fn iter_is_lowest_bit_first_and_skips_unknown_bits() {
+    let flags = Flags::<SbusFlag>::from_bits(0xF9);
+    assert_eq!(flags.bits(), 0xF9);
+    assert_eq!(flags.iter().collect::<Vec<_>>(), [SbusFlag::Channel17, SbusFlag::Failsafe]);
+    assert_eq!((&flags).into_iter().count(), 2);
+    assert_eq!(Flags::<SbusFlag>::empty().iter().next(), None);
+}
+
+#[test]
+fn from_iterator_and_arrays() {
+    let flags: Flags<SbusFlag> = [SbusFlag::Failsafe, SbusFlag::Channel18, SbusFlag::Failsafe].into_iter().collect();
+    assert_eq!(flags.bits(), 0x0A);
+    assert_eq!(Flags::from([SbusFlag::Failsafe, SbusFlag::Channel18]), flags);
+    assert!(Flags::<SbusFlag>::from_iter([]).is_empty());
+    assert_eq!(Flags::<SbusFlag>::default(), Flags::empty());
+}
+
+#[test]
+fn bit_or_and_bit_and() {
+    let a: Flags<SbusFlag> = Flags::from([SbusFlag::Channel17, SbusFlag::FrameLost]);
+    let b = Flags::from([SbusFlag::FrameLost, SbusFlag::Failsafe]);
+    assert_eq!((a | b).bits(), 0x0D);
+    assert_eq!((a & b).bits(), 0x04);
+    assert_eq!((a | SbusFlag::Channel18).bits(), 0x07);
+
+    let mut c = a;
+    c |= b;
+    assert_eq!(c, a | b);
+    c &= Flags::from(SbusFlag::Failsafe);
+    assert_eq!(c.bits(), 0x08);
+}
+
+#[test]
+fn zero_variant_is_the_empty_set() {
+    let mut flags = Flags::from(BindOption::None);
+    assert!(flags.is_empty());
+    assert!(flags.has(BindOption::None));
+    flags.insert(BindOption::UsPower);
+    assert_eq!(flags.iter().collect::<Vec<_>>(), [BindOption::UsPower]);
+}
+
+#[test]
+fn baud_rates() {
+    let slow = BaudRates::from(Baud::Baud115200);
+    let fast = BaudRates::from([Baud::Baud115200, Baud::Baud400000]);
+    assert_eq!(slow.bits(), 0);
+    assert_eq!(fast.bits(), 1);
+    assert_eq!(BaudRates::default(), slow);
+
+    assert!(slow.supports(Baud::Baud115200));
+    assert!(!slow.supports(Baud::Baud400000));
+    assert!(fast.supports(Baud::Baud400000));
+    assert_eq!(slow.iter().collect::<Vec<_>>(), [Baud::Baud115200]);
+    assert_eq!(fast.iter().collect::<Vec<_>>(), [Baud::Baud115200, Baud::Baud400000]);
+
+    assert_eq!(slow.fastest(), Baud::Baud115200);
+    assert_eq!(fast.fastest(), Baud::Baud400000);
+    assert_eq!((fast & slow).fastest(), Baud::Baud115200);
+
+    let mut negotiated = fast;
+    negotiated &= BaudRates::from_bits(0xFF);
+    assert_eq!(negotiated, fast);
+    negotiated &= slow;
+    assert_eq!(negotiated, slow);
+}
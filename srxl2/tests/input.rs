@@ -0,0 +1,121 @@
+//! SRXL2 and satellite channels read through the protocol-independent `RcInput` view.
+
+use srxl2::{
+    channel::ChannelValue,
+    device::{DeviceId, DeviceInfo, DeviceType},
+    flags::Flags,
+    handshake::{Baud, BaudRates},
+    input::{ChannelInput, LinkState, RcInput},
+    satellite::{Satellite, FRAME_LENGTH},
+    sim::SimBus,
+    Srxl2Interpreter,
+};
+
+/// Frames missing for longer than this count as a lost link
+const TIMEOUT_MS: u32 = 100;
+
+fn device(device_id: DeviceId, priority: u8) -> Srxl2Interpreter {
+    let mut srxl = Srxl2Interpreter::new();
+    srxl.init_device(device_id, priority, Flags::from(DeviceInfo::TelemTxEnabled), 0x1234_5678);
+    srxl.init_bus(0, 0, BaudRates::from([Baud::Baud115200, Baud::Baud400000]));
+    srxl
+}
+
+/// What robot code sees: only the trait
+fn summary(input: &dyn RcInput, now_ms: u32) -> (LinkState, bool, usize) {
+    (input.link_state(now_ms), input.is_failsafe(now_ms), input.channel_count())
+}
+
+/// Runs an 11ms frame on the bus, then hands the robot's channels to `input`
+fn run_frame(sim: &mut SimBus, input: &mut ChannelInput) {
+    sim.run_for_ms(11);
+    let now_ms = sim.now_ms() as u32;
+    input.update_from_srxl2(sim.node_mut(1), now_ms);
+}
+
+#[test]
+fn srxl2_channels_and_failsafe() {
+    let mut sim = SimBus::new(1);
+    sim.add_node(device(DeviceId::new(DeviceType::Receiver, 1), 0));
+    sim.add_node(device(DeviceId::new(DeviceType::FlightController, 0), 30));
+    let mut input = ChannelInput::new(TIMEOUT_MS);
+
+    input.update_from_srxl2(sim.node_mut(1), 0);
+    assert_eq!(summary(&input, 0), (LinkState::NoSignal, true, 0));
+    assert_eq!(input.frame_age_ms(0), None);
+    sim.run_for_ms(300);
+
+    let value = ChannelValue::from_percent(50);
+    for _ in 0..3 {
+        let receiver = sim.node_mut(0);
+        for channel in 0..4 {
+            receiver.channel_data.set(channel, value);
+        }
+        receiver.set_outgoing_channel_mask(0b1111);
+        run_frame(&mut sim, &mut input);
+    }
+    let now_ms = sim.now_ms() as u32;
+    assert_eq!(summary(&input, now_ms), (LinkState::Connected, false, 4));
+    assert_eq!(input.channel(3), Some(value.to_normalized()));
+    assert_eq!(input.channel(4), None);
+    assert!(input.frame_age_ms(now_ms).unwrap() < 11);
+
+    // The receiver loses the transmitter and, after its hold time, sends failsafe data
+    for _ in 0..45 {
+        sim.node_mut(0).on_frame_error(0);
+        run_frame(&mut sim, &mut input);
+    }
+    let now_ms = sim.now_ms() as u32;
+    assert_eq!(summary(&input, now_ms), (LinkState::Lost, true, 4));
+    assert!(input.frame_age_ms(now_ms).unwrap() < 11);
+}
+
+#[test]
+fn srxl2_link_is_lost_when_frames_stop() {
+    let mut sim = SimBus::new(1);
+    sim.add_node(device(DeviceId::new(DeviceType::Receiver, 1), 0));
+    sim.add_node(device(DeviceId::new(DeviceType::FlightController, 0), 30));
+    let mut input = ChannelInput::new(TIMEOUT_MS);
+
+    sim.run_for_ms(300);
+    sim.node_mut(0).channel_data.set(0, ChannelValue::CENTER);
+    sim.node_mut(0).set_outgoing_channel_mask(0b1);
+    run_frame(&mut sim, &mut input);
+    let last_frame_ms = sim.now_ms() as u32;
+    assert_eq!(input.link_state(last_frame_ms), LinkState::Connected);
+
+    sim.set_connected(0, false);
+    for _ in 0..20 {
+        run_frame(&mut sim, &mut input);
+    }
+    let now_ms = sim.now_ms() as u32;
+    assert!(input.frame_age_ms(now_ms).unwrap() > TIMEOUT_MS);
+    assert_eq!(summary(&input, now_ms), (LinkState::Lost, true, 1));
+}
+
+#[test]
+fn satellite_channels_and_hold() {
+    let mut bytes = [0xFF; FRAME_LENGTH];
+    bytes[..2].copy_from_slice(&[0, 0xA2]);
+    for channel in 0..6u16 {
+        let servo = channel << 11 | 1024;
+        bytes[2 + channel as usize * 2..4 + channel as usize * 2].copy_from_slice(&servo.to_be_bytes());
+    }
+
+    let mut satellite = Satellite::new();
+    let mut input = ChannelInput::new(1000);
+    let mut now_ms = 0;
+    for _ in 0..10 {
+        for (index, byte) in bytes.iter().enumerate() {
+            let now_us = now_ms * 1000 + index as u32 * 80;
+            if let Some(frame) = satellite.push(*byte, now_us) {
+                input.update_from_satellite(&mut satellite, &frame, now_ms);
+            }
+        }
+        now_ms += 22;
+    }
+
+    assert_eq!(summary(&input, now_ms), (LinkState::Connected, false, 6));
+    assert_eq!(input.channel(5), Some(ChannelValue::CENTER.to_normalized()));
+    assert_eq!(summary(&input, now_ms + 1000), (LinkState::Lost, true, 6));
+}
@@ -0,0 +1,142 @@
+//! Logic capture blocks, encoded as the Uno sends them and picked back out of a byte
+//! stream.
+
+use proptest::prelude::*;
+
+use srxl2::{
+    error::LogicError,
+    logic::{encode_varint, Decoder, Event, Header, Status, Trigger, MAGIC, MAX_VARINT_BYTES},
+};
+
+fn varint(value: u32) -> Vec<u8> {
+    let mut out = [0; MAX_VARINT_BYTES];
+    let len = encode_varint(value, &mut out).unwrap();
+    out[..len].to_vec()
+}
+
+/// A whole block, as `LogicCapture::stream` sends it
+fn block(header: Header, runs: &[u32], status: Status) -> Vec<u8> {
+    let (bytes, len) = header.encode();
+    let mut out = bytes[..len].to_vec();
+    for run in runs {
+        out.extend(varint(*run));
+    }
+    out.extend(status.encode());
+    out
+}
+
+fn decode(bytes: &[u8]) -> Vec<Result<Event, LogicError>> {
+    let mut decoder = Decoder::new();
+    bytes.iter().filter_map(|byte| decoder.push(*byte).transpose()).collect()
+}
+
+#[test]
+fn varints() {
+    assert_eq!(varint(0), [0x00]);
+    assert_eq!(varint(0x7F), [0x7F]);
+    assert_eq!(varint(0x80), [0x80, 0x01]);
+    // A bit at 115200 baud
+    assert_eq!(varint(139), [0x8B, 0x01]);
+    assert_eq!(varint(u32::MAX), [0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+    assert_eq!(encode_varint(0x80, &mut [0; 1]), None);
+    assert_eq!(encode_varint(1, &mut []), None);
+}
+
+#[test]
+fn headers() {
+    let header = Header { level: true, trigger: Trigger::FallingEdge };
+    let (bytes, len) = header.encode();
+    assert_eq!(&bytes[..len], b"LCAP\x01\x01");
+
+    // 1ms
+    let header = Header { level: false, trigger: Trigger::IdleGap(16_000) };
+    let (bytes, len) = header.encode();
+    assert_eq!(&bytes[..len], b"LCAP\x00\x02\x80\x7D");
+    assert_eq!(Status::EdgesLost.encode(), [0, 2]);
+}
+
+#[test]
+fn blocks_round_trip() {
+    let header = Header { level: false, trigger: Trigger::IdleGap(16_000) };
+    let runs = [139, 139 * 7, 1, 100_000];
+    let events = decode(&block(header, &runs, Status::Idle));
+    let mut expected = vec![Ok(Event::Start(header))];
+    expected.extend(runs.iter().map(|run| Ok(Event::Run(*run))));
+    expected.push(Ok(Event::End(Status::Idle)));
+    assert_eq!(events, expected);
+}
+
+#[test]
+fn blocks_are_found_among_other_output() {
+    let header = Header { level: true, trigger: Trigger::Immediate };
+    let mut bytes = b"Capturing on D8\r\nLCLLCAP".to_vec();
+    // That last "LCAP" was a real magic, but its level isn't
+    bytes.push(7);
+    bytes.extend(block(header, &[5], Status::Full));
+    bytes.extend(b"\r\n");
+
+    let mut decoder = Decoder::new();
+    let mut events = Vec::new();
+    for byte in &bytes {
+        events.push(decoder.push(*byte));
+    }
+    let events: Vec<_> = events.into_iter().filter_map(Result::transpose).collect();
+    assert_eq!(events, vec![
+        Err(LogicError::Header),
+        Ok(Event::Start(header)),
+        Ok(Event::Run(5)),
+        Ok(Event::End(Status::Full)),
+    ]);
+    assert!(!decoder.in_block());
+}
+
+#[test]
+fn bad_blocks() {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend([1, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F]);
+    assert_eq!(decode(&bytes).last(), Some(&Err(LogicError::Number)));
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend([1, 3]);
+    assert_eq!(decode(&bytes), vec![Err(LogicError::Header)]);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend([0, 0, 0, 9]);
+    assert_eq!(decode(&bytes).last(), Some(&Err(LogicError::Status)));
+}
+
+fn any_trigger() -> impl Strategy<Value = Trigger> {
+    prop_oneof![Just(Trigger::Immediate), Just(Trigger::FallingEdge), any::<u32>().prop_map(Trigger::IdleGap)]
+}
+
+fn any_status() -> impl Strategy<Value = Status> {
+    prop_oneof![Just(Status::Idle), Just(Status::Full), Just(Status::EdgesLost)]
+}
+
+proptest! {
+    #[test]
+    fn varints_round_trip(value: u32) {
+        let mut decoder = Decoder::new();
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([0, 2]);
+        bytes.extend(varint(value));
+        let events: Vec<_> = bytes.iter().filter_map(|byte| decoder.push(*byte).unwrap()).collect();
+        prop_assert_eq!(events, vec![Event::Start(Header { level: false, trigger: Trigger::IdleGap(value) })]);
+    }
+
+    #[test]
+    fn any_block_round_trips(level: bool, trigger in any_trigger(), runs in prop::collection::vec(1..=u32::MAX, 0..64), status in any_status()) {
+        let header = Header { level, trigger };
+        let events = decode(&block(header, &runs, status));
+        prop_assert_eq!(events.len(), runs.len() + 2);
+        prop_assert_eq!(events[0], Ok(Event::Start(header)));
+        prop_assert_eq!(events[events.len() - 1], Ok(Event::End(status)));
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+        let mut stream = MAGIC.to_vec();
+        stream.extend(bytes);
+        decode(&stream);
+    }
+}
@@ -0,0 +1,218 @@
+//! Tests for the internal echo tests a device can run against others on the bus, driven
+//! by hand from the receiver's side so replies can be withheld or tampered with.
+
+use zerocopy::IntoBytes;
+
+use srxl2::{
+    device::{DeviceId, DeviceInfo, DeviceType},
+    flags::Flags,
+    handshake::{Baud, BaudRates},
+    internal::{InternalData, InternalTest, LoopbackStats, State, LOOPBACK_TIMEOUT_MS, MAX_LOOPBACK_DEVICES},
+    packet::{Packet, PacketType},
+    Srxl2Interpreter,
+    Transport,
+};
+
+const RECEIVER: DeviceId = DeviceId::new(DeviceType::Receiver, 1);
+const ROBOT: DeviceId = DeviceId::new(DeviceType::FlightController, 0);
+
+#[derive(Default)]
+struct Record {
+    sent: Vec<Vec<u8>>,
+    now_ms: Option<u32>,
+}
+
+impl Transport for Record {
+    fn send(&mut self, _uart: u8, packet: &[u8]) {
+        self.sent.push(packet.to_vec());
+    }
+
+    fn change_baud(&mut self, _uart: u8, _baud: Baud) { }
+
+    fn now_ms(&mut self) -> Option<u32> {
+        self.now_ms
+    }
+}
+
+/// A receiver alone on its bus, done with its handshake
+fn receiver(port: &mut Record) -> Srxl2Interpreter {
+    let mut srxl = Srxl2Interpreter::new();
+    assert!(srxl.init_device(RECEIVER, 0, Flags::from(DeviceInfo::TelemTxEnabled), 0x1234_5678));
+    assert!(srxl.init_bus(0, 0, BaudRates::from(Baud::Baud115200)));
+    run_ms(&mut srxl, port, 500);
+    assert_eq!(srxl.get_state(0), State::Running);
+    srxl
+}
+
+fn run_ms(srxl: &mut Srxl2Interpreter, port: &mut Record, ms: u16) {
+    for _ in 0..ms {
+        srxl.run(0, 1, port);
+    }
+}
+
+/// Sends an echo request to `dest` and returns the request as it went out
+fn request(srxl: &mut Srxl2Interpreter, port: &mut Record, dest: DeviceId) -> InternalData {
+    assert!(srxl.send_internal_data(0, dest));
+    port.sent.clear();
+    for _ in 0..100 {
+        srxl.run(0, 1, port);
+        let sent = port.sent.iter()
+            .filter_map(|bytes| Packet::try_from_slice(bytes).ok())
+            .find_map(|packet| packet.as_internal_ref().map(|packet| *packet.internal));
+        if let Some(internal) = sent {
+            assert_eq!(internal.test, InternalTest::EchoRequest);
+            assert_eq!(internal.dest_dev_id, dest);
+            return internal;
+        }
+    }
+    panic!("echo request was never sent");
+}
+
+/// Answers `request` as its destination would, with the given key
+fn reply(srxl: &mut Srxl2Interpreter, port: &mut Record, request: InternalData, key: u32) {
+    let reply = InternalData {
+        src_dev_id: request.dest_dev_id,
+        dest_dev_id: request.src_dev_id,
+        test: InternalTest::EchoReply,
+        key,
+    };
+    let packet = Packet::new(PacketType::Internal, reply.as_bytes());
+    assert!(srxl.parse_packet(0, packet.as_slice(), port));
+}
+
+#[test]
+fn echo_is_received() {
+    let mut port = Record::default();
+    let mut srxl = receiver(&mut port);
+
+    let sent = request(&mut srxl, &mut port, ROBOT);
+    run_ms(&mut srxl, &mut port, 3);
+    reply(&mut srxl, &mut port, sent, sent.key);
+
+    let stats = srxl.loopback_stats(ROBOT).unwrap();
+    assert_eq!((stats.sent, stats.received, stats.mismatched, stats.lost), (1, 1, 0, 0));
+    assert_eq!(stats.rtt_last_ms, 3);
+    assert_eq!(stats.error_pct(), 0);
+}
+
+#[test]
+fn rtt_is_timed_by_the_transport_clock() {
+    let mut port = Record { now_ms: Some(u32::MAX - 2), ..Record::default() };
+    let mut srxl = receiver(&mut port);
+
+    // the echo comes back before the next tick
+    let sent = request(&mut srxl, &mut port, ROBOT);
+    port.now_ms = Some(4);
+    reply(&mut srxl, &mut port, sent, sent.key);
+
+    let stats = srxl.loopback_stats(ROBOT).unwrap();
+    assert_eq!(stats.received, 1);
+    assert_eq!(stats.rtt_last_ms, 7);
+}
+
+#[test]
+fn unanswered_request_is_lost() {
+    let mut port = Record::default();
+    let mut srxl = receiver(&mut port);
+
+    request(&mut srxl, &mut port, ROBOT);
+    run_ms(&mut srxl, &mut port, LOOPBACK_TIMEOUT_MS - 1);
+    assert_eq!(srxl.loopback_stats(ROBOT).unwrap().lost, 0);
+    // still waiting, so another request can't start yet
+    assert!(!srxl.send_internal_data(0, ROBOT));
+
+    run_ms(&mut srxl, &mut port, 1);
+    let stats = srxl.loopback_stats(ROBOT).unwrap();
+    assert_eq!((stats.sent, stats.received, stats.mismatched, stats.lost), (1, 0, 0, 1));
+    assert_eq!(stats.error_pct(), 100);
+}
+
+#[test]
+fn unsent_request_is_lost() {
+    // a device that waits to be polled, with no receiver on the bus to poll it
+    let mut port = Record::default();
+    let mut srxl = Srxl2Interpreter::new();
+    assert!(srxl.init_device(ROBOT, 0, Flags::from(DeviceInfo::TelemTxEnabled), 0x1234_5678));
+    assert!(srxl.init_bus(0, 0, BaudRates::from(Baud::Baud115200)));
+    run_ms(&mut srxl, &mut port, 500);
+
+    assert!(srxl.send_internal_data(0, RECEIVER));
+    port.sent.clear();
+    run_ms(&mut srxl, &mut port, LOOPBACK_TIMEOUT_MS - 1);
+    assert!(!srxl.send_internal_data(0, RECEIVER));
+    run_ms(&mut srxl, &mut port, 1);
+
+    let stats = srxl.loopback_stats(RECEIVER).unwrap();
+    assert_eq!((stats.sent, stats.received, stats.mismatched, stats.lost), (0, 0, 0, 1));
+    assert!(port.sent.is_empty());
+    // the bus is free for the next test
+    assert!(srxl.send_internal_data(0, RECEIVER));
+}
+
+#[test]
+fn late_echo_is_ignored() {
+    let mut port = Record::default();
+    let mut srxl = receiver(&mut port);
+
+    let sent = request(&mut srxl, &mut port, ROBOT);
+    run_ms(&mut srxl, &mut port, LOOPBACK_TIMEOUT_MS);
+    reply(&mut srxl, &mut port, sent, sent.key);
+
+    let stats = srxl.loopback_stats(ROBOT).unwrap();
+    assert_eq!((stats.received, stats.lost), (0, 1));
+}
+
+#[test]
+fn wrong_key_is_mismatched() {
+    let mut port = Record::default();
+    let mut srxl = receiver(&mut port);
+
+    let sent = request(&mut srxl, &mut port, ROBOT);
+    reply(&mut srxl, &mut port, sent, sent.key ^ 1);
+
+    let stats = srxl.loopback_stats(ROBOT).unwrap();
+    assert_eq!((stats.sent, stats.received, stats.mismatched, stats.lost), (1, 0, 1, 0));
+
+    // the mismatch finished the test, so it isn't also counted as lost
+    run_ms(&mut srxl, &mut port, LOOPBACK_TIMEOUT_MS);
+    let stats = srxl.loopback_stats(ROBOT).unwrap();
+    assert_eq!(stats.lost, 0);
+
+    let sent = request(&mut srxl, &mut port, ROBOT);
+    reply(&mut srxl, &mut port, sent, sent.key);
+    assert_eq!(srxl.loopback_stats(ROBOT).unwrap().error_pct(), 50);
+}
+
+#[test]
+fn full_table_refuses_new_devices() {
+    let mut port = Record::default();
+    let mut srxl = receiver(&mut port);
+
+    let devices: Vec<DeviceId> = (0..=MAX_LOOPBACK_DEVICES as u8)
+        .map(|unit| DeviceId::new(DeviceType::ESC, unit))
+        .collect();
+    for &device in &devices[..MAX_LOOPBACK_DEVICES] {
+        let sent = request(&mut srxl, &mut port, device);
+        reply(&mut srxl, &mut port, sent, sent.key);
+    }
+
+    let extra = devices[MAX_LOOPBACK_DEVICES];
+    assert!(!srxl.send_internal_data(0, extra));
+    assert!(srxl.loopback_stats(extra).is_none());
+
+    // devices already in the table can still be tested
+    let sent = request(&mut srxl, &mut port, devices[0]);
+    reply(&mut srxl, &mut port, sent, sent.key);
+    assert_eq!(srxl.loopback_stats(devices[0]).unwrap().received, 2);
+
+    srxl.clear_loopback_stats();
+    assert!(srxl.send_internal_data(0, extra));
+}
+
+#[test]
+fn empty_stats() {
+    let stats = LoopbackStats::new(ROBOT);
+    assert_eq!(stats.sent, 0);
+    assert_eq!(stats.error_pct(), 0);
+    assert_eq!(stats.rtt_avg_ms(), 0);
+}
@@ -0,0 +1,340 @@
+//! Property tests for the packet parser: every packet type survives a build and parse
+//! round trip, and no input, however mangled, makes the parser or interpreter panic.
+
+use proptest::prelude::*;
+use zerocopy::IntoBytes;
+
+use srxl2::{
+    bind::{self, BindData, BindPayload},
+    channel::{ChannelData, ChannelValue, MAX_CHANNELS},
+    control::{self, CmdCode},
+    device::{DeviceId, DeviceInfo, DeviceType},
+    flags::Flags,
+    framer::Framer,
+    handshake::{Baud, BaudRates, HandshakeData},
+    internal::{InternalData, InternalTest},
+    packet::{Packet, PacketType, FRAMING_LENGTH, SRXL_MAX_BUFFER_SIZE},
+    param::{self, ParamPayload},
+    rssi::{self, RssiPayload},
+    sim::SimBus,
+    telemetry::{TelemetryData, TelemetryPayload},
+    vtx::{Band, Mode, Power, Region, VtxData},
+    Srxl2Interpreter,
+    Transport,
+};
+
+const MAX_PAYLOAD: usize = SRXL_MAX_BUFFER_SIZE - FRAMING_LENGTH;
+
+struct Discard;
+
+impl Transport for Discard {
+    fn send(&mut self, _uart: u8, _packet: &[u8]) { }
+    fn change_baud(&mut self, _uart: u8, _baud: Baud) { }
+}
+
+fn device_id() -> impl Strategy<Value = DeviceId> {
+    any::<u8>().prop_map(DeviceId::from)
+}
+
+fn packet_type() -> impl Strategy<Value = PacketType> {
+    prop::sample::select(vec![
+        PacketType::Handshake,
+        PacketType::BindInfo,
+        PacketType::ParamConfig,
+        PacketType::SignalQuality,
+        PacketType::TelemetryData,
+        PacketType::ControlData,
+        PacketType::Internal,
+    ])
+}
+
+/// Builds `packet` and reads it back from its wire bytes
+fn round_trip(packet: Packet) -> Packet {
+    Packet::try_from_slice(packet.as_slice()).unwrap()
+}
+
+/// Calls everything that looks inside a packet
+fn inspect(packet: &Packet) {
+    let _ = format!("{packet:?}");
+    let _ = (packet.payload(), packet.is_crc_valid());
+    let _ = packet.as_handshake_ref().map(|packet| format!("{packet:?}"));
+    let _ = packet.as_bind_ref().map(|packet| format!("{packet:?}"));
+    let _ = packet.as_param_ref().map(|packet| format!("{packet:?}"));
+    let _ = packet.as_rssi_ref().map(|packet| format!("{packet:?}"));
+    let _ = packet.as_telemetry_ref().map(|packet| format!("{packet:?}"));
+    let _ = packet.as_internal_ref().map(|packet| format!("{packet:?}"));
+    if let Some(control) = packet.as_control_ref() {
+        let _ = control.as_channel_ref().map(|packet| format!("{packet:?}"));
+        let _ = control.as_vtx_ref().map(|packet| format!("{packet:?}"));
+        let _ = control.as_fwd_pgm_ref().map(|packet| format!("{packet:?}"));
+    }
+}
+
+/// A receiver and a flight controller that have finished their handshake
+fn running_bus() -> SimBus {
+    let fast = BaudRates::from([Baud::Baud115200, Baud::Baud400000]);
+    let mut sim = SimBus::new(1);
+    for (device_id, priority) in [(DeviceId::new(DeviceType::Receiver, 1), 0), (DeviceId::new(DeviceType::FlightController, 0), 30)] {
+        let mut srxl = Srxl2Interpreter::new();
+        srxl.init_device(device_id, priority, Flags::from(DeviceInfo::TelemTxEnabled), 0x1234_5678);
+        srxl.init_bus(0, 0, fast);
+        sim.add_node(srxl);
+    }
+    sim.run_for_ms(300);
+    sim
+}
+
+proptest! {
+    #[test]
+    fn handshake_round_trip(
+        src_dev_id in device_id(),
+        dest_dev_id in device_id(),
+        priority: u8,
+        baud: u8,
+        info: u8,
+        uid: u32,
+    ) {
+        let handshake = HandshakeData {
+            src_dev_id,
+            dest_dev_id,
+            priority,
+            baud_supported: BaudRates::from_bits(baud),
+            info: Flags::from_bits(info),
+            uid,
+        };
+        let packet = round_trip(Packet::new(PacketType::Handshake, handshake.as_bytes()));
+        prop_assert_eq!(packet.as_handshake_ref().unwrap().handshake, &handshake);
+    }
+
+    #[test]
+    fn bind_round_trip(
+        request in prop::sample::select(vec![
+            bind::Request::Enter,
+            bind::Request::Status,
+            bind::Request::BoundData,
+            bind::Request::SetBind,
+        ]),
+        device_id in device_id(),
+        bind_type: u8,
+        options: u8,
+        guid: u64,
+        uid: u32,
+    ) {
+        let bind = BindPayload {
+            request,
+            device_id,
+            data: BindData {
+                bind_type,
+                options: Flags::from_bits(options),
+                guid,
+                uid,
+            },
+        };
+        let packet = round_trip(Packet::new(PacketType::BindInfo, bind.as_bytes()));
+        prop_assert_eq!(packet.as_bind_ref().unwrap().bind, &bind);
+    }
+
+    #[test]
+    fn param_round_trip(
+        request in prop::sample::select(vec![param::Request::Query, param::Request::Write]),
+        dest_dev_id in device_id(),
+        param_id: u32,
+        param_val: u32,
+    ) {
+        let param = ParamPayload {
+            request,
+            dest_dev_id,
+            param_id,
+            param_val,
+        };
+        let packet = round_trip(Packet::new(PacketType::ParamConfig, param.as_bytes()));
+        prop_assert_eq!(packet.as_param_ref().unwrap().param, &param);
+    }
+
+    #[test]
+    fn rssi_round_trip(
+        request in prop::sample::select(vec![rssi::Request::Request, rssi::Request::Send]),
+        antennas: [i8; 4],
+    ) {
+        let rssi = RssiPayload {
+            request,
+            antenna_a: antennas[0],
+            antenna_b: antennas[1],
+            antenna_c: antennas[2],
+            antenna_d: antennas[3],
+        };
+        let packet = round_trip(Packet::new(PacketType::SignalQuality, rssi.as_bytes()));
+        prop_assert_eq!(packet.as_rssi_ref().unwrap().rssi, &rssi);
+    }
+
+    #[test]
+    fn telemetry_round_trip(dest_dev in device_id(), sensor_id: u8, secondary_id: u8, data: [u8; 14]) {
+        let telemetry = TelemetryPayload {
+            dest_dev,
+            payload: TelemetryData {
+                sensor_id,
+                secondary_id,
+                data,
+            },
+        };
+        let packet = round_trip(Packet::new(PacketType::TelemetryData, telemetry.as_bytes()));
+        prop_assert_eq!(packet.as_telemetry_ref().unwrap().telemetry, &telemetry);
+    }
+
+    #[test]
+    fn internal_round_trip(
+        src_dev_id in device_id(),
+        dest_dev_id in device_id(),
+        test in prop::sample::select(vec![InternalTest::EchoRequest, InternalTest::EchoReply]),
+        key: u32,
+    ) {
+        let internal = InternalData {
+            src_dev_id,
+            dest_dev_id,
+            test,
+            key,
+        };
+        let packet = round_trip(Packet::new(PacketType::Internal, internal.as_bytes()));
+        prop_assert_eq!(packet.as_internal_ref().unwrap().internal, &internal);
+    }
+
+    #[test]
+    fn channel_round_trip(
+        failsafe: bool,
+        reply_id in device_id(),
+        rssi: i8,
+        frame_losses: u16,
+        set_mask: u32,
+        send_mask: u32,
+        values: [u16; MAX_CHANNELS],
+    ) {
+        let mut channels = ChannelData::new();
+        channels.rssi = rssi;
+        channels.frame_losses = frame_losses;
+        for (index, raw) in values.iter().enumerate() {
+            if set_mask & (1 << index) != 0 {
+                channels.set(index, ChannelValue::from_raw(*raw));
+            }
+        }
+
+        let cmd = if failsafe { CmdCode::ChannelFailsafe } else { CmdCode::Channel };
+        let (packed, len) = channels.packed(send_mask);
+        let packet = round_trip(control::packet(cmd, reply_id, &packed.as_bytes()[..len]));
+        let control = packet.as_control_ref().unwrap();
+        prop_assert_eq!(control.control.cmd, cmd);
+        prop_assert_eq!(control.control.reply_id, reply_id);
+
+        let mut received = ChannelData::new();
+        received.update_from_packed(control.as_channel_ref().unwrap().control.data);
+        prop_assert_eq!(received.rssi, rssi);
+        prop_assert_eq!({ received.frame_losses }, frame_losses);
+        for index in 0..MAX_CHANNELS {
+            let expected = if send_mask & (1 << index) != 0 { channels.get(index) } else { None };
+            prop_assert_eq!(received.get(index), expected);
+        }
+    }
+
+    #[test]
+    fn vtx_round_trip(
+        reply_id in device_id(),
+        band in prop::sample::select(vec![Band::FatShark, Band::RaceBand, Band::EBand, Band::BBand, Band::ABand]),
+        channel: u8,
+        pit in prop::sample::select(vec![Mode::Race, Mode::Pit]),
+        power in prop::sample::select(vec![
+            Power::Off,
+            Power::P1To14Mw,
+            Power::P15To99Mw,
+            Power::P26To99Mw,
+            Power::P100To299Mw,
+            Power::P300To600Mw,
+            Power::P601MwPlus,
+            Power::Manual,
+        ]),
+        power_dec: u16,
+        region in prop::sample::select(vec![Region::Us, Region::Eu]),
+    ) {
+        let vtx = VtxData {
+            band,
+            channel,
+            pit,
+            power,
+            power_dec,
+            region,
+        };
+        let packet = round_trip(control::packet(CmdCode::Vtx, reply_id, vtx.as_bytes()));
+        let control = packet.as_control_ref().unwrap();
+        prop_assert_eq!(control.as_vtx_ref().unwrap().control.data, &vtx);
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+        if let Ok(packet) = Packet::try_from_slice_unverified(&bytes) {
+            inspect(&packet);
+        }
+
+        let mut framer = Framer::new();
+        for byte in &bytes {
+            if let Some(Ok(packet)) = framer.push(*byte).map(Packet::try_from_slice_unverified) {
+                inspect(&packet);
+            }
+        }
+    }
+
+    #[test]
+    fn arbitrary_header_and_payload_never_panic(
+        header: [u8; 3],
+        payload in prop::collection::vec(any::<u8>(), 0..MAX_PAYLOAD),
+        crc: u16,
+    ) {
+        let mut bytes = header.to_vec();
+        bytes.extend(payload);
+        bytes.extend(crc.to_be_bytes());
+        if let Ok(packet) = Packet::try_from_slice_unverified(&bytes) {
+            inspect(&packet);
+        }
+    }
+
+    /// Packets that pass the CRC check but carry garbage, sent to devices in the middle
+    /// of a session
+    #[test]
+    fn interpreters_survive_valid_packets_with_garbage_payloads(
+        packets in prop::collection::vec(
+            (packet_type(), prop::collection::vec(any::<u8>(), 0..MAX_PAYLOAD), 0..20u64),
+            1..20,
+        ),
+    ) {
+        let mut sim = running_bus();
+        for (packet_type, payload, wait_ms) in packets {
+            let packet = Packet::new(packet_type, &payload);
+            for node in 0..2 {
+                sim.node_mut(node).parse_packet(0, packet.as_slice(), &mut Discard);
+            }
+            sim.run_for_ms(wait_ms);
+        }
+    }
+
+    /// Line noise, fed to devices that have just started up
+    #[test]
+    fn interpreters_survive_line_noise(
+        chunks in prop::collection::vec((prop::collection::vec(any::<u8>(), 0..100), -5..20i16), 1..20),
+    ) {
+        let mut master = Srxl2Interpreter::new();
+        master.init_device(DeviceId::new(DeviceType::Receiver, 1), 0, Flags::empty(), 1);
+        master.init_bus(0, 0, BaudRates::from([Baud::Baud115200, Baud::Baud400000]));
+        let mut slave = Srxl2Interpreter::new();
+        slave.init_device(DeviceId::new(DeviceType::ESC, 0), 10, Flags::empty(), 2);
+        slave.init_bus(0, 0, BaudRates::from(Baud::Baud115200));
+
+        let mut framer = Framer::new();
+        for (bytes, delta_ms) in chunks {
+            for byte in bytes {
+                if let Some(packet) = framer.push(byte) {
+                    master.parse_packet(0, packet, &mut Discard);
+                    slave.parse_packet(0, packet, &mut Discard);
+                }
+            }
+            master.run(0, delta_ms, &mut Discard);
+            slave.run(0, delta_ms, &mut Discard);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! PPM and PWM decoding from lists of edge timestamps, as a timer capture would record
+//! them.
+
+use proptest::prelude::*;
+
+use srxl2::{
+    channel::{ChannelData, ChannelValue},
+    input::{ChannelInput, LinkState, RcInput},
+    ppm::{Frame, Polarity, PpmDecoder, PwmDecoder, Step, Timing, HOLD_US, MAX_CHANNELS, MIN_GAP_US, SYNC_GAP_US},
+};
+
+const FRAME_US: u32 = 22_500;
+
+/// Leading edges of one PPM frame starting at `start_us`: one per channel, then the
+/// one that ends the last channel and starts the gap
+fn ppm_edges(start_us: u32, widths: &[u16]) -> Vec<u32> {
+    let mut edges = vec![start_us];
+    let mut time_us = start_us;
+    for width in widths {
+        time_us = time_us.wrapping_add(*width as u32);
+        edges.push(time_us);
+    }
+    edges
+}
+
+/// `frames` frames of `widths` starting at `start_us`, `FRAME_US` apart or further if
+/// they need more room for the gap
+fn ppm_stream(start_us: u32, widths: &[u16], frames: u32) -> Vec<u32> {
+    let length_us = widths.iter().map(|width| *width as u32).sum::<u32>() + SYNC_GAP_US;
+    let frame_us = FRAME_US.max(length_us);
+    (0..frames).flat_map(|frame| ppm_edges(start_us.wrapping_add(frame * frame_us), widths)).collect()
+}
+
+fn decode(decoder: &mut PpmDecoder, edges: &[u32]) -> Vec<Vec<u16>> {
+    edges.iter().filter_map(|edge| decoder.push(*edge)).map(|frame| frame.widths().to_vec()).collect()
+}
+
+#[test]
+fn ppm_frames_start_after_the_first_gap() {
+    let widths = [1500, 1000, 2000, 1234, 1100, 1900, 1500, 1500];
+    let mut decoder = PpmDecoder::new();
+    assert!(decoder.is_signal_lost(0));
+
+    // The first frame is joined mid-way and dropped, the second sets the channel count,
+    // and each frame is only complete at the first edge of the next one
+    let edges = ppm_stream(1_000, &widths, 5);
+    let frames = decode(&mut decoder, &edges[3..]);
+    assert_eq!(frames, vec![widths.to_vec(), widths.to_vec()]);
+    assert!(!decoder.is_signal_lost(1_000 + 4 * FRAME_US));
+    assert!(decoder.is_signal_lost(1_000 + 4 * FRAME_US + HOLD_US + 1));
+}
+
+#[test]
+fn ppm_glitches_drop_the_frame() {
+    let widths = [1500; 6];
+    let mut edges = ppm_stream(0, &widths, 7);
+    // A spike in the middle of the third frame's third channel
+    let frame = widths.len() + 1;
+    edges.insert(2 * frame + 3, edges[2 * frame + 2] + 300);
+    // And the fourth frame loses an edge, merging two channels into a false gap, which
+    // leaves the next frame with a channel count to confirm
+    edges.remove(3 * frame + 3);
+
+    let mut decoder = PpmDecoder::new();
+    let mut frames = Vec::new();
+    for (index, edge) in edges.iter().enumerate() {
+        if let Some(frame) = decoder.push(*edge) {
+            frames.push((index, frame.widths().to_vec()));
+        }
+    }
+    // Only the sixth frame, complete at the first edge of the seventh; the added and the
+    // lost edge leave that where it was
+    assert_eq!(frames, vec![(6 * frame, widths.to_vec())]);
+}
+
+#[test]
+fn ppm_needs_four_channels() {
+    let mut decoder = PpmDecoder::new();
+    assert!(decode(&mut decoder, &ppm_stream(0, &[1500, 1500, 1500], 5)).is_empty());
+    assert!(decoder.is_signal_lost(5 * FRAME_US));
+}
+
+#[test]
+fn ppm_survives_clock_wrap() {
+    let widths = [1000, 1200, 1400, 1600, 1800, 2000];
+    let mut decoder = PpmDecoder::new();
+    let frames = decode(&mut decoder, &ppm_stream(u32::MAX - 2 * FRAME_US, &widths, 5));
+    assert_eq!(frames, vec![widths.to_vec(); 2]);
+}
+
+#[test]
+fn pwm_frames_wait_for_every_channel() {
+    let mut decoder = PwmDecoder::<3>::new();
+    // The end of a pulse that started before the capture did
+    assert_eq!(decoder.edge(0, false, 100), None);
+
+    // Receivers pulse their channels one after another
+    let mut time_us = 1_000;
+    let mut frames = Vec::new();
+    for round in 0..2 {
+        for (channel, width) in [1100, 1500, 1900].iter().enumerate() {
+            assert_eq!(decoder.edge(channel, true, time_us), None);
+            time_us += width + round;
+            frames.extend(decoder.edge(channel, false, time_us));
+        }
+        time_us += 15_000;
+    }
+    assert_eq!(frames, vec![Frame::from_widths(&[1100, 1500, 1900]), Frame::from_widths(&[1101, 1501, 1901])]);
+    assert!(!decoder.is_signal_lost(time_us));
+
+    // One channel unplugged
+    for channel in 0..2 {
+        decoder.edge(channel, true, time_us);
+        assert_eq!(decoder.edge(channel, false, time_us + 1500), None);
+    }
+    assert!(decoder.is_signal_lost(time_us + HOLD_US + 1_000));
+}
+
+#[test]
+fn pwm_ignores_out_of_range_pulses() {
+    let mut decoder = PwmDecoder::<1>::new();
+    decoder.edge(0, true, 0);
+    assert_eq!(decoder.edge(0, false, 100), None);
+    decoder.edge(0, true, 1_000);
+    assert_eq!(decoder.edge(0, false, 4_000), None);
+    assert_eq!(decoder.edge(1, true, 5_000), None);
+    decoder.edge(0, true, 10_000);
+    assert_eq!(decoder.edge(0, false, 11_500), Some(Frame::from_widths(&[1500])));
+}
+
+#[test]
+fn widths_map_onto_channel_input() {
+    let mut input = ChannelInput::new(100);
+    input.update_from_ppm(&Frame::from_widths(&[1000, 1500, 2000, 2100]), 20);
+    assert_eq!(input.link_state(20), LinkState::Connected);
+    assert_eq!(input.channel_count(), 4);
+    assert_eq!(input.channels.get(0), Some(ChannelValue::MIN));
+    assert_eq!(input.channels.get(1), Some(ChannelValue::CENTER));
+    assert_eq!(input.channels.get(2), Some(ChannelValue::MAX));
+    assert_eq!(input.channels.get(3), Some(ChannelValue::MAX));
+    assert_eq!(input.link_state(200), LinkState::Lost);
+}
+
+#[test]
+fn frames_hold_up_to_max_channels() {
+    let widths = [1500; MAX_CHANNELS + 2];
+    assert_eq!(Frame::from_widths(&widths).widths().len(), MAX_CHANNELS);
+    let mut frame = Frame::from_widths(&widths);
+    assert!(!frame.push(1500));
+
+    // A PPM frame with too many channels is as bad as a glitch
+    let mut decoder = PpmDecoder::new();
+    assert!(decode(&mut decoder, &ppm_stream(0, &[1000; MAX_CHANNELS + 1], 3)).is_empty());
+}
+
+/// Leading edges of the pulses in `frames` sent back to back with `timing`
+fn sent_edges(timing: &Timing, frames: &[Frame]) -> Vec<u32> {
+    let active = timing.polarity == Polarity::Positive;
+    let mut time_us = 0;
+    let mut edges = Vec::new();
+    for step in frames.iter().flat_map(|frame| timing.steps(frame)) {
+        if step.high == active {
+            edges.push(time_us);
+        }
+        time_us += step.duration_us;
+    }
+    edges
+}
+
+#[test]
+fn output_steps_alternate_pulses_and_channels() {
+    let timing = Timing { polarity: Polarity::Negative, ..Timing::new() };
+    let steps: Vec<Step> = timing.steps(&Frame::from_widths(&[1000, 2000, 100])).collect();
+    let expected = [(false, 300), (true, 700), (false, 300), (true, 1700), (false, 300), (true, 450), (false, 300), (true, 18_450)];
+    let expected: Vec<Step> = expected.iter().map(|(high, duration_us)| Step { high: *high, duration_us: *duration_us }).collect();
+    assert_eq!(steps, expected);
+    assert_eq!(steps.iter().map(|step| step.duration_us).sum::<u32>(), timing.frame_us);
+}
+
+#[test]
+fn full_frames_are_stretched() {
+    let timing = Timing { frame_us: 20_000, ..Timing::new() };
+    let steps: Vec<Step> = timing.steps(&Frame::from_widths(&[2000; 9])).collect();
+    assert_eq!(steps.len(), 20);
+    assert!(steps.iter().enumerate().all(|(index, step)| step.high == (index % 2 == 0)));
+    assert_eq!(steps.iter().map(|step| step.duration_us).sum::<u32>(), 9 * 2000 + MIN_GAP_US);
+}
+
+#[test]
+fn output_decodes_back() {
+    let mut channels = ChannelData::new();
+    channels.set(1, ChannelValue::MIN);
+    channels.set(3, ChannelValue::MAX);
+    let frame = Frame::from_channels(&channels, 6);
+    assert_eq!(frame.widths(), &[1500, 1000, 1500, 2000, 1500, 1500]);
+
+    let frames: Vec<Frame> = (0..4).map(|frame| Frame::test_pattern(8, frame * 22)).collect();
+    let mut decoder = PpmDecoder::new();
+    let edges = sent_edges(&Timing::new(), &[frame, frame, frame, frames[0], frames[1], frames[2], frames[3]]);
+    let decoded: Vec<Frame> = edges.iter().filter_map(|edge| decoder.push(*edge)).collect();
+    // The change from 6 to 8 channels costs a frame, and the last one is never ended
+    assert_eq!(decoded, vec![frame, frames[1], frames[2]]);
+}
+
+#[test]
+fn test_pattern_sweeps() {
+    assert_eq!(Frame::test_pattern(3, 0).widths(), &[1000, 1250, 1500]);
+    assert_eq!(Frame::test_pattern(1, 1_000).widths(), &[2000]);
+    assert_eq!(Frame::test_pattern(1, 1_500).widths(), &[1500]);
+    assert_eq!(Frame::test_pattern(20, 0).widths().len(), MAX_CHANNELS);
+}
+
+proptest! {
+    #[test]
+    fn ppm_round_trip(widths in prop::collection::vec(750..=2250u16, 4..=MAX_CHANNELS), start_us: u32) {
+        let mut decoder = PpmDecoder::new();
+        let frames = decode(&mut decoder, &ppm_stream(start_us, &widths, 5));
+        prop_assert_eq!(frames, vec![widths.clone(), widths]);
+    }
+
+    #[test]
+    fn sent_frames_round_trip(widths in prop::collection::vec(750..=2250u16, 4..=MAX_CHANNELS), frame_us in 10_000..40_000u32, positive: bool) {
+        let polarity = if positive { Polarity::Positive } else { Polarity::Negative };
+        let timing = Timing { frame_us, polarity, ..Timing::new() };
+        let frame = Frame::from_widths(&widths);
+        let mut decoder = PpmDecoder::new();
+        let decoded: Vec<Frame> = sent_edges(&timing, &[frame; 4]).iter().filter_map(|edge| decoder.push(*edge)).collect();
+        prop_assert_eq!(decoded, vec![frame]);
+    }
+
+    #[test]
+    fn arbitrary_edges_never_panic(gaps in prop::collection::vec(0..5_000u32, 0..256)) {
+        let mut ppm = PpmDecoder::new();
+        let mut pwm = PwmDecoder::<MAX_CHANNELS>::new();
+        let mut time_us = 0u32;
+        for (index, gap) in gaps.iter().enumerate() {
+            time_us = time_us.wrapping_add(*gap);
+            ppm.push(time_us);
+            pwm.edge(*gap as usize % (MAX_CHANNELS + 1), index % 2 == 0, time_us);
+        }
+    }
+}
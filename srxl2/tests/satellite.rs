@@ -0,0 +1,208 @@
+//! Satellite frames built by hand, the way DSM2 and DSMX remotes lay them out, fed
+//! through the decoder byte by byte with realistic timing.
+
+use proptest::prelude::*;
+
+use srxl2::{
+    bind::BindStatus,
+    channel::{ChannelData, ChannelValue},
+    satellite::{
+        bind_pulse_count,
+        BindPulses,
+        BindStep,
+        Frame,
+        Remote,
+        Resolution,
+        Satellite,
+        BIND_DELAY_US,
+        BIND_PULSE_US,
+        EMPTY_SERVO,
+        FRAME_LENGTH,
+        HOLD_US,
+    },
+};
+
+/// One byte at 125000 baud, 8N1
+const BYTE_US: u32 = 80;
+
+/// A frame with `header` in the first two bytes and `servos` as (channel ID, position)
+/// pairs, padded with empty slots. `phase` sets the top bit of 11-bit servo words.
+fn frame(header: [u8; 2], resolution: Resolution, phase: bool, servos: &[(u8, u16)]) -> [u8; FRAME_LENGTH] {
+    let mut bytes = [0xFF; FRAME_LENGTH];
+    bytes[..2].copy_from_slice(&header);
+    for (slot, (id, position)) in servos.iter().enumerate() {
+        let servo = match resolution {
+            Resolution::Bits10 => (*id as u16) << 10 | position,
+            Resolution::Bits11 => (phase as u16) << 15 | (*id as u16) << 11 | position,
+        };
+        bytes[2 + slot * 2..4 + slot * 2].copy_from_slice(&servo.to_be_bytes());
+    }
+    bytes
+}
+
+/// Sends `bytes` back to back starting at `*now_us`, returning every frame that completes
+fn receive(satellite: &mut Satellite, now_us: &mut u32, bytes: &[u8]) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    for byte in bytes {
+        frames.extend(satellite.push(*byte, *now_us));
+        *now_us += BYTE_US;
+    }
+    frames
+}
+
+/// Sends each frame of `frames` in turn, `period_ms` apart, into `channels`
+fn run(satellite: &mut Satellite, now_us: &mut u32, channels: &mut ChannelData, period_ms: u32, frames: &[[u8; FRAME_LENGTH]]) -> usize {
+    let mut updated = 0;
+    for bytes in frames {
+        let start_us = *now_us;
+        for frame in receive(satellite, now_us, bytes) {
+            updated += satellite.update(&frame, channels) as usize;
+        }
+        *now_us = start_us + period_ms * 1000;
+    }
+    updated
+}
+
+#[test]
+fn frames_are_found_by_the_silence_between_them() {
+    let bytes = frame([0, 0xA2], Resolution::Bits11, false, &[(0, 100), (1, 200)]);
+    let mut satellite = Satellite::new();
+    let mut now_us = 0;
+
+    // Switched on halfway through a frame
+    assert!(receive(&mut satellite, &mut now_us, &bytes[6..]).is_empty());
+    now_us += 9_000;
+    assert_eq!(receive(&mut satellite, &mut now_us, &bytes), vec![Frame(bytes)]);
+
+    // A byte lost in the middle of a frame costs only that frame
+    now_us += 9_000;
+    assert!(receive(&mut satellite, &mut now_us, &bytes[1..]).is_empty());
+    now_us += 9_000;
+    assert_eq!(receive(&mut satellite, &mut now_us, &bytes), vec![Frame(bytes)]);
+}
+
+#[test]
+fn dsm2_22ms_is_detected_as_10_bit() {
+    let servos = [(0, 0), (1, 512), (2, 1023), (3, 341), (4, 683), (5, 512), (6, 100)];
+    let bytes = frame([3, 0x01], Resolution::Bits10, false, &servos);
+    let mut satellite = Satellite::new();
+    let mut channels = ChannelData::new();
+    let mut now_us = 0;
+
+    assert_eq!(run(&mut satellite, &mut now_us, &mut channels, 22, &[bytes; 6]), 3);
+    assert_eq!(satellite.resolution(), Some(Resolution::Bits10));
+    assert_eq!(Frame(bytes).system(), Some(BindStatus::Dsm2_1024_22ms));
+    assert_eq!({ channels.mask }, 0x7F);
+    assert_eq!({ channels.frame_losses }, 3);
+    assert_eq!(channels.get(0), Some(ChannelValue::MIN));
+    assert_eq!(channels.get(1), Some(ChannelValue::CENTER));
+    assert_eq!(channels.get(2).unwrap().raw(), 1023 << 6);
+    assert_eq!(channels.get(6).unwrap().raw(), 100 << 6);
+}
+
+#[test]
+fn dsmx_11ms_is_detected_as_11_bit_across_both_phases() {
+    let first = frame([0, 0xB2], Resolution::Bits11, false, &[(0, 342), (1, 1024), (2, 1024), (3, 1024), (4, 1706), (5, 1024), (6, 1024)]);
+    let second = frame([0, 0xB2], Resolution::Bits11, true, &[(0, 342), (7, 2047), (8, 1024), (9, 1024), (10, 1024), (11, 0)]);
+    let mut satellite = Satellite::new();
+    let mut channels = ChannelData::new();
+    let mut now_us = 0;
+
+    assert_eq!(run(&mut satellite, &mut now_us, &mut channels, 11, &[first, second, first, second, first, second]), 3);
+    assert_eq!(satellite.resolution(), Some(Resolution::Bits11));
+    assert_eq!({ channels.mask }, 0xFFF);
+    assert_eq!(channels.get(1), Some(ChannelValue::CENTER));
+    assert_eq!(channels.get(0).unwrap().raw(), 342 << 5);
+    assert_eq!(channels.get(7).unwrap().raw(), 2047 << 5);
+    assert_eq!(channels.get(11), Some(ChannelValue::MIN));
+}
+
+#[test]
+fn six_channels_at_11_bit_leave_the_last_slot_empty() {
+    let bytes = frame([0, 0xA2], Resolution::Bits11, false, &[(0, 342), (1, 1000), (2, 1100), (3, 1024), (4, 342), (5, 1706)]);
+    let mut satellite = Satellite::new();
+    let mut channels = ChannelData::new();
+    let mut now_us = 0;
+
+    assert_eq!(Frame(bytes).servo(6), EMPTY_SERVO);
+    assert_eq!(Frame(bytes).servos().count(), 6);
+    run(&mut satellite, &mut now_us, &mut channels, 22, &[bytes; 4]);
+    assert_eq!(satellite.resolution(), Some(Resolution::Bits11));
+    assert_eq!({ channels.mask }, 0x3F);
+}
+
+#[test]
+fn external_remotes_send_a_16_bit_fade_count() {
+    let bytes = frame([0x01, 0x05], Resolution::Bits11, false, &[(0, 342), (1, 1024)]);
+    let mut satellite = Satellite::with_resolution(Resolution::Bits11);
+    let mut channels = ChannelData::new();
+    let mut now_us = 0;
+
+    assert_eq!(Frame(bytes).system(), None);
+    assert_eq!(run(&mut satellite, &mut now_us, &mut channels, 22, &[bytes]), 1);
+    assert_eq!({ channels.frame_losses }, 0x0105);
+}
+
+#[test]
+fn failsafe_after_a_second_without_frames() {
+    let bytes = frame([0, 0xA2], Resolution::Bits11, false, &[(0, 342)]);
+    let mut satellite = Satellite::new();
+    let mut now_us = 1_000;
+
+    assert!(satellite.is_failsafe(now_us));
+    receive(&mut satellite, &mut now_us, &bytes);
+    let received_us = now_us - BYTE_US;
+    assert!(!satellite.is_failsafe(received_us));
+    assert!(!satellite.is_failsafe(received_us + HOLD_US));
+    assert!(satellite.is_failsafe(received_us + HOLD_US + 1));
+}
+
+#[test]
+fn bind_pulse_counts_match_the_spec() {
+    let counts = [
+        (BindStatus::Dsm2_1024_22ms, 3),
+        (BindStatus::Dsm2_2048_11ms, 5),
+        (BindStatus::Dsmx22Ms, 7),
+        (BindStatus::Dsmx11Ms, 9),
+    ];
+    for (system, internal) in counts {
+        assert_eq!(bind_pulse_count(system, Remote::Internal), Some(internal));
+        assert_eq!(bind_pulse_count(system, Remote::External), Some(internal + 1));
+    }
+    assert_eq!(bind_pulse_count(BindStatus::NotBound, Remote::Internal), None);
+    assert_eq!(bind_pulse_count(BindStatus::Dsmr11ms22ms, Remote::External), None);
+    assert!(BindPulses::new(BindStatus::SurfaceDsm1, Remote::Internal).is_none());
+}
+
+#[test]
+fn bind_pulse_train_waits_for_startup_then_pulses_low() {
+    let steps: Vec<BindStep> = BindPulses::new(BindStatus::Dsmx22Ms, Remote::External).unwrap().collect();
+    assert_eq!(steps.len(), 1 + 8 * 2);
+    assert_eq!(steps[0], BindStep { high: true, duration_us: BIND_DELAY_US });
+    for pulse in steps[1..].chunks(2) {
+        assert_eq!(pulse, [BindStep { high: false, duration_us: BIND_PULSE_US }, BindStep { high: true, duration_us: BIND_PULSE_US }]);
+    }
+
+    // Every pulse has to be over within 200ms of power-up
+    let total_us: u32 = steps.iter().map(|step| step.duration_us).sum();
+    assert!(total_us < 200_000);
+
+    let falling_edges = steps.windows(2).filter(|pair| pair[0].high && !pair[1].high).count();
+    assert_eq!(falling_edges, 8);
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes_never_panic(bytes in prop::collection::vec((any::<u8>(), 0..20_000u32), 0..256)) {
+        let mut satellite = Satellite::new();
+        let mut channels = ChannelData::new();
+        let mut now_us = 0u32;
+        for (byte, delay_us) in bytes {
+            now_us = now_us.wrapping_add(delay_us);
+            if let Some(frame) = satellite.push(byte, now_us) {
+                let _ = (frame.system(), frame.fades());
+                satellite.update(&frame, &mut channels);
+            }
+        }
+    }
+}
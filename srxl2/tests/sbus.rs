@@ -0,0 +1,150 @@
+//! SBUS frames packed and unpacked on the host, checked against hand-packed bytes and
+//! fed through the decoder with line noise around them.
+
+use proptest::prelude::*;
+
+use srxl2::{
+    channel::{ChannelData, ChannelValue},
+    flags::Flags,
+    input::{ChannelInput, LinkState, RcInput},
+    sbus::{Frame, Sbus, SbusFlag, CHANNELS, DIGITAL_CHANNEL, FRAME_LENGTH, HEADER},
+};
+
+fn frame(channels: [u16; CHANNELS], flags: Flags<SbusFlag>) -> Frame {
+    Frame { channels, flags }
+}
+
+#[test]
+fn channels_are_packed_lsb_first() {
+    let mut channels = [0; CHANNELS];
+    channels[0] = 0x7FF;
+    channels[2] = 0x001;
+    channels[15] = 0x400;
+    let bytes = frame(channels, Flags::from(SbusFlag::Failsafe)).encode();
+
+    let mut expected = [0; FRAME_LENGTH];
+    expected[0] = HEADER;
+    expected[1] = 0xFF;
+    expected[2] = 0x07;
+    // channel 2 starts at bit 22: bit 6 of byte 3
+    expected[3] = 0x40;
+    // channel 15 ends the data at bit 175: its top bit is the top bit of byte 22
+    expected[22] = 0x80;
+    expected[23] = 0x08;
+    assert_eq!(bytes, expected);
+    assert_eq!(Frame::decode(&bytes), Some(frame(channels, Flags::from(SbusFlag::Failsafe))));
+}
+
+#[test]
+fn bad_header_or_footer_is_rejected() {
+    let mut bytes = Frame::new().encode();
+    bytes[24] = 0x55;
+    assert_eq!(Frame::decode(&bytes), None);
+
+    // FASSTest telemetry slot footers are fine
+    bytes[24] = 0x24;
+    assert!(Frame::decode(&bytes).is_some());
+
+    bytes[0] = 0x0E;
+    assert_eq!(Frame::decode(&bytes), None);
+}
+
+#[test]
+fn decoder_syncs_past_noise_and_false_headers() {
+    let mut channels = [1024; CHANNELS];
+    channels[3] = 0x0F0F & 0x7FF;
+    let sent = frame(channels, Flags::empty());
+    let bytes = sent.encode();
+
+    let mut stream = vec![0x55, 0xAA];
+    // A header byte followed by too few bytes for a frame
+    stream.extend([HEADER, 0x12, 0x34]);
+    stream.extend(bytes);
+    stream.extend(bytes);
+
+    let mut sbus = Sbus::new();
+    let frames: Vec<Frame> = stream.iter().filter_map(|byte| sbus.push(*byte)).collect();
+    assert_eq!(frames, vec![sent, sent]);
+    assert!(!sbus.is_receiving());
+}
+
+#[test]
+fn decoder_reset_drops_a_partial_frame() {
+    let bytes = Frame::new().encode();
+    let mut sbus = Sbus::new();
+    for byte in &bytes[..10] {
+        assert_eq!(sbus.push(*byte), None);
+    }
+    assert!(sbus.is_receiving());
+    sbus.reset();
+    let frames: Vec<Frame> = bytes.iter().filter_map(|byte| sbus.push(*byte)).collect();
+    assert_eq!(frames, vec![Frame::new()]);
+}
+
+#[test]
+fn channels_map_onto_channel_data() {
+    let mut channels = [1024; CHANNELS];
+    channels[0] = 0;
+    channels[1] = 2047;
+    let sent = frame(channels, Flags::from([SbusFlag::Channel18, SbusFlag::FrameLost]));
+
+    let mut data = ChannelData::new();
+    sent.update_channels(&mut data);
+    assert_eq!({ data.mask }, 0x3FFFF);
+    assert_eq!(data.get(0), Some(ChannelValue::MIN));
+    assert_eq!(data.get(1).unwrap().raw(), 2047 << 5);
+    assert_eq!(data.get(2), Some(ChannelValue::CENTER));
+    assert_eq!(data.get(DIGITAL_CHANNEL), Some(ChannelValue::MIN));
+    assert_eq!(data.get(DIGITAL_CHANNEL + 1), Some(ChannelValue::MAX));
+    assert_eq!({ data.frame_losses }, 1);
+
+    // And back, for sending
+    let flags = Flags::from(SbusFlag::FrameLost);
+    assert_eq!(Frame::from_channels(&data, flags), sent);
+}
+
+#[test]
+fn missing_channels_are_sent_centered() {
+    let mut data = ChannelData::new();
+    data.set(4, ChannelValue::MAX);
+    let sent = Frame::from_channels(&data, Flags::empty());
+    assert_eq!(sent.channels[4], ChannelValue::MAX.raw() >> 5);
+    assert_eq!(sent.channels[5], 1024);
+    assert!(sent.flags.is_empty());
+}
+
+#[test]
+fn failsafe_flag_drops_the_link() {
+    let mut input = ChannelInput::new(100);
+    input.update_from_sbus(&Frame::new(), 0);
+    assert_eq!(input.link_state(7), LinkState::Connected);
+    assert_eq!(input.channel_count(), 18);
+
+    input.update_from_sbus(&frame([1024; CHANNELS], Flags::from(SbusFlag::Failsafe)), 14);
+    assert_eq!(input.link_state(14), LinkState::Lost);
+    assert!(input.is_failsafe(14));
+}
+
+proptest! {
+    #[test]
+    fn round_trip(channels in prop::array::uniform16(0..2048u16), flags in 0..16u8) {
+        let sent = frame(channels, Flags::from_bits(flags));
+        let bytes = sent.encode();
+        prop_assert_eq!(Frame::decode(&bytes), Some(sent));
+
+        let mut sbus = Sbus::new();
+        let frames: Vec<Frame> = bytes.iter().filter_map(|byte| sbus.push(*byte)).collect();
+        prop_assert_eq!(frames, vec![sent]);
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+        let mut sbus = Sbus::new();
+        let mut data = ChannelData::new();
+        for byte in bytes {
+            if let Some(frame) = sbus.push(byte) {
+                frame.update_channels(&mut data);
+            }
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use srxl2::{
+    capture::Record,
+    channel::ChannelValue,
+    device::{DeviceId, DeviceInfo, DeviceType},
+    flags::Flags,
+    framer::Framer,
+    handshake::{Baud, BaudRates},
+    internal::State,
+    packet::{Packet, PacketType},
+    sim::{Faults, SimBus},
+    Srxl2Interpreter,
+};
+
+const RECEIVER: DeviceId = DeviceId::new(DeviceType::Receiver, 1);
+const ROBOT: DeviceId = DeviceId::new(DeviceType::FlightController, 0);
+const ESC: DeviceId = DeviceId::new(DeviceType::ESC, 0);
+
+fn device(device_id: DeviceId, priority: u8, baud_supported: BaudRates) -> Srxl2Interpreter {
+    let mut srxl = Srxl2Interpreter::new();
+    assert!(srxl.init_device(device_id, priority, Flags::from(DeviceInfo::TelemTxEnabled), 0x1234_5678 ^ u8::from(device_id) as u32));
+    assert!(srxl.init_bus(0, 0, baud_supported));
+    srxl
+}
+
+fn fast() -> BaudRates {
+    BaudRates::from([Baud::Baud115200, Baud::Baud400000])
+}
+
+/// Receiver, robot, and ESC on one bus, in that node order
+fn bus(seed: u64) -> SimBus {
+    let mut sim = SimBus::new(seed);
+    sim.add_node(device(RECEIVER, 0, fast()));
+    sim.add_node(device(ROBOT, 30, fast()));
+    sim.add_node(device(ESC, 10, fast()));
+    sim
+}
+
+/// Runs frames in which the receiver gets a good RF frame with `value` on channels 0-3
+fn run_good_frames(sim: &mut SimBus, frames: usize, value: ChannelValue) {
+    for _ in 0..frames {
+        let receiver = sim.node_mut(0);
+        for channel in 0..4 {
+            receiver.channel_data.set(channel, value);
+        }
+        receiver.set_outgoing_channel_mask(0b1111);
+        sim.run_for_ms(11);
+    }
+}
+
+fn sent_by(sim: &SimBus, sender: usize, packet_type: PacketType) -> usize {
+    sim.log().iter()
+        .filter(|frame| frame.sender == sender && frame.bytes[1] == packet_type as u8)
+        .count()
+}
+
+#[test]
+fn handshake_discovers_devices_and_negotiates_baud() {
+    let mut sim = bus(1);
+    sim.run_for_ms(300);
+
+    let devices: Vec<_> = sim.node(0).get_devices(0).iter().map(|entry| entry.device_id).collect();
+    assert_eq!(devices, [ROBOT, ESC]);
+    for node in 0..3 {
+        assert_eq!(sim.node(node).get_state(0), State::Running);
+        assert_eq!(sim.node(node).get_baud_rate(0), Baud::Baud400000);
+    }
+    assert_eq!(sim.collisions(), 0);
+}
+
+#[test]
+fn handshake_falls_back_to_slowest_device() {
+    let mut sim = SimBus::new(1);
+    sim.add_node(device(RECEIVER, 0, fast()));
+    sim.add_node(device(ROBOT, 30, fast()));
+    sim.add_node(device(ESC, 10, BaudRates::from(Baud::Baud115200)));
+    sim.run_for_ms(300);
+
+    assert_eq!(sim.node(0).get_devices(0).len(), 2);
+    for node in 0..3 {
+        assert_eq!(sim.node(node).get_baud_rate(0), Baud::Baud115200);
+    }
+}
+
+#[test]
+fn channel_data_reaches_every_device() {
+    let mut sim = bus(1);
+    sim.run_for_ms(300);
+
+    let value = ChannelValue::from_us(1750);
+    run_good_frames(&mut sim, 3, value);
+    for node in 1..3 {
+        let srxl = sim.node(node);
+        assert!(!srxl.is_failsafe());
+        for channel in 0..4 {
+            assert_eq!(srxl.channel_data.get(channel), Some(value));
+        }
+        assert_eq!(srxl.channel_data.get(4), None);
+    }
+}
+
+#[test]
+fn telemetry_is_polled_by_priority() {
+    let mut sim = bus(1);
+    sim.node_mut(1).telem_data.sensor_id = 0x7E;
+    sim.run_for_ms(300);
+    sim.clear_log();
+
+    run_good_frames(&mut sim, 400, ChannelValue::CENTER);
+    let robot = sent_by(&sim, 1, PacketType::TelemetryData);
+    let esc = sent_by(&sim, 2, PacketType::TelemetryData);
+
+    // 30:10 priority, one reply per frame
+    assert_eq!(robot + esc, 400);
+    assert_eq!(robot, 300);
+    assert_eq!(esc, 100);
+    assert_eq!(sim.collisions(), 0);
+}
+
+#[test]
+fn telemetry_reaches_receiver() {
+    let mut sim = SimBus::new(1);
+    sim.add_node(device(RECEIVER, 0, fast()));
+    sim.add_node(device(ROBOT, 30, fast()));
+    sim.node_mut(1).telem_data.sensor_id = 0x7E;
+    sim.node_mut(1).telem_data.data[0] = 42;
+    sim.run_for_ms(300);
+
+    run_good_frames(&mut sim, 2, ChannelValue::CENTER);
+    assert_eq!(sim.node(0).telem_data.sensor_id, 0x7E);
+    assert_eq!(sim.node(0).telem_data.data[0], 42);
+}
+
+#[test]
+fn bind_round_trip() {
+    let mut sim = bus(1);
+    sim.run_for_ms(300);
+
+    // the robot asks the receiver to bind
+    assert!(sim.node_mut(1).enter_bind(0xA2, false));
+    run_good_frames(&mut sim, 10, ChannelValue::CENTER);
+    assert_eq!(sim.node_mut(0).take_bind_request(), Some(0xA2));
+
+    // the receiver reports the result to everyone
+    assert!(sim.node_mut(0).set_bind_info(0xA2, 0x0123_4567_89AB_CDEF, 0xCAFE));
+    run_good_frames(&mut sim, 2, ChannelValue::CENTER);
+    for node in 1..3 {
+        let bind_info = sim.node(node).get_bind_info();
+        assert_eq!({ bind_info.guid }, 0x0123_4567_89AB_CDEF);
+        assert_eq!({ bind_info.uid }, 0xCAFE);
+    }
+
+    // the robot changes the receiver's bind info, which is reported back
+    assert!(sim.node_mut(1).set_bind_info(0xB2, 7, 8));
+    run_good_frames(&mut sim, 10, ChannelValue::CENTER);
+    assert_eq!({ sim.node(0).get_bind_info().guid }, 7);
+    assert_eq!(sim.node(2).get_bind_info().bind_type, 0xB2);
+}
+
+#[test]
+fn bind_info_can_be_requested() {
+    let mut sim = bus(1);
+    sim.run_for_ms(300);
+    sim.node_mut(0).set_bind_info(0xA2, 99, 1);
+    run_good_frames(&mut sim, 2, ChannelValue::CENTER);
+    sim.clear_log();
+
+    assert!(sim.node_mut(2).request_bind_info(0, RECEIVER));
+    run_good_frames(&mut sim, 10, ChannelValue::CENTER);
+    assert_eq!(sent_by(&sim, 2, PacketType::BindInfo), 1);
+    assert_eq!(sent_by(&sim, 0, PacketType::BindInfo), 1);
+    assert_eq!({ sim.node(2).get_bind_info().guid }, 99);
+}
+
+#[test]
+fn failsafe_after_hold() {
+    let mut sim = bus(1);
+    sim.run_for_ms(300);
+    let value = ChannelValue::from_percent(40);
+    run_good_frames(&mut sim, 5, value);
+
+    for _ in 0..44 {
+        sim.node_mut(0).on_frame_error(0);
+        sim.run_for_ms(11);
+    }
+    assert!(!sim.node(1).is_failsafe());
+
+    sim.node_mut(0).on_frame_error(0);
+    sim.run_for_ms(11);
+    assert!(sim.node(0).is_failsafe());
+    for node in 1..3 {
+        assert!(sim.node(node).is_failsafe());
+        assert_eq!(sim.node(node).channel_data.get(0), Some(value));
+    }
+    assert_eq!(sim.node(0).get_comm_stats().holds, 1);
+    assert_eq!(sim.node(0).get_comm_stats().frame_losses, 0);
+
+    run_good_frames(&mut sim, 1, value);
+    assert!(!sim.node(1).is_failsafe());
+}
+
+#[test]
+fn frame_losses_below_hold_are_counted() {
+    let mut sim = bus(1);
+    sim.run_for_ms(300);
+    for _ in 0..3 {
+        sim.node_mut(0).on_frame_error(0);
+        sim.run_for_ms(11);
+    }
+    run_good_frames(&mut sim, 2, ChannelValue::CENTER);
+
+    assert_eq!(sim.node(0).get_comm_stats().frame_losses, 3);
+    assert_eq!(sim.node(0).get_comm_stats().holds, 0);
+    assert!(!sim.node(1).is_failsafe());
+    assert_eq!({ sim.node(1).channel_data.frame_losses }, 3);
+}
+
+#[test]
+fn devices_recover_when_receiver_returns() {
+    let mut sim = bus(1);
+    sim.run_for_ms(300);
+
+    sim.set_connected(0, false);
+    sim.run_for_ms(100);
+    for node in 1..3 {
+        assert_eq!(sim.node(node).get_baud_rate(0), Baud::Baud115200);
+        assert_eq!(sim.node(node).get_state(0), State::ListenForHandshake);
+    }
+
+    // the receiver hears nobody at 400000 baud, so it handshakes again
+    sim.set_connected(0, true);
+    run_good_frames(&mut sim, 30, ChannelValue::MAX);
+    for node in 0..3 {
+        assert_eq!(sim.node(node).get_state(0), State::Running);
+        assert_eq!(sim.node(node).get_baud_rate(0), Baud::Baud400000);
+    }
+    assert_eq!(sim.node(2).channel_data.get(3), Some(ChannelValue::MAX));
+}
+
+#[test]
+fn corrupt_crcs_are_ignored() {
+    let mut sim = bus(1);
+    sim.faults = Faults {
+        crc_corruption: 1.0,
+        ..Faults::default()
+    };
+    sim.run_for_ms(300);
+    run_good_frames(&mut sim, 10, ChannelValue::MAX);
+
+    assert!(sim.node(0).get_devices(0).is_empty());
+    for node in 1..3 {
+        assert_eq!(sim.node(node).channel_data.get(0), None);
+    }
+}
+
+#[test]
+fn survives_a_noisy_bus() {
+    for seed in 1..=5 {
+        let mut sim = bus(seed);
+        sim.faults = Faults {
+            bit_error: 0.002,
+            dropped_byte: 0.002,
+            crc_corruption: 0.01,
+        };
+        sim.run_for_ms(300);
+
+        let mut delivered = 0;
+        for frame in 0..500 {
+            let value = ChannelValue::from_raw(frame * 64);
+            run_good_frames(&mut sim, 1, value);
+            if sim.node(2).channel_data.get(0) == Some(value) {
+                delivered += 1;
+            }
+        }
+
+        // roughly one channel packet in eight is hit by the noise, and the odd device
+        // has to handshake again, but the bus keeps running
+        assert!(delivered > 400, "seed {seed}: only {delivered} of 500 frames delivered");
+        assert_eq!(sim.node(0).get_devices(0).len(), 2);
+        assert_eq!(sim.node(2).get_state(0), State::Running);
+    }
+}
+
+#[test]
+fn loopback_over_the_bus() {
+    let mut sim = bus(1);
+    sim.run_for_ms(300);
+
+    for _ in 0..10 {
+        assert!(sim.node_mut(1).send_internal_data(0, RECEIVER));
+        run_good_frames(&mut sim, 10, ChannelValue::CENTER);
+    }
+
+    let stats = sim.node(1).loopback_stats(RECEIVER).unwrap();
+    assert_eq!(stats.sent, 10);
+    assert_eq!(stats.received, 10);
+    assert_eq!(stats.lost + stats.mismatched, 0);
+    assert!(stats.rtt_max_ms <= 1);
+}
+
+#[test]
+fn capture_of_traffic_can_be_read_back() {
+    let mut sim = bus(1);
+    sim.run_for_ms(300);
+    run_good_frames(&mut sim, 10, ChannelValue::CENTER);
+
+    let capture = sim.capture();
+    let mut buf = [0u8; 80];
+    let mut last_us = 0;
+    let mut packets = 0;
+    let mut framer = Framer::new();
+    for line in capture.lines() {
+        let record = Record::parse(line, &mut buf).unwrap().unwrap();
+        assert!(record.timestamp_us >= last_us);
+        last_us = record.timestamp_us;
+
+        for byte in record.bytes {
+            if let Some(packet) = framer.push(*byte) {
+                assert!(Packet::try_from_slice(packet).is_ok());
+                packets += 1;
+            }
+        }
+    }
+    assert_eq!(packets, sim.log().len());
+}
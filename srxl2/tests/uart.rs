@@ -0,0 +1,288 @@
+//! Software UART framing, checked against waveforms worked out by hand: one character
+//! per bit time, '1' for a high line and '0' for a low one.
+
+use proptest::prelude::*;
+
+use srxl2::uart::{
+    estimate_baud, snap_baud, BaudDivider, BitOrder, FrameError, Framing, LineReceiver, Parity, Sampler, StopBits,
+};
+
+fn waveform(framing: &Framing, word: u16) -> String {
+    framing.bits(word).map(|high| if high { '1' } else { '0' }).collect()
+}
+
+/// Samples `levels`, one word after another, as a receiver would: each start bit is
+/// found by its edge rather than sampled
+fn sample(framing: Framing, levels: &str) -> Vec<Result<u16, FrameError>> {
+    let mut sampler = Sampler::new(framing);
+    let levels: Vec<bool> = levels.chars().map(|level| level == '1').collect();
+    levels.chunks(framing.frame_bits() as usize).filter_map(|word| {
+        assert_eq!(word[0], !framing.idle_level());
+        word[1..].iter().find_map(|level| sampler.push(*level))
+    }).collect()
+}
+
+fn framing(data_bits: u8, parity: Parity, stop_bits: StopBits) -> Framing {
+    Framing { data_bits, parity, stop_bits, ..Framing::new() }
+}
+
+#[test]
+fn eight_n_one_goes_lsb_first() {
+    // 'A', 0x41
+    assert_eq!(waveform(&Framing::new(), 0x41), "0100000101");
+    assert_eq!(waveform(&Framing::new(), 0x00), "0000000001");
+    assert_eq!(waveform(&Framing::new(), 0xFF), "0111111111");
+    assert_eq!(Framing::new().frame_bits(), 10);
+    assert!(Framing::new().idle_level());
+}
+
+#[test]
+fn msb_first() {
+    let framing = Framing { bit_order: BitOrder::MsbFirst, ..Framing::new() };
+    assert_eq!(waveform(&framing, 0x41), "0010000011");
+    assert_eq!(sample(framing, "0010000011"), vec![Ok(0x41)]);
+}
+
+#[test]
+fn parity_bits() {
+    // 'C', 0x43: three ones in its low seven bits
+    assert_eq!(waveform(&framing(7, Parity::Even, StopBits::One), 0x43), "0110000111");
+    assert_eq!(waveform(&framing(7, Parity::Odd, StopBits::One), 0x43), "0110000101");
+    assert_eq!(waveform(&framing(8, Parity::Odd, StopBits::One), 0x00), "00000000011");
+    assert_eq!(waveform(&framing(8, Parity::Even, StopBits::One), 0x00), "00000000001");
+}
+
+#[test]
+fn nine_data_bits_and_two_stop_bits() {
+    let framing = framing(9, Parity::None, StopBits::Two);
+    assert_eq!(waveform(&framing, 0x100), "000000000111");
+    assert_eq!(waveform(&framing, 0x0AA), "001010101011");
+    assert_eq!(framing.frame_bits(), 12);
+    // Only the first stop bit is sampled
+    assert_eq!(sample(framing, "00000000011"), vec![Ok(0x100)]);
+}
+
+#[test]
+fn sbus_is_inverted_8e2() {
+    // The SBUS header byte
+    assert_eq!(waveform(&Framing::SBUS, 0x0F), "100001111100");
+    assert!(!Framing::SBUS.idle_level());
+    assert_eq!(sample(Framing::SBUS, "100001111100"), vec![Ok(0x0F)]);
+}
+
+#[test]
+fn word_size_limits() {
+    assert!(framing(7, Parity::None, StopBits::One).is_valid());
+    assert!(framing(9, Parity::None, StopBits::One).is_valid());
+    assert!(!framing(6, Parity::None, StopBits::One).is_valid());
+    assert!(!framing(10, Parity::None, StopBits::One).is_valid());
+    // Bits above the word size are dropped
+    assert_eq!(waveform(&framing(7, Parity::None, StopBits::One), 0xC1), "010000011");
+    assert_eq!(Framing::new().bits(0x1234).word(), 0x34);
+}
+
+#[test]
+fn receive_errors() {
+    let framing = framing(8, Parity::Even, StopBits::One);
+    // Stop bit low
+    assert_eq!(sample(framing, "00000000000"), vec![Err(FrameError::Framing)]);
+    // A data bit flipped
+    assert_eq!(sample(framing, "01000000001"), vec![Err(FrameError::Parity)]);
+    // And the sampler carries on with the next word
+    assert_eq!(sample(framing, "0100000000101000000011"), vec![Err(FrameError::Parity), Ok(0x01)]);
+}
+
+#[test]
+fn sampler_resets() {
+    let mut sampler = Sampler::new(Framing::new());
+    assert!(!sampler.is_receiving());
+    sampler.push(true);
+    assert!(sampler.is_receiving());
+    sampler.reset();
+    assert!(!sampler.is_receiving());
+    let levels: Vec<bool> = Framing::new().bits(0x5A).skip(1).collect();
+    assert_eq!(levels.iter().filter_map(|level| sampler.push(*level)).collect::<Vec<_>>(), vec![Ok(0x5A)]);
+}
+
+const CLOCK_HZ: u32 = 16_000_000;
+/// The Uno's 8-bit Timer2
+const TIMER2_PRESCALES: [u32; 7] = [1, 8, 32, 64, 128, 256, 1024];
+
+#[test]
+fn baud_dividers() {
+    let divider = BaudDivider::closest(CLOCK_HZ, 115_200, &TIMER2_PRESCALES, 256).unwrap();
+    assert_eq!((divider.prescale, divider.count, divider.actual), (1, 139, 115_108));
+    assert_eq!(divider.error_permille(), 0);
+
+    // Two prescales get as close; the finer one wins
+    let divider = BaudDivider::closest(CLOCK_HZ, 9_600, &TIMER2_PRESCALES, 256).unwrap();
+    assert_eq!((divider.prescale, divider.count, divider.actual), (8, 208, 9_615));
+    assert_eq!(divider.error_permille(), 1);
+    assert!(divider.is_within_tolerance());
+
+    let divider = BaudDivider::closest(CLOCK_HZ, 400_000, &[1], 256).unwrap();
+    assert_eq!((divider.count, divider.actual, divider.error_permille()), (40, 400_000, 0));
+
+    // Out of reach of the timer
+    assert_eq!(BaudDivider::closest(CLOCK_HZ, 30, &TIMER2_PRESCALES, 256), None);
+    assert_eq!(BaudDivider::closest(CLOCK_HZ, 40_000_000, &TIMER2_PRESCALES, 256), None);
+
+    // In reach, but too far off
+    let divider = BaudDivider::closest(CLOCK_HZ, 460_800, &[8], 256).unwrap();
+    assert_eq!((divider.count, divider.actual, divider.error_permille()), (4, 500_000, 85));
+    assert!(!divider.is_within_tolerance());
+    assert_eq!(BaudDivider::new(CLOCK_HZ, 500_000, 1, 33).error_permille(), -30);
+}
+
+/// `levels` recorded as runs, one character per bit time at `baud`, with the edges on
+/// the nearest tick; `waveform` gives the levels for each word
+fn record(levels: &str, baud: u32) -> Vec<(bool, u32)> {
+    let mut runs: Vec<(bool, u32)> = Vec::new();
+    let mut last_edge = 0;
+    for (index, level) in levels.chars().map(|level| level == '1').enumerate() {
+        if runs.last().is_some_and(|(last, _)| *last == level) {
+            continue;
+        }
+        let edge = (index as u64 * CLOCK_HZ as u64 / baud as u64) as u32;
+        if let Some((_, ticks)) = runs.last_mut() {
+            *ticks = edge - last_edge;
+        }
+        runs.push((level, 0));
+        last_edge = edge;
+    }
+    let end = (levels.len() as u64 * CLOCK_HZ as u64 / baud as u64) as u32;
+    if let Some((_, ticks)) = runs.last_mut() {
+        *ticks = end - last_edge;
+    }
+    runs
+}
+
+fn receive(framing: Framing, runs: &[(bool, u32)], baud: u32) -> Vec<(u64, Result<u16, FrameError>)> {
+    let mut receiver = LineReceiver::new(framing, CLOCK_HZ, baud);
+    let mut words = Vec::new();
+    for (level, ticks) in runs {
+        receiver.push(*level, *ticks, |start, word| words.push((start, word)));
+    }
+    assert!(!receiver.is_receiving());
+    words
+}
+
+fn bauds(runs: &[(bool, u32)]) -> Option<u32> {
+    let mut ticks: Vec<u32> = runs.iter().map(|(_, ticks)| *ticks).collect();
+    estimate_baud(&mut ticks, CLOCK_HZ)
+}
+
+#[test]
+fn lines_are_received_from_their_edges() {
+    let framing = Framing::new();
+    // Idle, then "Hi" back to back, a gap, and a 0
+    let levels = format!("1111{}{}111{}11", waveform(&framing, b'H' as u16), waveform(&framing, b'i' as u16), waveform(&framing, 0));
+    let runs = record(&levels, 115_200);
+    // 'H' is 0x48: the start bit and three 0s, then a 1
+    assert_eq!(runs[..3], [(true, 555), (false, 556), (true, 139)]);
+
+    // Start bits at bit times 4, 14, and 27
+    assert_eq!(receive(framing, &runs, 115_200), vec![(555, Ok(0x48)), (1944, Ok(0x69)), (3750, Ok(0x00))]);
+    let estimate = bauds(&runs).unwrap();
+    assert!(estimate.abs_diff(115_200) < 1000, "{estimate}");
+    assert_eq!(snap_baud(estimate), Some(115_200));
+}
+
+#[test]
+fn line_errors_and_glitches() {
+    let framing = Framing::new();
+    // A break: low for longer than a word
+    let runs = record(&format!("1111{}1111{}1", "0".repeat(12), waveform(&framing, 0x01)), 115_200);
+    assert_eq!(receive(framing, &runs, 115_200), vec![(555, Err(FrameError::Framing)), (2777, Ok(0x01))]);
+
+    // A low glitch shorter than half a bit isn't a start bit
+    let mut runs = vec![(true, 1000), (false, 50), (true, 1000)];
+    assert_eq!(receive(framing, &runs, 115_200), vec![]);
+    runs.extend(record(&format!("1{}", waveform(&framing, 0x55)), 115_200));
+    assert_eq!(receive(framing, &runs, 115_200), vec![(2188, Ok(0x55))]);
+
+    // Starting partway through a word, there's no edge to start from
+    let runs = record("00011111", 115_200);
+    assert_eq!(receive(framing, &runs, 115_200), vec![]);
+    // Unless the line was known to be idle before
+    let mut receiver = LineReceiver::new(framing, CLOCK_HZ, 115_200);
+    receiver.set_level(true);
+    let mut words = Vec::new();
+    for (level, ticks) in record(&waveform(&framing, 0x80), 115_200) {
+        receiver.push(level, ticks, |start, word| words.push((start, word)));
+    }
+    assert_eq!(words, vec![(0, Ok(0x80))]);
+    assert_eq!(receiver.now(), 1388);
+}
+
+#[test]
+fn bauds_snap_to_common_rates() {
+    assert_eq!(snap_baud(115_108), Some(115_200));
+    assert_eq!(snap_baud(400_000), Some(400_000));
+    assert_eq!(snap_baud(410_000), None);
+    assert_eq!(snap_baud(100_500), Some(100_000));
+    assert_eq!(snap_baud(1000), None);
+
+    let mut nothing: [u32; 0] = [];
+    assert_eq!(estimate_baud(&mut nothing, CLOCK_HZ), None);
+    assert_eq!(estimate_baud(&mut [0, 0], CLOCK_HZ), None);
+    // A glitch among plenty of real bits doesn't count
+    let mut runs = [1, 139, 139, 278, 139, 417, 139, 139, 139, 139, 139, 139, 139, 139, 139, 139, 139, 139, 139, 139, 139, 139, 139];
+    assert_eq!(snap_baud(estimate_baud(&mut runs, CLOCK_HZ).unwrap()), Some(115_200));
+}
+
+fn any_framing() -> impl Strategy<Value = Framing> {
+    let parity = prop_oneof![Just(Parity::None), Just(Parity::Even), Just(Parity::Odd)];
+    let stop_bits = prop_oneof![Just(StopBits::One), Just(StopBits::Two)];
+    let bit_order = prop_oneof![Just(BitOrder::LsbFirst), Just(BitOrder::MsbFirst)];
+    (7..=9u8, parity, stop_bits, bit_order, any::<bool>()).prop_map(|(data_bits, parity, stop_bits, bit_order, inverted)| {
+        Framing { data_bits, parity, stop_bits, bit_order, inverted }
+    })
+}
+
+proptest! {
+    #[test]
+    fn words_round_trip(framing in any_framing(), words in prop::collection::vec(any::<u16>(), 1..8)) {
+        let levels: String = words.iter().map(|word| waveform(&framing, *word)).collect();
+        prop_assert_eq!(levels.len(), words.len() * framing.frame_bits() as usize);
+
+        let received = sample(framing, &levels);
+        let expected: Vec<Result<u16, FrameError>> = words.iter().map(|word| Ok(word & framing.word_mask())).collect();
+        prop_assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn recorded_lines_round_trip(
+        framing in any_framing(),
+        baud in prop::sample::select(&[9600u32, 57_600, 100_000, 115_200, 400_000][..]),
+        words in prop::collection::vec((any::<u16>(), 0..20usize), 1..32),
+    ) {
+        let mut levels = String::from(if framing.idle_level() { "1" } else { "0" });
+        let mut starts = Vec::new();
+        // 0x55 alternates, so there are always single bits to find the rate from
+        for (word, gap) in [(0x55, 1)].iter().chain(&words) {
+            starts.push((levels.len() as u64 * CLOCK_HZ as u64 / baud as u64, Ok(word & framing.word_mask())));
+            levels.push_str(&waveform(&framing, *word));
+            let idle = if framing.idle_level() { '1' } else { '0' };
+            levels.extend(std::iter::repeat_n(idle, *gap));
+        }
+
+        let runs = record(&levels, baud);
+        prop_assert_eq!(receive(framing, &runs, baud), starts);
+        // Rounding to the nearest tick puts a little jitter on the edges
+        let estimate = bauds(&runs).unwrap();
+        prop_assert!(estimate.abs_diff(baud) * 100 <= baud, "{} for {}", estimate, baud);
+    }
+
+    #[test]
+    fn stop_bits_idle_and_parity_counts(framing in any_framing(), word: u16) {
+        let levels: Vec<bool> = framing.bits(word).collect();
+        prop_assert_eq!(levels.last(), Some(&framing.idle_level()));
+        if framing.parity != Parity::None {
+            // Data and parity bits together
+            let data_end = 1 + framing.data_bits as usize;
+            let ones = levels[1..=data_end].iter().filter(|level| **level != framing.inverted).count();
+            prop_assert_eq!(ones % 2 == 1, framing.parity == Parity::Odd);
+        }
+    }
+}